@@ -0,0 +1,319 @@
+use std::collections::hash_map::RandomState;
+use std::ffi::CString;
+use std::fs::{self, File, OpenOptions, Permissions};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, ErrorKind, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{fchown, MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Attempts at a temp name before giving up
+const TEMP_ATTEMPTS: usize = 16;
+
+/// The xattr calls take extra position and options arguments on macOS
+#[cfg(target_os = "macos")]
+mod sys {
+    use libc::{c_char, c_int, c_void, size_t, ssize_t};
+
+    pub unsafe fn flistxattr(fd: c_int, names: *mut c_char, size: size_t) -> ssize_t {
+        libc::flistxattr(fd, names, size, 0)
+    }
+
+    pub unsafe fn fgetxattr(
+        fd: c_int,
+        name: *const c_char,
+        value: *mut c_void,
+        size: size_t,
+    ) -> ssize_t {
+        libc::fgetxattr(fd, name, value, size, 0, 0)
+    }
+
+    pub unsafe fn fsetxattr(
+        fd: c_int,
+        name: *const c_char,
+        value: *const c_void,
+        size: size_t,
+    ) -> c_int {
+        libc::fsetxattr(fd, name, value, size, 0, 0)
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+mod sys {
+    use libc::{c_char, c_int, c_void, size_t, ssize_t};
+
+    pub unsafe fn flistxattr(fd: c_int, names: *mut c_char, size: size_t) -> ssize_t {
+        libc::flistxattr(fd, names, size)
+    }
+
+    pub unsafe fn fgetxattr(
+        fd: c_int,
+        name: *const c_char,
+        value: *mut c_void,
+        size: size_t,
+    ) -> ssize_t {
+        libc::fgetxattr(fd, name, value, size)
+    }
+
+    pub unsafe fn fsetxattr(
+        fd: c_int,
+        name: *const c_char,
+        value: *const c_void,
+        size: size_t,
+    ) -> c_int {
+        libc::fsetxattr(fd, name, value, size, 0)
+    }
+}
+
+/// Call a listxattr or getxattr, once for the size and again for the bytes
+fn read_sized<F: Fn(*mut u8, usize) -> isize>(call: F) -> io::Result<Vec<u8>> {
+    loop {
+        let size = call(std::ptr::null_mut(), 0);
+        if size < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut buf = vec![0u8; size as usize];
+        let read = call(buf.as_mut_ptr(), buf.len());
+
+        if read >= 0 {
+            buf.truncate(read as usize);
+            return Ok(buf);
+        }
+
+        // ERANGE if it grew since we asked for the size
+        let e = io::Error::last_os_error();
+        if e.raw_os_error() != Some(libc::ERANGE) {
+            return Err(e);
+        }
+    }
+}
+
+fn xattr_names(file: &File) -> io::Result<Vec<CString>> {
+    let fd = file.as_raw_fd();
+    let names = read_sized(|buf, size| unsafe { sys::flistxattr(fd, buf as *mut _, size) })?;
+
+    Ok(names
+        .split(|b| *b == 0)
+        .filter(|name| !name.is_empty())
+        .filter_map(|name| CString::new(name).ok())
+        .collect())
+}
+
+fn get_xattr(file: &File, name: &CString) -> io::Result<Vec<u8>> {
+    let fd = file.as_raw_fd();
+    read_sized(|buf, size| unsafe { sys::fgetxattr(fd, name.as_ptr(), buf as *mut _, size) })
+}
+
+fn set_xattr(file: &File, name: &CString, value: &[u8]) -> io::Result<()> {
+    let set = unsafe {
+        sys::fsetxattr(
+            file.as_raw_fd(),
+            name.as_ptr(),
+            value.as_ptr() as *const _,
+            value.len(),
+        )
+    };
+
+    match set {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+/// Hex no other launchk run would pick, so temp names cannot be guessed
+fn random_suffix() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Must get ts")
+        .as_nanos();
+
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(nanos);
+    hasher.write_u32(process::id());
+
+    format!("{:016x}", hasher.finish())
+}
+
+/// Create a new file readable only by us in `dir`, failing rather than
+/// opening anything already at the name (e.g. a planted symlink)
+pub fn create_temp(dir: &Path, prefix: &str) -> io::Result<(PathBuf, File)> {
+    for _ in 0..TEMP_ATTEMPTS {
+        let path = dir.join(format!("{}.{}", prefix, random_suffix()));
+        let created = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path);
+
+        match created {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(io::Error::new(
+        ErrorKind::AlreadyExists,
+        format!("No free temp name in {}", dir.display()),
+    ))
+}
+
+/// Write `bytes` to a temp file next to `path`, with the owner, mode and
+/// xattrs of the file it replaces, then fsync and rename it over `path`.
+/// A crash leaves either the old file or the new one, never part of one.
+/// Symlinks are not followed.
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let existing = match fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_symlink() => {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("{} is a symlink, not following it", path.display()),
+            ))
+        }
+        Ok(meta) if !meta.is_file() => {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("{} is not a file", path.display()),
+            ))
+        }
+        Ok(meta) => Some(meta),
+        Err(e) if e.kind() == ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "No file name"))?;
+
+    let prefix = format!(".{}", String::from_utf8_lossy(file_name.as_bytes()));
+    let (temp_path, mut temp) = create_temp(dir, &prefix)?;
+
+    let written = (|| {
+        temp.write_all(bytes)?;
+
+        if let Some(meta) = &existing {
+            let original = OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_NOFOLLOW)
+                .open(path)?;
+
+            for name in xattr_names(&original)? {
+                match set_xattr(&temp, &name, &get_xattr(&original, &name)?) {
+                    Ok(()) => {}
+                    // e.g. com.apple.rootless, or a filesystem without xattrs
+                    Err(e) if matches!(e.raw_os_error(), Some(libc::EPERM | libc::ENOTSUP)) => {
+                        log::debug!("write_atomic: skipping xattr {:?}: {}", name, e)
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+
+            let temp_meta = temp.metadata()?;
+            if (temp_meta.uid(), temp_meta.gid()) != (meta.uid(), meta.gid()) {
+                fchown(&temp, Some(meta.uid()), Some(meta.gid()))?;
+            }
+
+            // After chown, which clears setuid and setgid
+            temp.set_permissions(Permissions::from_mode(meta.mode() & 0o7777))?;
+        } else {
+            temp.set_permissions(Permissions::from_mode(0o644))?;
+        }
+
+        temp.sync_all()?;
+        fs::rename(&temp_path, path)?;
+
+        // The rename itself
+        File::open(dir)?.sync_all()
+    })();
+
+    if written.is_err() {
+        fs::remove_file(&temp_path).ok();
+    }
+
+    written
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+    use std::fs::{self, File, Permissions};
+    use std::os::unix::fs::{chown, symlink, MetadataExt, PermissionsExt};
+
+    use super::{create_temp, get_xattr, set_xattr, write_atomic};
+    use crate::launchd::test_util::scratch;
+
+    #[test]
+    fn replaces_keeping_metadata() {
+        let dir = scratch("atomic");
+        let path = dir.join("com.example.atomic.plist");
+        fs::write(&path, "old").unwrap();
+        fs::set_permissions(&path, Permissions::from_mode(0o640)).unwrap();
+
+        // Only where the filesystem has user xattrs, and as root for chown
+        let xattr = CString::new("user.launchk").unwrap();
+        let has_xattrs = set_xattr(&File::open(&path).unwrap(), &xattr, b"kept").is_ok();
+        let as_root = chown(&path, Some(1), Some(1)).is_ok();
+
+        write_atomic(&path, b"new").unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        let meta = fs::metadata(&path).unwrap();
+        assert_eq!(meta.mode() & 0o7777, 0o640);
+
+        if has_xattrs {
+            assert_eq!(
+                get_xattr(&File::open(&path).unwrap(), &xattr).unwrap(),
+                b"kept"
+            );
+        }
+
+        if as_root {
+            assert_eq!((meta.uid(), meta.gid()), (1, 1));
+        }
+
+        // No temp files left behind
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_symlinks() {
+        let dir = scratch("atomic-symlink");
+        let target = dir.join("target.plist");
+        let link = dir.join("link.plist");
+        fs::write(&target, "old").unwrap();
+        symlink(&target, &link).unwrap();
+
+        assert!(write_atomic(&link, b"new").is_err());
+        assert_eq!(fs::read(&target).unwrap(), b"old");
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn temp_names() {
+        let dir = scratch("atomic-temp");
+
+        let (a, _) = create_temp(&dir, "edit").unwrap();
+        let (b, _) = create_temp(&dir, "edit").unwrap();
+
+        assert_ne!(a, b);
+        assert_eq!(fs::metadata(&a).unwrap().mode() & 0o777, 0o600);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,272 +1,547 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use std::convert::TryFrom;
-use std::ffi::CString;
-use xpc_sys::{object::xpc_shmem::XPCShmem, rs_geteuid, MAP_SHARED};
-
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::os::unix::prelude::OwnedFd;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::launchd::atomic_write::create_temp;
+use crate::launchd::domain_info::{parse_domain_info, DomainInfo};
 use crate::launchd::entry_status::ENTRY_STATUS_CACHE;
-use regex::Regex;
-use std::iter::FromIterator;
-use std::slice::from_raw_parts;
+use crate::launchd::error::{LaunchdError, LaunchdErrorKind};
+use crate::launchd::overrides::{parse_overrides, OverrideState};
+use crate::launchd::routine::LaunchdRoutine;
 use xpc_sys::api::dict_builder::DictBuilder;
-use xpc_sys::api::pipe_routine::{handle_reply_dict_errors, pipe_interface_routine};
+use xpc_sys::api::transport::XPCTransport;
 use xpc_sys::enums::DomainType;
-use xpc_sys::object::try_xpc_into_rust::TryXPCIntoRust;
 use xpc_sys::object::xpc_error::XPCError;
-use xpc_sys::object::xpc_object::XPCHashMap;
-
-pub fn find_in_all<S: Into<String>>(label: S) -> Result<(DomainType, XPCHashMap), XPCError> {
-    let label_string = label.into();
+use xpc_sys::object::xpc_value::{XPCFd, XPCValue, XPCValueMap};
+use xpc_sys::service_target::ServiceTarget;
 
-    for domain_type in DomainType::System as u64..=DomainType::RequestorDomain as u64 {
-        let dict: XPCHashMap = HashMap::new()
-            .entry("handle", 0u64)
-            .entry("type", domain_type)
-            .entry("name", label_string.clone());
+use serde::Deserialize;
 
-        let response = pipe_interface_routine(None, 815, dict, None)
-            .and_then(handle_reply_dict_errors);
+/// Reply of routines that write into a shmem region
+#[derive(Debug, Deserialize)]
+struct ShmemReply {
+    #[serde(rename = "bytes-written")]
+    bytes_written: u64,
+}
 
-        if response.is_ok() {
-            return Ok((domain_type.into(), response.unwrap().to_rust()?));
-        }
-    }
+/// Reply of list for a domain
+#[derive(Debug, Deserialize)]
+struct ListReply {
+    services: HashMap<String, ListedService>,
+}
 
-    Err(XPCError::NotFound)
+/// A service in the list reply for its domain, with the keys of the
+/// `launchctl list` reply in doc/launchctl_messages.md
+#[derive(Debug, Clone, Deserialize)]
+pub struct ListedService {
+    /// 0 if not running
+    #[serde(default, rename = "PID")]
+    pub pid: i64,
+    /// wait(2) status of the last exit
+    #[serde(rename = "LastExitStatus")]
+    pub status: Option<i64>,
+    #[serde(rename = "LimitLoadToSessionType")]
+    pub limit_load_to_session_type: Option<String>,
 }
 
-/// Query for jobs in a domain
-pub fn list(domain_type: DomainType, name: Option<String>) -> Result<XPCHashMap, XPCError> {
-    let dict = HashMap::new()
-        .handle_and_type_from_domain(domain_type)
-        .entry_if_present("name", name);
+#[derive(Debug, Deserialize)]
+struct BlameReply {
+    reason: String,
+}
 
-    pipe_interface_routine(None, 815, dict, None)
-        .and_then(handle_reply_dict_errors)
-        .and_then(|o| o.to_rust())
+/// Send a routine with a "shmem" region for it to write into, and return
+/// the bytes written
+fn call_shmem(
+    transport: &dyn XPCTransport,
+    routine: LaunchdRoutine,
+    mut request: XPCValueMap,
+) -> Result<Vec<u8>, LaunchdError> {
+    let reply: ShmemReply = routine.call_as(transport, &mut request)?;
+
+    // Only copy out what was written, the region is mapped
+    let bytes_written = usize::try_from(reply.bytes_written).unwrap();
+
+    match request.remove("shmem") {
+        Some(XPCValue::Shmem(region)) => Ok(region.read(bytes_written)),
+        _ => Err(LaunchdError::from_xpc(
+            routine,
+            &request,
+            XPCError::NotFound,
+        )),
+    }
 }
 
-pub fn list_services(domain_type: DomainType, name: Option<String>) -> Result<XPCHashMap, XPCError> {
-    list(domain_type, name)?
-        .get("services")
-        .ok_or(XPCError::NotFound)?
-        .to_rust()
+/// Send a routine with an "fd" for it to print into, like launchctl does
+/// with its tty, and return what was printed
+fn call_fd(
+    transport: &dyn XPCTransport,
+    routine: LaunchdRoutine,
+    request: XPCValueMap,
+) -> Result<Vec<u8>, LaunchdError> {
+    let io_error =
+        |e: io::Error| LaunchdError::from_xpc(routine, &request, XPCError::IOError(e.to_string()));
+
+    // A file and not a pipe, so launchd never blocks on a full buffer
+    let (path, file) = create_temp(&env::temp_dir(), "launchk-print").map_err(io_error)?;
+    let reader = File::open(&path);
+    fs::remove_file(&path).map_err(io_error)?;
+    let mut reader = reader.map_err(io_error)?;
+
+    let mut request = request
+        .clone()
+        .entry("fd", XPCValue::Fd(XPCFd::from(OwnedFd::from(file))));
+    routine.call(transport, &mut request)?;
+
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes).map_err(io_error)?;
+    Ok(bytes)
 }
 
-/// Query for jobs across all domain types
-pub fn list_all() -> HashSet<String> {
-    let system_list = list_services(DomainType::System, None).unwrap();
-    let user_list = if rs_geteuid() != 0 {
-        list_services(DomainType::User, None).unwrap()
-    } else {
-        HashMap::new()
-    };
+/// Query for jobs in a domain, or a single job if the target has a name
+pub fn list(
+    transport: &dyn XPCTransport,
+    target: &ServiceTarget,
+) -> Result<XPCValue, LaunchdError> {
+    let mut request = XPCValueMap::new().with_service_target(target);
 
-    HashSet::from_iter(system_list.keys().cloned().chain(user_list.keys().cloned()))
+    LaunchdRoutine::List.call(transport, &mut request)
 }
 
-pub fn blame<S: Into<String>>(label: S, domain_type: DomainType) -> Result<String, XPCError> {
-    let label_string = label.into();
-    log::debug!("blame: {} {}", &label_string, domain_type);
+/// Query for jobs across all domain types, with one list per domain. A
+/// label listed in several domains is in the first one, by domain type.
+pub fn list_all(transport: &dyn XPCTransport) -> HashMap<String, (ServiceTarget, ListedService)> {
+    let mut services: HashMap<String, (ServiceTarget, ListedService)> = HashMap::new();
 
-    ENTRY_STATUS_CACHE
-        .lock()
-        .expect("Must invalidate")
-        .remove(&label_string);
+    for domain_type in DomainType::System as u64..=DomainType::RequestorDomain as u64 {
+        let target = ServiceTarget::from(DomainType::from(domain_type));
+        let mut request = XPCValueMap::new().with_service_target(&target);
+
+        let reply: ListReply = match LaunchdRoutine::List.call_as(transport, &mut request) {
+            Ok(reply) => reply,
+            Err(e) => {
+                log::debug!("list_all: {}", e);
+                continue;
+            }
+        };
+
+        for (label, service) in reply.services {
+            services
+                .entry(label)
+                .or_insert_with(|| (target.clone(), service));
+        }
+    }
 
-    let dict = HashMap::new()
-        .entry("name", label_string)
-        .handle_and_type_from_domain(domain_type);
+    services
+}
 
-    let response: XPCHashMap = pipe_interface_routine(None, 707, dict, None)
-        .and_then(handle_reply_dict_errors)
-        .and_then(|o| o.to_rust())?;
+/// Drop the cached status of the target's service, e.g. after changing it
+fn invalidate_entry_status(target: &ServiceTarget) {
+    if let Some(name) = &target.name {
+        ENTRY_STATUS_CACHE
+            .lock()
+            .expect("Must invalidate")
+            .remove(name);
+    }
+}
 
-    let reason: String = response
-        .get("reason")
-        .ok_or(XPCError::NotFound)
-        .and_then(|o| o.to_rust())?;
+pub fn blame(transport: &dyn XPCTransport, target: &ServiceTarget) -> Result<String, LaunchdError> {
+    log::debug!("blame: {}", target);
+    invalidate_entry_status(target);
 
-    Ok(reason)
-}
+    let mut request = XPCValueMap::new().with_service_target(target);
 
-pub fn bootout<S: Into<String>>(label: S, domain_type: DomainType) -> Result<XPCHashMap, XPCError> {
-    let label_string = label.into();
-    log::debug!("bootout: {} {}", &label_string, domain_type);
+    let reply: BlameReply = LaunchdRoutine::Blame.call_as(transport, &mut request)?;
+    Ok(reply.reason)
+}
 
-    ENTRY_STATUS_CACHE
-        .lock()
-        .expect("Must invalidate")
-        .remove(&label_string);
+pub fn bootout(
+    transport: &dyn XPCTransport,
+    target: &ServiceTarget,
+) -> Result<XPCValue, LaunchdError> {
+    log::debug!("bootout: {}", target);
+    invalidate_entry_status(target);
 
-    let dict = HashMap::new()
-        .entry("name", label_string)
+    let mut request = XPCValueMap::new()
         .entry("no-einprogress", true)
-        .handle_and_type_from_domain(domain_type);
+        .with_service_target(target);
 
-    pipe_interface_routine(None, 801, dict, None)
-        .and_then(handle_reply_dict_errors)
-        .and_then(|o| o.to_rust())
+    LaunchdRoutine::Bootout.call(transport, &mut request)
 }
 
+/// Load the plist into the target's domain. launchd reads the label from
+/// the plist, the target's name is only used to invalidate its status.
 pub fn bootstrap<S: Into<String>>(
-    label: S,
-    domain_type: DomainType,
+    transport: &dyn XPCTransport,
+    target: &ServiceTarget,
     plist_path: S,
-) -> Result<XPCHashMap, XPCError> {
-    let label_string = label.into();
-    log::debug!("bootstrap: {} {}", &label_string, domain_type);
+) -> Result<XPCValue, LaunchdError> {
+    log::debug!("bootstrap: {}", target);
+    invalidate_entry_status(target);
 
-    ENTRY_STATUS_CACHE
-        .lock()
-        .expect("Must invalidate")
-        .remove(&label_string);
-
-    let dict = HashMap::new()
+    let mut request = XPCValueMap::new()
         .entry("by-cli", true)
         .entry("paths", vec![plist_path.into()])
-        .handle_and_type_from_domain(domain_type);
+        .with_service_target(&target.domain_target());
 
-    pipe_interface_routine(None, 800, dict, None)
-        .and_then(handle_reply_dict_errors)
-        .and_then(|o| o.to_rust())
+    LaunchdRoutine::Bootstrap.call(transport, &mut request)
 }
 
-pub fn enable<S: Into<String>>(label: S, domain_type: DomainType) -> Result<XPCHashMap, XPCError> {
-    let label_string = label.into();
-
-    let dict = HashMap::new()
-        .entry("name", label_string.clone())
-        .entry("names", vec![label_string])
-        .handle_and_type_from_domain(domain_type);
+/// What kickstart does with a service that is already running
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum KickstartMode {
+    /// Leave it running, like `launchctl kickstart`
+    IfNotRunning,
+    /// Kill it and start it again, like `launchctl kickstart -k` but with
+    /// a separate SIGKILL, see kickstart
+    KillRunning,
+}
 
-    pipe_interface_routine(None, 808, dict, None)
-        .and_then(handle_reply_dict_errors)
-        .and_then(|o| o.to_rust())
+/// Signals for kill, with their macOS numbers
+pub const SIGNALS: [(&str, i64); 9] = [
+    ("SIGTERM", 15),
+    ("SIGKILL", 9),
+    ("SIGHUP", 1),
+    ("SIGINT", 2),
+    ("SIGQUIT", 3),
+    ("SIGUSR1", 30),
+    ("SIGUSR2", 31),
+    ("SIGSTOP", 17),
+    ("SIGCONT", 19),
+];
+
+const SIGKILL: i64 = 9;
+
+/// How long restart waits for the killed process to go away
+const KILL_WAIT: Duration = Duration::from_secs(2);
+
+/// PID of the target's service, if it is running
+fn service_pid(transport: &dyn XPCTransport, target: &ServiceTarget) -> Option<i64> {
+    list(transport, target)
+        .ok()?
+        .get("service")
+        .and_then(|s| s.get("PID"))
+        .and_then(|p| p.as_i64())
+        .filter(|p| *p > 0)
 }
 
-pub fn disable<S: Into<String>>(label: S, domain_type: DomainType) -> Result<XPCHashMap, XPCError> {
-    let label_string = label.into();
+/// Start the target's service. Ok(None) if it was already running and
+/// left alone, or launchd restarted it (e.g. KeepAlive) after the kill.
+/// An error if the killed process is still running after KILL_WAIT.
+///
+/// KillRunning does not send launchd's own kickstart -k option, as its
+/// request key has not been captured (see doc/launchctl_messages.md).
+/// It sends SIGKILL (812), polls the PID, then 702, which races launchd:
+/// the process can exit just after KILL_WAIT, so 702 starts nothing, or
+/// launchd can restart it between polls, so 702 is not sent at all.
+pub fn kickstart(
+    transport: &dyn XPCTransport,
+    target: &ServiceTarget,
+    mode: KickstartMode,
+) -> Result<Option<XPCValue>, LaunchdError> {
+    log::debug!("kickstart: {} {:?}", target, mode);
+    invalidate_entry_status(target);
+
+    let mut request = XPCValueMap::new().with_service_target(target);
+
+    if let Some(pid) = service_pid(transport, target) {
+        if mode == KickstartMode::IfNotRunning {
+            return Ok(None);
+        }
+
+        kill(transport, target, SIGKILL)?;
 
-    let dict = HashMap::new()
-        .entry("name", label_string.clone())
-        .entry("names", vec![label_string])
-        .handle_and_type_from_domain(domain_type);
+        // Starting a job that is still exiting does nothing
+        let killed_at = Instant::now();
+        while service_pid(transport, target) == Some(pid) && killed_at.elapsed() < KILL_WAIT {
+            thread::sleep(Duration::from_millis(50));
+        }
 
-    pipe_interface_routine(None, 809, dict, None)
-        .and_then(handle_reply_dict_errors)
-        .and_then(|o| o.to_rust())
+        match service_pid(transport, target) {
+            Some(new_pid) if new_pid != pid => return Ok(None),
+            Some(_) => {
+                return Err(LaunchdError::from_kind(
+                    LaunchdRoutine::Kickstart,
+                    &request,
+                    LaunchdErrorKind::StillRunning,
+                    format!("PID {} still running after SIGKILL", pid),
+                ))
+            }
+            None => {}
+        }
+    }
+
+    LaunchdRoutine::Kickstart
+        .call(transport, &mut request)
+        .map(Some)
 }
 
-/// Create a shared shmem region for the XPC routine to write
-/// dumpstate contents into, and return the bytes written and
-/// shmem region
-pub fn dumpstate() -> Result<(usize, XPCShmem), XPCError> {
-    let shmem = XPCShmem::allocate_task_self(
-        0x1400000,
-        MAP_SHARED,
-    )?;
-
-    let dict = HashMap::new()
-        .entry("shmem", &shmem)
-        .handle_and_type_from_domain(DomainType::System);
+/// Kill and start the target's service, or just start it
+pub fn restart(
+    transport: &dyn XPCTransport,
+    target: &ServiceTarget,
+) -> Result<Option<XPCValue>, LaunchdError> {
+    kickstart(transport, target, KickstartMode::KillRunning)
+}
 
-    let response: XPCHashMap = pipe_interface_routine(None, 834, dict, None)
-        .and_then(handle_reply_dict_errors)
-        .and_then(|o| o.to_rust())?;
+/// Send signal to the target's service
+pub fn kill(
+    transport: &dyn XPCTransport,
+    target: &ServiceTarget,
+    signal: i64,
+) -> Result<XPCValue, LaunchdError> {
+    log::debug!("kill: {} {}", target, signal);
+    invalidate_entry_status(target);
 
-    let bytes_written: u64 = response
-        .get("bytes-written")
-        .ok_or(XPCError::NotFound)?
-        .to_rust()?;
+    let mut request = XPCValueMap::new()
+        .entry("signal", signal)
+        .with_service_target(target);
 
-    Ok((usize::try_from(bytes_written).unwrap(), shmem))
+    LaunchdRoutine::Kill.call(transport, &mut request)
 }
 
-pub fn dumpjpcategory() -> Result<(usize, XPCShmem), XPCError> {
-    let shmem = XPCShmem::allocate_task_self(
-        0x1400000,
-        MAP_SHARED,
-    )?;
+pub fn enable(
+    transport: &dyn XPCTransport,
+    target: &ServiceTarget,
+) -> Result<XPCValue, LaunchdError> {
+    let mut request = XPCValueMap::new()
+        .with_service_target(target)
+        .entry_if_present("names", target.name.clone().map(|n| vec![n]));
 
-    let dict = HashMap::new()
-        .entry("shmem", &shmem)
-        .handle_and_type_from_domain(DomainType::System);
+    LaunchdRoutine::Enable.call(transport, &mut request)
+}
 
-    let response: XPCHashMap = pipe_interface_routine(None, 837, dict, None)
-        .and_then(handle_reply_dict_errors)
-        .and_then(|o| o.to_rust())?;
+pub fn disable(
+    transport: &dyn XPCTransport,
+    target: &ServiceTarget,
+) -> Result<XPCValue, LaunchdError> {
+    let mut request = XPCValueMap::new()
+        .with_service_target(target)
+        .entry_if_present("names", target.name.clone().map(|n| vec![n]));
 
-    let bytes_written: u64 = response
-        .get("bytes-written")
-        .ok_or(XPCError::NotFound)?
-        .to_rust()?;
+    LaunchdRoutine::Disable.call(transport, &mut request)
+}
+
+/// Have launchd write dumpstate contents into a shmem region
+/// and return what it wrote
+pub fn dumpstate(transport: &dyn XPCTransport) -> Result<Vec<u8>, LaunchdError> {
+    let request = XPCValueMap::new()
+        .entry("shmem", XPCValue::shmem(0x1400000))
+        .handle_and_type_from_domain(DomainType::System);
 
-    Ok((usize::try_from(bytes_written).unwrap(), shmem))
+    call_shmem(transport, LaunchdRoutine::DumpState, request)
 }
 
-pub fn procinfo(pid: i64) -> Result<(usize, XPCShmem), XPCError> {
-    let shmem = XPCShmem::allocate_task_self(
-        0x1400000,
-        MAP_SHARED,
-    )?;
+pub fn dumpjpcategory(transport: &dyn XPCTransport) -> Result<Vec<u8>, LaunchdError> {
+    let request = XPCValueMap::new()
+        .entry("shmem", XPCValue::shmem(0x1400000))
+        .handle_and_type_from_domain(DomainType::System);
+
+    call_shmem(transport, LaunchdRoutine::DumpJPCategory, request)
+}
 
-    let dict = HashMap::new()
-        .entry("shmem", &shmem)
+pub fn procinfo(transport: &dyn XPCTransport, pid: i64) -> Result<Vec<u8>, LaunchdError> {
+    let request = XPCValueMap::new()
+        .entry("shmem", XPCValue::shmem(0x1400000))
         .entry("pid", pid);
 
-    let response: XPCHashMap = pipe_interface_routine(None, 708, dict, None)
-        .and_then(handle_reply_dict_errors)
-        .and_then(|o| o.to_rust())?;
+    call_shmem(transport, LaunchdRoutine::ProcInfo, request)
+}
 
-    let bytes_written: u64 = response
-        .get("bytes-written")
-        .ok_or(XPCError::NotFound)?
-        .to_rust()?;
+/// launchctl print for the target's service
+pub fn print_service(
+    transport: &dyn XPCTransport,
+    target: &ServiceTarget,
+) -> Result<Vec<u8>, LaunchdError> {
+    let request = XPCValueMap::new().with_service_target(target);
+    call_fd(transport, LaunchdRoutine::Print, request)
+}
 
-    Ok((usize::try_from(bytes_written).unwrap(), shmem))
+/// launchctl print for the target's domain
+pub fn print_domain(
+    transport: &dyn XPCTransport,
+    target: &ServiceTarget,
+) -> Result<Vec<u8>, LaunchdError> {
+    let request = XPCValueMap::new()
+        .entry("shmem", XPCValue::shmem(0x1400000))
+        .with_service_target(&target.domain_target());
+
+    call_shmem(transport, LaunchdRoutine::PrintDomain, request)
+}
+
+/// Audit session of uid's gui domain, which keys its login domain
+pub fn gui_asid(transport: &dyn XPCTransport, uid: u64) -> Option<u64> {
+    let bytes = print_domain(transport, &ServiceTarget::gui(uid)).ok()?;
+    parse_domain_info(&String::from_utf8_lossy(&bytes))
+        .asid
+        .filter(|a| *a > 0)
 }
 
-pub fn read_disabled(domain_type: DomainType) -> Result<(usize, XPCShmem), XPCError> {
-    let shmem = XPCShmem::allocate_task_self(
-        1_000_000,
-        MAP_SHARED,
-    )?;
+/// Print system, and the user, gui and login domains of uid if
+/// launchd has them
+pub fn print_domains(
+    transport: &dyn XPCTransport,
+    uid: u64,
+) -> Result<Vec<(ServiceTarget, DomainInfo)>, LaunchdError> {
+    let print = |target: ServiceTarget| {
+        print_domain(transport, &target)
+            .map(|bytes| (target, parse_domain_info(&String::from_utf8_lossy(&bytes))))
+    };
 
-    let dict = HashMap::new()
-        .entry("shmem", &shmem)
-        .handle_and_type_from_domain(domain_type);
+    let mut domains = vec![print(ServiceTarget::system())?];
+    domains.extend(print(ServiceTarget::user(uid)).ok());
 
-    let response: XPCHashMap = pipe_interface_routine(None, 828, dict, None)
-        .and_then(handle_reply_dict_errors)
-        .and_then(|o| o.to_rust())?;
+    if let Ok(gui) = print(ServiceTarget::gui(uid)) {
+        // The login domain is keyed by the gui domain's audit session
+        let login = gui.1.asid.filter(|a| *a > 0).map(ServiceTarget::login);
+        domains.push(gui);
+        domains.extend(login.and_then(|l| print(l).ok()));
+    }
+
+    Ok(domains)
+}
 
-    let bytes_written: u64 = response
-        .get("bytes-written")
-        .ok_or(XPCError::NotFound)?
-        .to_rust()?;
+/// launchctl print-disabled for the target's domain
+pub fn read_disabled(
+    transport: &dyn XPCTransport,
+    target: &ServiceTarget,
+) -> Result<Vec<u8>, LaunchdError> {
+    let request = XPCValueMap::new()
+        .entry("shmem", XPCValue::shmem(1_000_000))
+        .with_service_target(&target.domain_target());
 
-    Ok((usize::try_from(bytes_written).unwrap(), shmem))
+    call_shmem(transport, LaunchdRoutine::ReadDisabled, request)
 }
 
-lazy_static! {
-    static ref DISABLED_RE: Regex = Regex::new(r#""([\w.]+)" => disabled"#).expect("Must compile");
+/// The enabled and disabled overrides in the target's domain
+pub fn read_overrides(
+    transport: &dyn XPCTransport,
+    target: &ServiceTarget,
+) -> Result<HashMap<String, OverrideState>, LaunchdError> {
+    let bytes = read_disabled(transport, target)?;
+    Ok(parse_overrides(&String::from_utf8_lossy(&bytes)))
 }
 
-pub fn read_disabled_hashset(domain_type: DomainType) -> Result<HashSet<String>, XPCError> {
-    let (sz, shmem) = read_disabled(domain_type)?;
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
 
-    // Copy out of shmem and make a CString
-    let slice: &[u8] = unsafe { from_raw_parts(shmem.region as *const _, sz) };
-    let data: Vec<u8> = slice.to_vec();
-    let cs = unsafe { CString::from_vec_unchecked(data) };
+    use xpc_sys::api::transport::ReplayTransport;
+    use xpc_sys::service_target::ServiceTarget;
 
-    // Find all the quoted service names
-    let services: Vec<String> = DISABLED_RE
-        .captures_iter(cs.to_str().unwrap())
-        .flat_map(|c| c.iter().flatten().map(|m| m.as_str().to_string()).last())
-        .collect();
+    use crate::launchd::error::{LaunchdError, LaunchdErrorKind};
+    use crate::launchd::overrides::OverrideState;
 
-    let mut hs = HashSet::new();
-    hs.extend(services.iter().cloned());
-    Ok(hs)
+    use super::{
+        bootout, bootstrap, disable, dumpstate, enable, kickstart, list_all, read_overrides,
+        restart, KickstartMode,
+    };
+
+    fn replay(fixture: &str) -> ReplayTransport {
+        ReplayTransport::from_reader(Cursor::new(fixture)).expect("Must read fixture")
+    }
+
+    #[test]
+    fn list_all_domains() {
+        // Synthetic, see tests/fixtures/README.md. Only system is in it,
+        // the other domains fail and are skipped
+        let transport = replay(include_str!("../../tests/fixtures/list.jsonl"));
+        let services = list_all(&transport);
+
+        let mut labels: Vec<&String> = services.keys().collect();
+        labels.sort();
+
+        assert_eq!(labels, vec!["com.apple.lskdd", "com.apple.usbmuxd"]);
+
+        let (target, usbmuxd) = services.get("com.apple.usbmuxd").unwrap();
+        assert_eq!(target, &ServiceTarget::system());
+        assert_eq!(usbmuxd.pid, 165);
+        assert_eq!(usbmuxd.status, Some(0));
+        assert_eq!(
+            usbmuxd.limit_load_to_session_type.as_deref(),
+            Some("System")
+        );
+    }
+
+    #[test]
+    fn bootstrap_and_bootout() {
+        let transport = replay(include_str!("../../tests/fixtures/bootstrap_bootout.jsonl"));
+        let plist = "/Library/LaunchDaemons/com.example.agent.plist";
+        let target: ServiceTarget = "system/com.example.agent".parse().unwrap();
+
+        assert!(bootstrap(&transport, &target, plist).is_ok());
+        assert!(bootout(&transport, &target).is_ok());
+
+        // The second bootout was recorded failing with ESRCH
+        assert!(matches!(
+            bootout(&transport, &target),
+            Err(LaunchdError {
+                kind: LaunchdErrorKind::NotFound,
+                code: Some(3),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn enable_and_disable() {
+        let transport = replay(include_str!("../../tests/fixtures/enable_disable.jsonl"));
+
+        let target = ServiceTarget::system().with_name("com.example.agent");
+
+        assert!(disable(&transport, &target).is_ok());
+        assert!(enable(&transport, &target).is_ok());
+    }
+
+    #[test]
+    fn kickstart_and_restart() {
+        let fixture = include_str!("../../tests/fixtures/kickstart_kill.jsonl");
+        let target = ServiceTarget::system().with_name("com.example.agent");
+
+        // Only the list reply with a PID: kickstart leaves it running
+        let running = replay(fixture.lines().next().unwrap());
+        assert!(matches!(
+            kickstart(&running, &target, KickstartMode::IfNotRunning),
+            Ok(None)
+        ));
+
+        // SIGKILL, wait for the PID to go away, then kickstart
+        let transport = replay(fixture);
+        let reply = restart(&transport, &target).unwrap().unwrap();
+        assert_eq!(reply.get("pid").and_then(|p| p.as_i64()), Some(712));
+
+        // The PID never goes away
+        let stuck = replay(&fixture.lines().take(2).collect::<Vec<&str>>().join("\n"));
+        assert!(matches!(
+            restart(&stuck, &target),
+            Err(LaunchdError {
+                kind: LaunchdErrorKind::StillRunning,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn shmem_routines() {
+        let transport = replay(include_str!("../../tests/fixtures/shmem.jsonl"));
+
+        assert_eq!(
+            dumpstate(&transport).unwrap(),
+            b"com.apple.xpc.launchd.domain.system = {\n".to_vec()
+        );
+
+        let overrides = read_overrides(&transport, &ServiceTarget::system()).unwrap();
+        assert_eq!(
+            overrides.get("com.apple.ftpd"),
+            Some(&OverrideState::Disabled)
+        );
+        assert_eq!(
+            overrides.get("com.apple.usbmuxd"),
+            Some(&OverrideState::Enabled)
+        );
+    }
 }
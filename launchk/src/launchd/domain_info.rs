@@ -0,0 +1,210 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::launchd::dumpstate::{parse_blocks, StateBlock, StateEntry};
+use crate::launchd::overrides::{block_overrides, OverrideState};
+
+/// A row of the domain's services table
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct DomainService {
+    pub label: String,
+    /// None if it is not running
+    pub pid: Option<i64>,
+    pub last_exit_code: Option<i64>,
+}
+
+/// A row of the domain's endpoints table
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct DomainEndpoint {
+    pub name: String,
+    pub port: Option<u32>,
+}
+
+/// launchctl print for a domain
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct DomainInfo {
+    /// As launchd printed it, e.g. `system` or `gui/501`
+    pub name: String,
+    pub active_count: Option<u64>,
+    pub on_demand_count: Option<u64>,
+    pub services: Vec<DomainService>,
+    pub endpoints: Vec<DomainEndpoint>,
+    pub overrides: BTreeMap<String, OverrideState>,
+    /// The audit session, e.g. for the login domain of a gui domain
+    pub asid: Option<u64>,
+    /// The domain's properties block
+    pub properties: BTreeMap<String, String>,
+}
+
+/// Parse print output for a domain. Anything missing is left empty.
+pub fn parse_domain_info(text: &str) -> DomainInfo {
+    let block = parse_blocks(text).into_iter().next().unwrap_or_default();
+    let count = |key: &str| block.get(key).and_then(|c| c.parse().ok());
+
+    DomainInfo {
+        name: block.name.clone(),
+        active_count: count("active count"),
+        on_demand_count: count("on-demand count"),
+        services: items(&block, "services").filter_map(service).collect(),
+        endpoints: items(&block, "endpoints").filter_map(endpoint).collect(),
+        overrides: block
+            .child("disabled services")
+            .map(|d| block_overrides(d).collect())
+            .unwrap_or_default(),
+        asid: block
+            .child("security context")
+            .and_then(|c| c.get("asid"))
+            .and_then(|a| a.parse().ok()),
+        properties: block
+            .child("properties")
+            .map(|p| {
+                p.properties()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default(),
+    }
+}
+
+/// The lines in the block called name
+fn items<'a>(block: &'a StateBlock, name: &str) -> impl Iterator<Item = &'a str> {
+    block
+        .child(name)
+        .into_iter()
+        .flat_map(|c| c.entries.iter())
+        .filter_map(|e| match e {
+            StateEntry::Item(i) => Some(i.as_str()),
+            _ => None,
+        })
+}
+
+/// `185      -     com.apple.usbmuxd`: PID (0 if not running),
+/// last exit code and label
+fn service(line: &str) -> Option<DomainService> {
+    let mut fields = line.split_whitespace();
+    let pid = fields.next()?.parse().ok().filter(|p| *p > 0);
+    let last_exit_code = fields.next()?.parse().ok();
+
+    Some(DomainService {
+        label: fields.next()?.to_string(),
+        pid,
+        last_exit_code,
+    })
+}
+
+/// `0x1a07  M   A   com.apple.usbmuxd`: port, flags and name
+fn endpoint(line: &str) -> Option<DomainEndpoint> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+
+    Some(DomainEndpoint {
+        name: fields.last()?.to_string(),
+        port: u32::from_str_radix(fields.first()?.trim_start_matches("0x"), 16).ok(),
+    })
+}
+
+impl DomainInfo {
+    pub fn running_count(&self) -> usize {
+        self.services.iter().filter(|s| s.pid.is_some()).count()
+    }
+}
+
+impl fmt::Display for DomainInfo {
+    /// Summary for the domain details panel
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let count = |c: Option<u64>| c.map(|c| c.to_string()).unwrap_or("-".to_string());
+        let rows = [
+            ("Active", count(self.active_count)),
+            ("On-demand", count(self.on_demand_count)),
+            (
+                "Running",
+                format!("{}/{}", self.running_count(), self.services.len()),
+            ),
+            ("ASID", count(self.asid)),
+        ];
+
+        for (title, value) in rows.iter() {
+            writeln!(f, "{:<18}{}", title, value)?;
+        }
+
+        writeln!(f, "\nProperties")?;
+        for (k, v) in &self.properties {
+            writeln!(f, "  {} = {}", k, v)?;
+        }
+
+        writeln!(f, "\nServices")?;
+        for service in &self.services {
+            let number = |n: Option<i64>| n.map(|n| n.to_string()).unwrap_or("-".to_string());
+            writeln!(
+                f,
+                "  {:<8}{:<6}{}",
+                number(service.pid),
+                number(service.last_exit_code),
+                service.label
+            )?;
+        }
+
+        writeln!(f, "\nEndpoints")?;
+        for endpoint in &self.endpoints {
+            let port = endpoint
+                .port
+                .map(|p| format!("{:#x}", p))
+                .unwrap_or("-".to_string());
+            writeln!(f, "  {:<10}{}", port, endpoint.name)?;
+        }
+
+        writeln!(f, "\nOverrides")?;
+        for (label, state) in &self.overrides {
+            writeln!(f, "  {} {}", label, state)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::launchd::overrides::OverrideState;
+
+    use super::{parse_domain_info, DomainEndpoint, DomainService};
+
+    const PRINT_DOMAIN: &str = include_str!("../../tests/fixtures/print_domain.txt");
+
+    #[test]
+    fn system_domain() {
+        let info = parse_domain_info(PRINT_DOMAIN);
+
+        assert_eq!(info.name, "system");
+        assert_eq!(info.active_count, Some(575));
+        assert_eq!(info.on_demand_count, Some(0));
+        assert_eq!(info.asid, Some(0));
+        assert_eq!(info.running_count(), 1);
+        assert_eq!(
+            info.services[2],
+            DomainService {
+                label: "homebrew.mxcl.redis".to_string(),
+                pid: None,
+                last_exit_code: Some(78)
+            }
+        );
+        assert_eq!(
+            info.endpoints[1],
+            DomainEndpoint {
+                name: "com.apple.usbmuxd".to_string(),
+                port: Some(0x1a07)
+            }
+        );
+        assert_eq!(
+            info.overrides.get("com.apple.ftpd"),
+            Some(&OverrideState::Disabled)
+        );
+        assert_eq!(
+            info.properties
+                .get("shutdown-initiated")
+                .map(String::as_str),
+            Some("0")
+        );
+        assert!(info
+            .to_string()
+            .contains("  -       78    homebrew.mxcl.redis\n"));
+    }
+}
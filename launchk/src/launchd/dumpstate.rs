@@ -0,0 +1,369 @@
+use std::fmt;
+
+/// launchd's name for a domain in dumpstate, e.g.
+/// com.apple.xpc.launchd.domain.gui.501
+pub const DOMAIN_PREFIX: &str = "com.apple.xpc.launchd.domain.";
+
+/// One line of a block
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum StateEntry {
+    /// `key = value` or `"key" => value`
+    Property(String, String),
+    /// A line that is not a key-value pair, e.g. an argument
+    /// or a row of the services table
+    Item(String),
+    /// `key = { ... }`
+    Block(StateBlock),
+}
+
+/// A `name = { ... }` block of launchctl's dumpstate (and print) output
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct StateBlock {
+    pub name: String,
+    /// In the order launchd wrote them
+    pub entries: Vec<StateEntry>,
+}
+
+impl StateBlock {
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        Self {
+            name: name.into(),
+            entries: vec![],
+        }
+    }
+
+    /// The first value for key
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.iter().find_map(|e| match e {
+            StateEntry::Property(k, v) if k == key => Some(v.as_str()),
+            _ => None,
+        })
+    }
+
+    /// The first nested block with this name
+    pub fn child(&self, name: &str) -> Option<&StateBlock> {
+        self.entries.iter().find_map(|e| match e {
+            StateEntry::Block(b) if b.name == name => Some(b),
+            _ => None,
+        })
+    }
+
+    pub fn properties(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().filter_map(|e| match e {
+            StateEntry::Property(k, v) => Some((k.as_str(), v.as_str())),
+            _ => None,
+        })
+    }
+
+    fn write_indented(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        let indent = "\t".repeat(depth + 1);
+        writeln!(f, "{} = {{", self.name)?;
+
+        for entry in &self.entries {
+            match entry {
+                StateEntry::Property(k, v) => writeln!(f, "{}{} = {}", indent, k, v)?,
+                StateEntry::Item(i) => writeln!(f, "{}{}", indent, i)?,
+                StateEntry::Block(b) => {
+                    write!(f, "{}", indent)?;
+                    b.write_indented(f, depth + 1)?;
+                }
+            }
+        }
+
+        writeln!(f, "{}}}", &indent[1..])
+    }
+}
+
+impl fmt::Display for StateBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_indented(f, 0)
+    }
+}
+
+/// Services grouped by domain
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct DumpState {
+    pub domains: Vec<DumpStateDomain>,
+    /// Top level blocks that are neither a domain nor a service in one
+    pub other: Vec<StateBlock>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DumpStateDomain {
+    /// Domain properties, subdomains, endpoints, disabled services, etc.
+    pub block: StateBlock,
+    pub services: Vec<StateBlock>,
+}
+
+impl DumpState {
+    /// Keep only the services matching `keep`, and the domains they are in
+    pub fn filter_services<F: Fn(&StateBlock) -> bool>(&self, keep: F) -> DumpState {
+        let domains = self
+            .domains
+            .iter()
+            .filter_map(|d| {
+                let services: Vec<StateBlock> =
+                    d.services.iter().filter(|s| keep(s)).cloned().collect();
+
+                if services.is_empty() {
+                    None
+                } else {
+                    Some(DumpStateDomain {
+                        block: d.block.clone(),
+                        services,
+                    })
+                }
+            })
+            .collect();
+
+        DumpState {
+            domains,
+            other: vec![],
+        }
+    }
+}
+
+impl fmt::Display for DumpState {
+    /// The same layout as dumpstate: each domain followed by its services
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let blocks = self
+            .domains
+            .iter()
+            .flat_map(|d| std::iter::once(&d.block).chain(d.services.iter()))
+            .chain(self.other.iter());
+
+        for block in blocks {
+            writeln!(f, "{}", block)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parse dumpstate output. Each domain is followed by its services,
+/// which also name their domain with a `domain` property.
+pub fn parse_dumpstate(text: &str) -> DumpState {
+    let mut state = DumpState::default();
+
+    for block in parse_blocks(text) {
+        if block.name.starts_with(DOMAIN_PREFIX) {
+            state.domains.push(DumpStateDomain {
+                block,
+                services: vec![],
+            });
+            continue;
+        }
+
+        let domain = match block.get("domain") {
+            Some(name) => state.domains.iter().rposition(|d| d.block.name == name),
+            None if block.get("state").is_some() => state.domains.len().checked_sub(1),
+            None => None,
+        };
+
+        match domain {
+            Some(i) => state.domains[i].services.push(block),
+            None => state.other.push(block),
+        }
+    }
+
+    state
+}
+
+/// Parse launchctl's block format into its top level blocks, skipping
+/// lines outside a block
+pub fn parse_blocks(text: &str) -> Vec<StateBlock> {
+    parse_entries(text)
+        .entries
+        .into_iter()
+        .filter_map(|e| match e {
+            StateEntry::Block(b) => Some(b),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Parse launchctl's block format as the contents of an unnamed block.
+/// Keys and lines launchk does not know about are kept as they are.
+/// Output cut off at the end of the shmem leaves blocks open, so they end
+/// at the end of input; stray closing braces are skipped.
+pub fn parse_entries(text: &str) -> StateBlock {
+    // Blocks being read, innermost last
+    let mut open: Vec<StateBlock> = vec![StateBlock::default()];
+
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if line == "}" {
+            if open.len() > 1 {
+                close_block(&mut open);
+            }
+            continue;
+        }
+
+        // `name = {`, or `name => {` for event triggers
+        if let Some(name) = line.strip_suffix('{').and_then(|l| {
+            let l = l.trim_end();
+            l.strip_suffix("=>").or_else(|| l.strip_suffix('='))
+        }) {
+            open.push(StateBlock::new(unquote(name)));
+            continue;
+        }
+
+        let entry = match split_property(line) {
+            // Empty blocks are sometimes written on one line
+            Some((k, v)) if v == "{}" || v == "{ }" => StateEntry::Block(StateBlock::new(k)),
+            Some((k, v)) => StateEntry::Property(k, v),
+            None => StateEntry::Item(line.to_string()),
+        };
+
+        open.last_mut().expect("Must have root").entries.push(entry);
+    }
+
+    while open.len() > 1 {
+        close_block(&mut open);
+    }
+
+    open.pop().expect("Must have root")
+}
+
+/// Move the innermost open block into its parent
+fn close_block(open: &mut Vec<StateBlock>) {
+    let block = open.pop().expect("Must have a block to close");
+    open.last_mut()
+        .expect("Must not close root")
+        .entries
+        .push(StateEntry::Block(block));
+}
+
+/// Split at the first ` = ` or ` => `
+fn split_property(line: &str) -> Option<(String, String)> {
+    let (at, len) = [" = ", " => "]
+        .iter()
+        .filter_map(|sep| line.find(sep).map(|at| (at, sep.len())))
+        .min()?;
+
+    Some((unquote(&line[..at]), line[at + len..].trim().to_string()))
+}
+
+fn unquote(s: &str) -> String {
+    let s = s.trim();
+
+    s.strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(s)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_blocks, parse_dumpstate, StateBlock, StateEntry};
+
+    const DUMPSTATE: &str = include_str!("../../tests/fixtures/dumpstate.txt");
+
+    fn items(block: &StateBlock) -> Vec<&str> {
+        block
+            .entries
+            .iter()
+            .filter_map(|e| match e {
+                StateEntry::Item(i) => Some(i.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn domains_and_services() {
+        let state = parse_dumpstate(DUMPSTATE);
+
+        let domains: Vec<(&str, usize)> = state
+            .domains
+            .iter()
+            .map(|d| (d.block.name.as_str(), d.services.len()))
+            .collect();
+
+        assert_eq!(
+            domains,
+            vec![
+                ("com.apple.xpc.launchd.domain.system", 3),
+                ("com.apple.xpc.launchd.domain.gui.501", 1)
+            ]
+        );
+        assert!(state.other.is_empty());
+
+        let system = &state.domains[0].block;
+        let disabled = system.child("disabled services").unwrap();
+        let subdomains = system.child("subdomains").unwrap();
+
+        assert_eq!(system.get("active count"), Some("575"));
+        assert_eq!(disabled.get("com.apple.ftpd"), Some("disabled"));
+        assert_eq!(
+            items(subdomains),
+            vec!["pid/7578", "com.apple.xpc.launchd.domain.gui.501"]
+        );
+    }
+
+    #[test]
+    fn service_properties_and_endpoints() {
+        let state = parse_dumpstate(DUMPSTATE);
+        let usbmuxd = &state.domains[0].services[0];
+        let environment = usbmuxd.child("default environment").unwrap();
+
+        assert_eq!(usbmuxd.name, "com.apple.usbmuxd");
+        assert_eq!(usbmuxd.get("pid"), Some("185"));
+        assert_eq!(
+            usbmuxd.get("jetsam memory limit (active)"),
+            Some("(unlimited)")
+        );
+        assert_eq!(
+            environment.get("PATH"),
+            Some("/usr/bin:/bin:/usr/sbin:/sbin")
+        );
+        assert_eq!(items(usbmuxd.child("arguments").unwrap()).len(), 2);
+        assert!(items(usbmuxd).contains(&"submitted job. ignore execute allowed"));
+
+        let endpoint = usbmuxd
+            .child("endpoints")
+            .and_then(|e| e.child("com.apple.usbmuxd"))
+            .unwrap();
+        assert_eq!(endpoint.get("port"), Some("0x1a07"));
+
+        let lskdd = &state.domains[0].services[1];
+        assert!(lskdd.child("event triggers").unwrap().entries.is_empty());
+
+        let redis = &state.domains[0].services[2];
+        assert_eq!(redis.get("last exit code"), Some("78: EX_CONFIG"));
+    }
+
+    #[test]
+    fn display_round_trip() {
+        let state = parse_dumpstate(DUMPSTATE);
+
+        assert_eq!(parse_dumpstate(&state.to_string()), state);
+    }
+
+    #[test]
+    fn filter_services() {
+        let state = parse_dumpstate(DUMPSTATE).filter_services(|s| s.name.contains("usernoted"));
+
+        assert_eq!(state.domains.len(), 1);
+        assert_eq!(state.domains[0].services[0].name, "com.apple.usernoted");
+    }
+
+    #[test]
+    fn truncated_and_stray() {
+        let blocks = parse_blocks("}\nnot in a block\nouter = {\n\tinner = {\n\t\ta = 1\n\t\tb =");
+
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].name, "outer");
+
+        let inner = blocks[0].child("inner").unwrap();
+        assert_eq!(inner.get("a"), Some("1"));
+        assert_eq!(items(inner), vec!["b ="]);
+    }
+
+    #[test]
+    fn arrow_block() {
+        let blocks = parse_blocks("triggers = {\n\tcom.example.trigger => {\n\t\tstream = com.apple.notifyd.matching\n\t}\n}");
+        let trigger = blocks[0].child("com.example.trigger").unwrap();
+
+        assert_eq!(trigger.get("stream"), Some("com.apple.notifyd.matching"));
+    }
+}
@@ -1,104 +1,141 @@
 use std::collections::HashMap;
+use std::env;
 use std::sync::Mutex;
-use std::time::{Duration, SystemTime};
 
-use crate::launchd::command::find_in_all;
-use crate::launchd::plist::LaunchdPlist;
+use crate::launchd::command::ListedService;
+use crate::launchd::exit_status::LastExit;
+use crate::launchd::plist::{for_label, resolve_domain, LaunchdPlist};
 
 use xpc_sys::enums::{DomainType, SessionType};
-use xpc_sys::object::try_xpc_into_rust::TryXPCIntoRust;
-use xpc_sys::object::xpc_error::XPCError;
-use xpc_sys::object::xpc_object::XPCHashMap;
-
-const ENTRY_INFO_QUERY_TTL: Duration = Duration::from_secs(15);
+use xpc_sys::rs_geteuid;
+use xpc_sys::service_target::ServiceTarget;
 
 lazy_static! {
+    /// Status by label, replaced in a batch by refresh_entry_status
     pub static ref ENTRY_STATUS_CACHE: Mutex<HashMap<String, LaunchdEntryStatus>> =
         Mutex::new(HashMap::new());
+
+    /// Audit session of the user's gui domain, as of the last refresh
+    static ref LOGIN_ASID: Mutex<Option<u64>> = Mutex::new(None);
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct LaunchdEntryStatus {
     pub plist: Option<LaunchdPlist>,
     pub limit_load_to_session_type: SessionType,
-    pub domain: DomainType,
+    /// Domain to bootstrap, bootout, enable and disable the service in
+    pub domain: ServiceTarget,
     // So, there is a pid_t, but it's i32, and the XPC response has an i64?
     pub pid: i64,
-    tick: SystemTime,
+    /// None if it never exited
+    pub last_exit: Option<LastExit>,
 }
 
 impl Default for LaunchdEntryStatus {
     fn default() -> Self {
         LaunchdEntryStatus {
             limit_load_to_session_type: SessionType::Unknown,
-            domain: DomainType::Unknown,
+            domain: ServiceTarget::new(DomainType::Unknown, 0),
             plist: None,
             pid: 0,
-            tick: SystemTime::now(),
+            last_exit: None,
         }
     }
 }
 
-/// Get entry info for label
+/// Get entry info for label, from its plist if it was not listed in the
+/// last refresh
 pub fn get_entry_status<S: Into<String>>(label: S) -> LaunchdEntryStatus {
     let label_string = label.into();
-    let mut cache = ENTRY_STATUS_CACHE.try_lock().unwrap();
+    let mut cache = ENTRY_STATUS_CACHE.lock().expect("Must read entry status");
 
-    if cache.contains_key(label_string.as_str()) {
-        let item = cache.get(label_string.as_str()).unwrap().clone();
-
-        if item.tick.elapsed().unwrap() > ENTRY_INFO_QUERY_TTL {
-            cache.remove(label_string.as_str());
-            drop(cache);
-            return get_entry_status(label_string);
-        }
-
-        return item;
-    }
+    cache
+        .entry(label_string.clone())
+        .or_insert_with(|| build_entry_status(&label_string, None, login_asid()))
+        .clone()
+}
 
-    let meta = build_entry_status(&label_string);
-    cache.insert(label_string, meta.clone());
-    meta
+/// Replace all entry info with the services from list_all. LoginWindow
+/// agents go in the login domain of `login_asid`, if it is known.
+pub fn refresh_entry_status(
+    listed: &HashMap<String, (ServiceTarget, ListedService)>,
+    login_asid: Option<u64>,
+) {
+    *LOGIN_ASID.lock().expect("Must set login asid") = login_asid;
+
+    let statuses = listed
+        .iter()
+        .map(|(label, listing)| {
+            (
+                label.clone(),
+                build_entry_status(label, Some(listing), login_asid),
+            )
+        })
+        .collect();
+
+    *ENTRY_STATUS_CACHE
+        .lock()
+        .expect("Must refresh entry status") = statuses;
 }
 
-fn build_entry_status<S: Into<String>>(label: S) -> LaunchdEntryStatus {
-    let label_string = label.into();
-    let response = find_in_all(label_string.clone());
-    let entry_config = crate::launchd::plist::for_label(label_string.clone());
+/// Audit session the last refresh was given
+pub fn login_asid() -> Option<u64> {
+    *LOGIN_ASID.lock().expect("Must read login asid")
+}
 
-    log::info!("build_entry_status: {:?}", entry_config);
+/// The user whose agents we manage, also after restarting with sudo
+pub fn agent_uid() -> u64 {
+    env::var("SUDO_UID")
+        .ok()
+        .and_then(|uid| uid.parse().ok())
+        .unwrap_or(rs_geteuid() as u64)
+}
 
-    let found_domain = response.clone().map(|(domain, _)| domain).ok();
+fn build_entry_status(
+    label: &str,
+    listing: Option<&(ServiceTarget, ListedService)>,
+    login_asid: Option<u64>,
+) -> LaunchdEntryStatus {
+    let entry_config = for_label(label);
+
+    log::trace!("build_entry_status: {:?}", entry_config);
+
+    let pid = listing.map(|(_, service)| service.pid).unwrap_or(0);
+    let last_exit = listing
+        .and_then(|(_, service)| service.status)
+        .map(LastExit::from_wait_status);
+
+    let limit_load_to_session_type: SessionType = listing
+        .and_then(|(_, service)| service.limit_load_to_session_type.clone())
+        .map(SessionType::from)
+        .or_else(|| {
+            entry_config
+                .as_ref()
+                .map(|ec| ec.limit_load_to_session_type.clone())
+        })
+        .unwrap_or(SessionType::Unknown);
 
-    // Prefer to infer domain from location of the plist,
-    // otherwise try to find it at runtime
+    // Prefer to infer domain from the plist,
+    // otherwise use the domain it was listed in
     let domain = entry_config
-        .clone()
-        .map(|ec| ec.entry_location.into())
-        .or(found_domain)
-        .unwrap_or(DomainType::RequestorDomain);
-
-    let service: XPCHashMap = response
-        .and_then(|(_, d)| d.get("service").ok_or(XPCError::NotFound).cloned())
-        .and_then(|o| o.to_rust())
-        .unwrap_or(HashMap::new());
-
-    let pid: i64 = service
-        .get("PID")
-        .and_then(|o| o.to_rust().ok())
-        .unwrap_or(0);
-
-    let limit_load_to_session_type: SessionType = service
-        .get("LimitLoadToSessionType")
-        .and_then(|o| o.to_rust().ok())
-        .map(|s: String| SessionType::from(s))
-        .unwrap_or(SessionType::Unknown);
+        .as_ref()
+        .map(|ec| {
+            resolve_domain(
+                &ec.entry_type,
+                &ec.entry_location,
+                &limit_load_to_session_type,
+                agent_uid(),
+                login_asid,
+            )
+        })
+        .or_else(|| listing.map(|(target, _)| target.clone()))
+        .unwrap_or_else(|| ServiceTarget::from(DomainType::RequestorDomain));
 
     LaunchdEntryStatus {
         limit_load_to_session_type,
         domain,
         plist: entry_config,
         pid,
-        tick: SystemTime::now(),
+        last_exit,
     }
 }
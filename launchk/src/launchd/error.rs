@@ -0,0 +1,238 @@
+use std::error::Error;
+use std::fmt;
+
+use xpc_sys::enums::DomainType;
+use xpc_sys::object::xpc_error::XPCError;
+use xpc_sys::object::xpc_value::{XPCValue, XPCValueMap};
+use xpc_sys::rs_xpc_strerror;
+
+use crate::launchd::routine::LaunchdRoutine;
+
+/// Error codes we know what to do about
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum LaunchdErrorKind {
+    RequiresRoot,
+    NotFound,
+    AlreadyBootstrapped,
+    InProgress,
+    DomainNotFound,
+    Disabled,
+    BadOwnership,
+    Unsupported,
+    SIPProtected,
+    /// The process was still there after SIGKILL
+    StillRunning,
+    /// A code not in the catalog
+    Unknown,
+    /// No reply from launchd, or one we could not read
+    Xpc(XPCError),
+}
+
+impl LaunchdErrorKind {
+    pub fn from_code(routine: LaunchdRoutine, code: i64) -> Self {
+        match code {
+            1 => LaunchdErrorKind::RequiresRoot,
+            3 | 113 => LaunchdErrorKind::NotFound,
+            // bootstrap replies EIO or EEXIST when the service is loaded
+            5 | 17 if routine == LaunchdRoutine::Bootstrap => LaunchdErrorKind::AlreadyBootstrapped,
+            37 => LaunchdErrorKind::AlreadyBootstrapped,
+            36 => LaunchdErrorKind::InProgress,
+            112 => LaunchdErrorKind::DomainNotFound,
+            119 => LaunchdErrorKind::Disabled,
+            122 => LaunchdErrorKind::BadOwnership,
+            125 => LaunchdErrorKind::Unsupported,
+            150 => LaunchdErrorKind::SIPProtected,
+            _ => LaunchdErrorKind::Unknown,
+        }
+    }
+
+    /// What to do about it
+    pub fn hint(&self) -> Option<&'static str> {
+        let hint = match self {
+            LaunchdErrorKind::RequiresRoot => "Restart launchk as root (sudo) to manage this job",
+            LaunchdErrorKind::NotFound => {
+                "The service is not loaded in this domain, load it or pick another domain"
+            }
+            LaunchdErrorKind::AlreadyBootstrapped => "The service is already loaded, unload it first",
+            LaunchdErrorKind::InProgress => "launchd is still working on this service, try again shortly",
+            LaunchdErrorKind::DomainNotFound => {
+                "The domain does not exist, e.g. there is no GUI login for this user"
+            }
+            LaunchdErrorKind::Disabled => "The service is disabled, enable it first",
+            LaunchdErrorKind::BadOwnership => {
+                "The plist must be owned by root (or you, for user agents) and not be group or world writable"
+            }
+            LaunchdErrorKind::Unsupported => "This domain does not support the action",
+            LaunchdErrorKind::SIPProtected => {
+                "The service is protected by System Integrity Protection, see CSR info"
+            }
+            LaunchdErrorKind::StillRunning => {
+                "The process did not exit after SIGKILL, e.g. it is stuck in the kernel"
+            }
+            LaunchdErrorKind::Unknown | LaunchdErrorKind::Xpc(_) => return None,
+        };
+
+        Some(hint)
+    }
+}
+
+/// A failed routine and what it was for
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LaunchdError {
+    pub routine: LaunchdRoutine,
+    /// Label or plist path from the request
+    pub target: Option<String>,
+    pub domain: DomainType,
+    /// Code from the reply, if launchd sent one
+    pub code: Option<i64>,
+    pub message: String,
+    pub kind: LaunchdErrorKind,
+}
+
+impl LaunchdError {
+    /// launchd replied with `code` for `request`
+    pub fn from_code(routine: LaunchdRoutine, request: &XPCValueMap, code: i64) -> Self {
+        Self {
+            code: Some(code),
+            message: rs_xpc_strerror(code as i32),
+            kind: LaunchdErrorKind::from_code(routine, code),
+            ..Self::for_request(routine, request)
+        }
+    }
+
+    pub fn from_xpc(routine: LaunchdRoutine, request: &XPCValueMap, err: XPCError) -> Self {
+        Self {
+            message: err.to_string(),
+            kind: LaunchdErrorKind::Xpc(err),
+            ..Self::for_request(routine, request)
+        }
+    }
+
+    /// Not from a reply, but something we found out after one
+    pub fn from_kind(
+        routine: LaunchdRoutine,
+        request: &XPCValueMap,
+        kind: LaunchdErrorKind,
+        message: String,
+    ) -> Self {
+        Self {
+            message,
+            kind,
+            ..Self::for_request(routine, request)
+        }
+    }
+
+    fn for_request(routine: LaunchdRoutine, request: &XPCValueMap) -> Self {
+        let first = |key: &str| match request.get(key) {
+            Some(XPCValue::Array(a)) => a.first().and_then(|v| v.as_str()).map(String::from),
+            Some(v) => v.as_str().map(String::from),
+            None => None,
+        };
+
+        Self {
+            routine,
+            target: first("name")
+                .or_else(|| first("names"))
+                .or_else(|| first("paths")),
+            domain: request
+                .get("type")
+                .and_then(|t| t.as_u64())
+                .map(DomainType::from)
+                .unwrap_or(DomainType::Unknown),
+            code: None,
+            message: String::new(),
+            kind: LaunchdErrorKind::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for LaunchdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.routine)?;
+
+        if let Some(target) = &self.target {
+            write!(f, " {}", target)?;
+        }
+
+        write!(f, ": {}", self.message)?;
+
+        if let Some(code) = self.code {
+            write!(f, " (code {})", code)?;
+        }
+
+        if let Some(hint) = self.kind.hint() {
+            write!(f, "\n\n{}", hint)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for LaunchdError {}
+
+#[cfg(test)]
+mod tests {
+    use xpc_sys::api::dict_builder::DictBuilder;
+    use xpc_sys::enums::DomainType;
+    use xpc_sys::object::xpc_error::XPCError;
+    use xpc_sys::object::xpc_value::XPCValueMap;
+
+    use crate::launchd::routine::LaunchdRoutine;
+
+    use super::{LaunchdError, LaunchdErrorKind};
+
+    #[test]
+    fn codes_depend_on_routine() {
+        assert_eq!(
+            LaunchdErrorKind::from_code(LaunchdRoutine::Bootstrap, 5),
+            LaunchdErrorKind::AlreadyBootstrapped
+        );
+        assert_eq!(
+            LaunchdErrorKind::from_code(LaunchdRoutine::Bootout, 5),
+            LaunchdErrorKind::Unknown
+        );
+        assert_eq!(
+            LaunchdErrorKind::from_code(LaunchdRoutine::Enable, 150),
+            LaunchdErrorKind::SIPProtected
+        );
+    }
+
+    #[test]
+    fn target_from_request() {
+        let request = XPCValueMap::new()
+            .entry(
+                "paths",
+                vec!["/Library/LaunchDaemons/com.example.agent.plist"],
+            )
+            .entry("handle", 0u64)
+            .entry("type", 1u64);
+
+        let err = LaunchdError::from_xpc(LaunchdRoutine::Bootstrap, &request, XPCError::NotFound);
+
+        assert_eq!(
+            err.target.as_deref(),
+            Some("/Library/LaunchDaemons/com.example.agent.plist")
+        );
+        assert_eq!(err.domain, DomainType::System);
+    }
+
+    #[test]
+    fn display_has_code_and_hint() {
+        let err = LaunchdError {
+            code: Some(113),
+            message: "Could not find specified service".to_string(),
+            kind: LaunchdErrorKind::NotFound,
+            ..LaunchdError::from_xpc(
+                LaunchdRoutine::Bootout,
+                &XPCValueMap::new().entry("name", "com.example.agent"),
+                XPCError::NotFound,
+            )
+        };
+
+        assert_eq!(
+            err.to_string(),
+            "Bootout (801) com.example.agent: Could not find specified service (code 113)\n\n\
+             The service is not loaded in this domain, load it or pick another domain"
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::launchd::command::SIGNALS;
+
+/// How a job last exited, from the wait(2) status launchd keeps
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum LastExit {
+    Exited(i64),
+    Signaled(i64),
+}
+
+const SIGTERM: i64 = 15;
+
+impl LastExit {
+    /// `LastExitStatus` of a list reply, e.g. 19968 for exit code 78
+    pub fn from_wait_status(status: i64) -> Self {
+        match status & 0x7f {
+            0 => LastExit::Exited((status >> 8) & 0xff),
+            signal => LastExit::Signaled(signal),
+        }
+    }
+
+    /// A non-zero exit code, or a signal other than the SIGTERM of a
+    /// normal stop
+    pub fn is_failure(&self) -> bool {
+        match self {
+            LastExit::Exited(code) => *code != 0,
+            LastExit::Signaled(signal) => *signal != SIGTERM,
+        }
+    }
+}
+
+impl fmt::Display for LastExit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LastExit::Exited(code) => write!(f, "{}", code),
+            LastExit::Signaled(signal) => match SIGNALS.iter().find(|(_, s)| s == signal) {
+                Some((name, _)) => write!(f, "{}", name),
+                None => write!(f, "signal {}", signal),
+            },
+        }
+    }
+}
+
+/// Flags jobs that got a new PID `restarts` times within `window`,
+/// e.g. a KeepAlive job that keeps crashing
+pub struct CrashLoopDetector {
+    restarts: usize,
+    window: Duration,
+    /// Last PID seen for a label, and when it changed
+    history: HashMap<String, (i64, VecDeque<Instant>)>,
+}
+
+impl CrashLoopDetector {
+    pub fn new(restarts: usize, window: Duration) -> Self {
+        Self {
+            restarts,
+            window,
+            history: HashMap::new(),
+        }
+    }
+
+    /// Record the PIDs of the loaded jobs (0 if not running) seen at
+    /// `now`, returning the labels that are crash looping
+    pub fn record(&mut self, pids: &HashMap<String, i64>, now: Instant) -> HashSet<String> {
+        self.history.retain(|label, _| pids.contains_key(label));
+
+        for (label, pid) in pids.iter().filter(|(_, pid)| **pid > 0) {
+            let (last_pid, changes) = self
+                .history
+                .entry(label.clone())
+                .or_insert_with(|| (*pid, VecDeque::new()));
+
+            if last_pid != pid {
+                *last_pid = *pid;
+                changes.push_back(now);
+            }
+        }
+
+        let window = self.window;
+        for (_, changes) in self.history.values_mut() {
+            while changes
+                .front()
+                .is_some_and(|c| now.duration_since(*c) > window)
+            {
+                changes.pop_front();
+            }
+        }
+
+        self.history
+            .iter()
+            .filter(|(_, (_, changes))| changes.len() >= self.restarts)
+            .map(|(label, _)| label.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::{Duration, Instant};
+
+    use super::{CrashLoopDetector, LastExit};
+
+    #[test]
+    fn wait_status() {
+        assert_eq!(LastExit::from_wait_status(0), LastExit::Exited(0));
+        assert_eq!(LastExit::from_wait_status(78 << 8), LastExit::Exited(78));
+        assert_eq!(LastExit::from_wait_status(9), LastExit::Signaled(9));
+
+        assert!(!LastExit::Exited(0).is_failure());
+        assert!(!LastExit::Signaled(15).is_failure());
+        assert!(LastExit::Exited(78).is_failure());
+
+        assert_eq!(LastExit::Signaled(9).to_string(), "SIGKILL");
+        assert_eq!(LastExit::Signaled(6).to_string(), "signal 6");
+    }
+
+    #[test]
+    fn crash_loop() {
+        let mut detector = CrashLoopDetector::new(3, Duration::from_secs(60));
+        let start = Instant::now();
+        let poll = |pid: i64| HashMap::from([("com.example.crash".to_string(), pid)]);
+
+        // First PID seen is not a restart, exiting (PID 0) is not either
+        for (secs, pid) in [(0, 100), (10, 0), (11, 101), (21, 102)] {
+            let at = start + Duration::from_secs(secs);
+            assert!(detector.record(&poll(pid), at).is_empty());
+        }
+
+        let at = start + Duration::from_secs(31);
+        assert!(detector
+            .record(&poll(103), at)
+            .contains("com.example.crash"));
+
+        // The first restarts age out of the window
+        let at = start + Duration::from_secs(80);
+        assert!(detector.record(&poll(103), at).is_empty());
+    }
+}
@@ -0,0 +1,332 @@
+use std::env;
+use std::fmt;
+use std::fs::{self, DirBuilder, File, OpenOptions};
+use std::io::{BufRead, BufReader, Cursor, ErrorKind, Write};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use xpc_sys::rs_geteuid;
+
+use crate::launchd::atomic_write::{create_temp, write_atomic};
+use crate::launchd::plist::is_binary_plist;
+
+lazy_static! {
+    static ref STATE_DIR: PathBuf =
+        state_dir(rs_geteuid(), &env::var("HOME").expect("Must read $HOME"));
+}
+
+/// Root's home when running as root: sudo keeps $HOME, and what root
+/// creates in the user's home would not be writable by later runs
+fn state_dir(euid: u32, home: &str) -> PathBuf {
+    let home = if euid == 0 { "/var/root" } else { home };
+    Path::new(home).join("Library/Application Support/launchk")
+}
+
+const JOURNAL: &str = "journal.jsonl";
+const BACKUPS: &str = "backups";
+
+/// A plist launchk replaced, and where the version before it was backed up
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub label: String,
+    pub path: String,
+    /// Fingerprints of the plist before and after
+    pub old_hash: String,
+    pub new_hash: String,
+    /// Seconds since the epoch
+    pub time: u64,
+    /// File name in the backups directory
+    pub backup: String,
+}
+
+impl JournalEntry {
+    /// e.g. 2024-05-01T12:00:00Z
+    pub fn timestamp(&self) -> String {
+        plist::Date::from(UNIX_EPOCH + Duration::from_secs(self.time)).to_xml_format()
+    }
+}
+
+impl fmt::Display for JournalEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}  {} -> {}",
+            self.timestamp(),
+            self.old_hash,
+            self.new_hash
+        )
+    }
+}
+
+/// FNV-1a, enough to tell versions of a plist apart
+fn fingerprint(bytes: &[u8]) -> String {
+    let hash = bytes.iter().fold(0xcbf29ce484222325_u64, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x100000001b3)
+    });
+
+    format!("{:016x}", hash)
+}
+
+/// The label as a file name: anything but letters, digits, `.`, `-` and
+/// `_` (e.g. a `/`) is replaced
+fn file_name_label(label: &str) -> String {
+    label
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+/// Backups of the plists launchk replaced, with a journal of the changes
+pub struct History {
+    dir: PathBuf,
+}
+
+impl History {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// In ~/Library/Application Support/launchk, root's when run with sudo
+    pub fn user() -> Self {
+        Self::new(STATE_DIR.clone())
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Back up the plist at `path`, then replace it with `bytes`
+    pub fn replace(&self, label: &str, path: &str, bytes: &[u8]) -> Result<JournalEntry, String> {
+        let old = fs::read(path).map_err(|e| format!("Cannot read {}: {}", path, e))?;
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Must get ts")
+            .as_secs();
+        let old_hash = fingerprint(&old);
+
+        // Only readable by us, daemon plists can have secrets in them
+        let backups = self.dir.join(BACKUPS);
+        let backup = DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&backups)
+            .and_then(|()| {
+                let prefix = format!("{}.{}.{}", file_name_label(label), time, old_hash);
+                let (backup_path, mut backup) = create_temp(&backups, &prefix)?;
+                backup.write_all(&old)?;
+                backup.sync_all()?;
+                Ok(backup_path)
+            })
+            .map_err(|e| format!("Cannot back up {}: {}", path, e))?
+            .file_name()
+            .expect("Must have a file name")
+            .to_string_lossy()
+            .to_string();
+
+        write_atomic(Path::new(path), bytes)
+            .map_err(|e| format!("Cannot write {}: {}", path, e))?;
+
+        let entry = JournalEntry {
+            label: label.to_string(),
+            path: path.to_string(),
+            old_hash,
+            new_hash: fingerprint(bytes),
+            time,
+            backup,
+        };
+        let line = serde_json::to_string(&entry).map_err(|e| e.to_string())?;
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .mode(0o600)
+            .open(self.dir.join(JOURNAL))
+            .and_then(|mut journal| writeln!(journal, "{}", line))
+            .map_err(|e| format!("Cannot write journal: {}", e))?;
+
+        Ok(entry)
+    }
+
+    /// Changes to the plist of `label`, newest first
+    pub fn entries(&self, label: &str) -> Result<Vec<JournalEntry>, String> {
+        let journal = match File::open(self.dir.join(JOURNAL)) {
+            Ok(journal) => journal,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(format!("Cannot read journal: {}", e)),
+        };
+
+        let mut entries = BufReader::new(journal)
+            .lines()
+            .map(|line| {
+                let line = line.map_err(|e| format!("Cannot read journal: {}", e))?;
+                serde_json::from_str::<JournalEntry>(&line)
+                    .map_err(|e| format!("Bad journal entry: {}", e))
+            })
+            .collect::<Result<Vec<JournalEntry>, String>>()?;
+
+        entries.retain(|e| e.label == label);
+        entries.reverse();
+
+        Ok(entries)
+    }
+
+    /// Restore the plist from before `entry`, keeping the binary or XML
+    /// format it has now. The revert is journaled too, so it can be undone.
+    pub fn revert(&self, entry: &JournalEntry) -> Result<JournalEntry, String> {
+        if entry.backup.contains('/') {
+            return Err(format!("Bad backup name {}", entry.backup));
+        }
+
+        let backup = fs::read(self.dir.join(BACKUPS).join(&entry.backup))
+            .map_err(|e| format!("Cannot read backup {}: {}", entry.backup, e))?;
+        let current =
+            fs::read(&entry.path).map_err(|e| format!("Cannot read {}: {}", entry.path, e))?;
+
+        if is_binary_plist(&backup) == is_binary_plist(&current) {
+            return self.replace(&entry.label, &entry.path, &backup);
+        }
+
+        let value = plist::Value::from_reader(Cursor::new(backup)).map_err(|e| e.to_string())?;
+        let mut bytes = vec![];
+
+        if is_binary_plist(&current) {
+            value.to_writer_binary(&mut bytes)
+        } else {
+            value.to_writer_xml(&mut bytes)
+        }
+        .map_err(|e| e.to_string())?;
+
+        self.replace(&entry.label, &entry.path, &bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+    use std::path::PathBuf;
+
+    use super::{state_dir, History};
+    use crate::launchd::plist::is_binary_plist;
+    use crate::launchd::test_util::scratch;
+
+    const LABEL: &str = "com.example.history";
+
+    fn plist(program: &str) -> plist::Value {
+        let mut dict = plist::Dictionary::new();
+        dict.insert("Label".to_string(), LABEL.into());
+        dict.insert("Program".to_string(), program.into());
+        plist::Value::Dictionary(dict)
+    }
+
+    fn xml(value: &plist::Value) -> Vec<u8> {
+        let mut bytes = vec![];
+        value.to_writer_xml(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn replace_and_revert() {
+        let dir = scratch("history");
+        let history = History::new(dir.join("state"));
+        let path = dir.join(format!("{}.plist", LABEL));
+        let path_str = path.to_str().unwrap();
+
+        let v1 = xml(&plist("/usr/bin/true"));
+        let v2 = xml(&plist("/usr/bin/false"));
+        fs::write(&path, &v1).unwrap();
+
+        let edit = history.replace(LABEL, path_str, &v2).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), v2);
+        assert_ne!(edit.old_hash, edit.new_hash);
+
+        let revert = history.revert(&edit).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), v1);
+        assert_eq!(revert.old_hash, edit.new_hash);
+        assert_eq!(revert.new_hash, edit.old_hash);
+
+        // Newest first, and only this label
+        assert_eq!(history.entries(LABEL).unwrap(), vec![revert, edit]);
+        assert!(history.entries("com.example.other").unwrap().is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn root_state_dir() {
+        assert_eq!(
+            state_dir(501, "/Users/me"),
+            PathBuf::from("/Users/me/Library/Application Support/launchk")
+        );
+        assert_eq!(
+            state_dir(0, "/Users/me"),
+            PathBuf::from("/var/root/Library/Application Support/launchk")
+        );
+    }
+
+    #[test]
+    fn private_backups() {
+        let dir = scratch("history-private");
+        let history = History::new(dir.join("state"));
+        let path = dir.join("daemon.plist");
+        fs::write(&path, xml(&plist("/usr/bin/true"))).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+
+        let edit = history
+            .replace(
+                "../../escape",
+                path.to_str().unwrap(),
+                &xml(&plist("/bin/sh")),
+            )
+            .unwrap();
+
+        let backups = dir.join("state/backups");
+        let backup = backups.join(&edit.backup);
+        assert!(edit.backup.starts_with(".._.._escape."));
+        assert_eq!(fs::metadata(&backups).unwrap().mode() & 0o777, 0o700);
+        assert_eq!(fs::metadata(&backup).unwrap().mode() & 0o777, 0o600);
+        assert_eq!(
+            fs::metadata(dir.join("state/journal.jsonl"))
+                .unwrap()
+                .mode()
+                & 0o777,
+            0o600
+        );
+        assert_eq!(fs::read(&backup).unwrap(), xml(&plist("/usr/bin/true")));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn revert_keeps_format() {
+        let dir = scratch("history-format");
+        let history = History::new(dir.join("state"));
+        let path = dir.join(format!("{}.plist", LABEL));
+        let path_str = path.to_str().unwrap();
+
+        fs::write(&path, xml(&plist("/usr/bin/true"))).unwrap();
+
+        // Converted to binary since the backup was taken
+        let mut binary = vec![];
+        plist("/usr/bin/false")
+            .to_writer_binary(&mut binary)
+            .unwrap();
+        let edit = history.replace(LABEL, path_str, &binary).unwrap();
+
+        history.revert(&edit).unwrap();
+        let reverted = fs::read(&path).unwrap();
+
+        assert!(is_binary_plist(&reverted));
+        assert_eq!(
+            plist::Value::from_reader(std::io::Cursor::new(reverted)).unwrap(),
+            plist("/usr/bin/true")
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
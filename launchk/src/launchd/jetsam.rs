@@ -0,0 +1,257 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::launchd::dumpstate::{parse_entries, StateBlock, StateEntry};
+
+/// A Jetsam memory limit in MB
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum MemoryLimit {
+    Limit(u64),
+    Unlimited,
+}
+
+impl MemoryLimit {
+    /// `400 MB`, `400`, `-1` and `(unlimited)`
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+
+        if value == "-1" || value.contains("unlimited") {
+            return Some(MemoryLimit::Unlimited);
+        }
+
+        value
+            .trim_end_matches("MB")
+            .trim()
+            .parse()
+            .ok()
+            .map(MemoryLimit::Limit)
+    }
+}
+
+impl Ord for MemoryLimit {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (MemoryLimit::Limit(a), MemoryLimit::Limit(b)) => a.cmp(b),
+            (MemoryLimit::Limit(_), MemoryLimit::Unlimited) => Ordering::Less,
+            (MemoryLimit::Unlimited, MemoryLimit::Limit(_)) => Ordering::Greater,
+            (MemoryLimit::Unlimited, MemoryLimit::Unlimited) => Ordering::Equal,
+        }
+    }
+}
+
+impl PartialOrd for MemoryLimit {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for MemoryLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MemoryLimit::Limit(mb) => write!(f, "{} MB", mb),
+            MemoryLimit::Unlimited => write!(f, "unlimited"),
+        }
+    }
+}
+
+/// Jetsam properties of a job, or of a process launchd knows by PID
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct JetsamEntry {
+    /// Label, or `pid/<pid>`
+    pub name: String,
+    pub pid: Option<i64>,
+    pub category: Option<String>,
+    pub priority: Option<i64>,
+    /// Name of the priority band, e.g. `background` in `3: background`
+    pub band: Option<String>,
+    pub memory_limit: Option<MemoryLimit>,
+    pub active_limit: Option<MemoryLimit>,
+    pub inactive_limit: Option<MemoryLimit>,
+    /// Freezer properties that are set, e.g. `freezereligible`
+    pub freeze_flags: Vec<String>,
+}
+
+impl JetsamEntry {
+    /// Jobs are keyed by label, processes by PID
+    pub fn label(&self) -> Option<&str> {
+        if self.name.starts_with("pid/") {
+            None
+        } else {
+            Some(&self.name)
+        }
+    }
+
+    /// The limit that applies while the job is active
+    pub fn effective_limit(&self) -> Option<MemoryLimit> {
+        self.active_limit.or(self.memory_limit)
+    }
+
+    fn from_block(block: &StateBlock) -> Option<Self> {
+        let mut entry = JetsamEntry {
+            name: block.name.clone(),
+            pid: block.name.strip_prefix("pid/").and_then(|p| p.parse().ok()),
+            ..Default::default()
+        };
+        let mut is_jetsam = false;
+
+        for (key, value) in block.properties() {
+            // launchctl print style ("jetsam memory limit (active)") or
+            // plist style ("JetsamMemoryLimit", "ActiveHardMemoryLimit")
+            let key: String = key
+                .to_ascii_lowercase()
+                .chars()
+                .filter(|c| c.is_ascii_alphanumeric())
+                .collect();
+            let key = key.trim_start_matches("jetsam");
+
+            match key {
+                "pid" => entry.pid = value.parse().ok(),
+                "category" | "propertiescategory" => entry.category = Some(value.to_string()),
+                "priority" => {
+                    let (priority, band) = value.split_once(':').unwrap_or((value, ""));
+                    entry.priority = priority.trim().parse().ok();
+                    entry.band = Some(band.trim().to_string()).filter(|b| !b.is_empty());
+                }
+                "memorylimit" => entry.memory_limit = MemoryLimit::parse(value),
+                "memorylimitactive" | "activehardmemorylimit" | "activesoftmemorylimit" => {
+                    entry.active_limit = MemoryLimit::parse(value)
+                }
+                "memorylimitinactive" | "inactivehardmemorylimit" | "inactivesoftmemorylimit" => {
+                    entry.inactive_limit = MemoryLimit::parse(value)
+                }
+                k if k.contains("freez") => {
+                    if value == "1" || value == "true" || value == "yes" {
+                        entry.freeze_flags.push(key.to_string());
+                    }
+                }
+                _ => continue,
+            }
+
+            is_jetsam = true;
+        }
+
+        if is_jetsam {
+            Some(entry)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum JetsamSort {
+    Name,
+    /// Highest first
+    Priority,
+    /// Largest (active) limit first
+    Limit,
+}
+
+/// Sort by `sort`, then by name
+pub fn sort_entries(entries: &mut [JetsamEntry], sort: JetsamSort) {
+    entries.sort_by(|a, b| {
+        let ordering = match sort {
+            JetsamSort::Name => Ordering::Equal,
+            JetsamSort::Priority => b.priority.cmp(&a.priority),
+            JetsamSort::Limit => b.effective_limit().cmp(&a.effective_limit()),
+        };
+
+        ordering.then_with(|| a.name.cmp(&b.name))
+    });
+}
+
+/// Parse dumpjpcategory output. Every block with Jetsam properties
+/// is an entry, however deep it is nested (e.g. under its category).
+pub fn parse_dumpjpcategory(text: &str) -> Vec<JetsamEntry> {
+    let mut entries = vec![];
+    collect_entries(&parse_entries(text), &mut entries);
+    entries
+}
+
+fn collect_entries(block: &StateBlock, entries: &mut Vec<JetsamEntry>) {
+    for entry in &block.entries {
+        if let StateEntry::Block(b) = entry {
+            match JetsamEntry::from_block(b) {
+                Some(mut jetsam) => {
+                    // Nested under its category
+                    if jetsam.category.is_none() && !block.name.is_empty() {
+                        jetsam.category = Some(block.name.clone());
+                    }
+                    entries.push(jetsam)
+                }
+                None => collect_entries(b, entries),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_dumpjpcategory, sort_entries, JetsamSort, MemoryLimit};
+
+    const DUMPJPCATEGORY: &str = include_str!("../../tests/fixtures/dumpjpcategory.txt");
+
+    #[test]
+    fn entries() {
+        let entries = parse_dumpjpcategory(DUMPJPCATEGORY);
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+
+        assert_eq!(
+            names,
+            vec![
+                "com.apple.mds",
+                "com.apple.ReportCrash",
+                "homebrew.mxcl.redis",
+                "pid/7475",
+                "com.apple.usernoted"
+            ]
+        );
+
+        let mds = &entries[0];
+        assert_eq!(mds.category.as_deref(), Some("daemon"));
+        assert_eq!(mds.priority, Some(40));
+        assert_eq!(mds.band.as_deref(), Some("daemon"));
+        assert_eq!(mds.active_limit, Some(MemoryLimit::Limit(400)));
+        assert_eq!(mds.inactive_limit, Some(MemoryLimit::Limit(200)));
+        assert_eq!(mds.freeze_flags, vec!["freezereligible"]);
+
+        let redis = &entries[2];
+        assert_eq!(redis.memory_limit, Some(MemoryLimit::Unlimited));
+        assert_eq!(redis.effective_limit(), Some(MemoryLimit::Unlimited));
+
+        let process = &entries[3];
+        assert_eq!(process.pid, Some(7475));
+        assert_eq!(process.label(), None);
+        assert_eq!(process.band.as_deref(), Some("background"));
+
+        // Plist style keys
+        let usernoted = &entries[4];
+        assert_eq!(usernoted.category.as_deref(), Some("agent"));
+        assert_eq!(usernoted.priority, Some(180));
+        assert_eq!(usernoted.active_limit, Some(MemoryLimit::Limit(50)));
+        assert!(usernoted.freeze_flags.is_empty());
+    }
+
+    #[test]
+    fn sorting() {
+        let mut entries = parse_dumpjpcategory(DUMPJPCATEGORY);
+
+        sort_entries(&mut entries, JetsamSort::Priority);
+        let priorities: Vec<Option<i64>> = entries.iter().map(|e| e.priority).collect();
+        assert_eq!(
+            priorities,
+            vec![Some(180), Some(40), Some(40), Some(3), Some(3)]
+        );
+
+        sort_entries(&mut entries, JetsamSort::Limit);
+        let limits: Vec<String> = entries
+            .iter()
+            .map(|e| {
+                e.effective_limit()
+                    .map(|l| l.to_string())
+                    .unwrap_or_default()
+            })
+            .collect();
+        assert_eq!(limits, vec!["unlimited", "400 MB", "50 MB", "6 MB", ""]);
+    }
+}
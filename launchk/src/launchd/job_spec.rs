@@ -0,0 +1,969 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use plist::{Dictionary, Value};
+
+/// Why a plist is not a valid job definition. Keys are given as a path,
+/// e.g. `KeepAlive.PathState./tmp/ready` or `ProgramArguments[1]`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum JobSpecError {
+    NotADictionary,
+    MissingKey(String),
+    /// (key, expected type, found type)
+    WrongType(String, &'static str, &'static str),
+    /// (key, why)
+    InvalidValue(String, String),
+}
+
+impl JobSpecError {
+    /// The key at fault, None if it is the whole plist
+    pub fn key(&self) -> Option<&str> {
+        match self {
+            JobSpecError::NotADictionary => None,
+            JobSpecError::MissingKey(key)
+            | JobSpecError::WrongType(key, _, _)
+            | JobSpecError::InvalidValue(key, _) => Some(key),
+        }
+    }
+}
+
+impl fmt::Display for JobSpecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobSpecError::NotADictionary => write!(f, "Job definition is not a dictionary"),
+            JobSpecError::MissingKey(key) => write!(f, "Missing {}", key),
+            JobSpecError::WrongType(key, expected, found) => {
+                write!(f, "{}: expected {}, found {}", key, expected, found)
+            }
+            JobSpecError::InvalidValue(key, why) => write!(f, "{}: {}", key, why),
+        }
+    }
+}
+
+/// Keys that take a value or an array of them, e.g. LimitLoadToSessionType
+#[derive(Debug, Clone, PartialEq)]
+pub enum OneOrMany<T> {
+    One(T),
+    Many(Vec<T>),
+}
+
+/// KeepAlive is a boolean, or a dictionary of conditions
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeepAlive {
+    Always(bool),
+    Conditions(KeepAliveConditions),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeepAliveConditions {
+    pub successful_exit: Option<bool>,
+    pub crashed: Option<bool>,
+    pub network_state: Option<bool>,
+    /// Path to whether it must exist
+    pub path_state: Option<BTreeMap<String, bool>>,
+    /// Label to whether it must be enabled
+    pub other_job_enabled: Option<BTreeMap<String, bool>>,
+    pub other: Dictionary,
+}
+
+/// A StartCalendarInterval entry, unset fields are wildcards
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CalendarInterval {
+    pub minute: Option<u64>,
+    pub hour: Option<u64>,
+    pub day: Option<u64>,
+    /// 0 and 7 are Sunday
+    pub weekday: Option<u64>,
+    pub month: Option<u64>,
+    /// launchd ignores these, most likely typos
+    pub other: Dictionary,
+}
+
+/// A MachServices entry is a boolean, or a dictionary of options
+#[derive(Debug, Clone, PartialEq)]
+pub enum MachService {
+    Enabled(bool),
+    Options(MachServiceOptions),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MachServiceOptions {
+    pub reset_at_close: Option<bool>,
+    pub hide_until_check_in: Option<bool>,
+    pub other: Dictionary,
+}
+
+/// SockServiceName is a service name from /etc/services, or a port
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ServiceName {
+    Name(String),
+    Port(u64),
+}
+
+/// A Sockets entry
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Socket {
+    pub sock_type: Option<String>,
+    pub sock_passive: Option<bool>,
+    pub sock_node_name: Option<String>,
+    pub sock_service_name: Option<ServiceName>,
+    pub sock_family: Option<String>,
+    pub sock_protocol: Option<String>,
+    pub sock_path_name: Option<String>,
+    pub sock_path_mode: Option<u64>,
+    pub other: Dictionary,
+}
+
+/// StartCalendarInterval keys and their ranges
+pub const CALENDAR_KEYS: [(&str, u64, u64); 5] = [
+    ("Minute", 0, 59),
+    ("Hour", 0, 23),
+    ("Day", 1, 31),
+    ("Weekday", 0, 7),
+    ("Month", 1, 12),
+];
+
+/// Keys of SoftResourceLimits and HardResourceLimits
+pub const RESOURCE_LIMITS: [&str; 9] = [
+    "Core",
+    "CPU",
+    "Data",
+    "FileSize",
+    "MemoryLock",
+    "NumberOfFiles",
+    "NumberOfProcesses",
+    "ResidentSetSize",
+    "Stack",
+];
+
+const PROCESS_TYPES: [&str; 4] = ["Background", "Standard", "Adaptive", "Interactive"];
+const SOCK_TYPES: [&str; 3] = ["stream", "dgram", "seqpacket"];
+const SOCK_FAMILIES: [&str; 4] = ["IPv4", "IPv6", "IPv4v6", "Unix"];
+
+/// A launchd.plist job definition, see launchd.plist(5). Keys that are
+/// not modeled here are kept in `other`, so the plist round trips.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JobSpec {
+    pub label: String,
+    pub disabled: Option<bool>,
+    pub user_name: Option<String>,
+    pub group_name: Option<String>,
+    pub limit_load_to_session_type: Option<OneOrMany<String>>,
+    pub program: Option<String>,
+    /// Relative to the bundle of the app the job belongs to
+    pub bundle_program: Option<String>,
+    pub program_arguments: Option<Vec<String>>,
+    pub keep_alive: Option<KeepAlive>,
+    pub run_at_load: Option<bool>,
+    pub root_directory: Option<String>,
+    pub working_directory: Option<String>,
+    pub environment_variables: Option<BTreeMap<String, String>>,
+    pub exit_time_out: Option<u64>,
+    pub throttle_interval: Option<u64>,
+    pub watch_paths: Option<Vec<String>>,
+    pub queue_directories: Option<Vec<String>>,
+    pub start_on_mount: Option<bool>,
+    pub start_interval: Option<u64>,
+    pub start_calendar_interval: Option<OneOrMany<CalendarInterval>>,
+    pub standard_in_path: Option<String>,
+    pub standard_out_path: Option<String>,
+    pub standard_error_path: Option<String>,
+    pub soft_resource_limits: Option<BTreeMap<String, u64>>,
+    pub hard_resource_limits: Option<BTreeMap<String, u64>>,
+    pub nice: Option<i64>,
+    pub process_type: Option<String>,
+    pub abandon_process_group: Option<bool>,
+    pub mach_services: Option<BTreeMap<String, MachService>>,
+    pub sockets: Option<BTreeMap<String, OneOrMany<Socket>>>,
+    pub other: Dictionary,
+}
+
+type Parsed<T> = Result<T, JobSpecError>;
+
+/// Takes keys out of a dictionary as they are parsed, the ones left over
+/// are not modeled
+struct Keys {
+    path: String,
+    dict: Dictionary,
+}
+
+impl Keys {
+    fn new(path: &str, dict: Dictionary) -> Self {
+        Self {
+            path: path.to_string(),
+            dict,
+        }
+    }
+
+    fn take<T>(
+        &mut self,
+        key: &str,
+        parse: impl FnOnce(&str, Value) -> Parsed<T>,
+    ) -> Parsed<Option<T>> {
+        let path = join(&self.path, key);
+        self.dict.remove(key).map(|v| parse(&path, v)).transpose()
+    }
+
+    fn rest(self) -> Dictionary {
+        self.dict
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Array(_) => "array",
+        Value::Dictionary(_) => "dictionary",
+        Value::Boolean(_) => "boolean",
+        Value::Data(_) => "data",
+        Value::Date(_) => "date",
+        Value::Real(_) => "real",
+        Value::Integer(_) => "integer",
+        Value::String(_) => "string",
+        _ => "uid",
+    }
+}
+
+fn wrong_type<T>(path: &str, expected: &'static str, value: &Value) -> Parsed<T> {
+    Err(JobSpecError::WrongType(
+        path.to_string(),
+        expected,
+        type_name(value),
+    ))
+}
+
+fn invalid<T>(path: &str, why: String) -> Parsed<T> {
+    Err(JobSpecError::InvalidValue(path.to_string(), why))
+}
+
+fn boolean(path: &str, value: Value) -> Parsed<bool> {
+    match value {
+        Value::Boolean(b) => Ok(b),
+        other => wrong_type(path, "boolean", &other),
+    }
+}
+
+fn string(path: &str, value: Value) -> Parsed<String> {
+    match value {
+        Value::String(s) => Ok(s),
+        other => wrong_type(path, "string", &other),
+    }
+}
+
+fn unsigned(path: &str, value: Value) -> Parsed<u64> {
+    match value {
+        Value::Integer(i) => i
+            .as_unsigned()
+            .map_or_else(|| invalid(path, format!("{} is negative", i)), Ok),
+        other => wrong_type(path, "integer", &other),
+    }
+}
+
+fn signed(path: &str, value: Value) -> Parsed<i64> {
+    match value {
+        Value::Integer(i) => i
+            .as_signed()
+            .map_or_else(|| invalid(path, format!("{} is too large", i)), Ok),
+        other => wrong_type(path, "integer", &other),
+    }
+}
+
+/// A string that must be one of `allowed`
+fn one_of(allowed: &'static [&'static str]) -> impl Fn(&str, Value) -> Parsed<String> {
+    move |path, value| {
+        let s = string(path, value)?;
+        if allowed.contains(&s.as_str()) {
+            Ok(s)
+        } else {
+            invalid(path, format!("{} is not one of {}", s, allowed.join(", ")))
+        }
+    }
+}
+
+/// An integer in range
+fn within(min: u64, max: u64) -> impl Fn(&str, Value) -> Parsed<u64> {
+    move |path, value| {
+        let n = unsigned(path, value)?;
+        if (min..=max).contains(&n) {
+            Ok(n)
+        } else {
+            invalid(path, format!("{} is not within {}-{}", n, min, max))
+        }
+    }
+}
+
+fn dictionary(path: &str, value: Value) -> Parsed<Dictionary> {
+    match value {
+        Value::Dictionary(d) => Ok(d),
+        other => wrong_type(path, "dictionary", &other),
+    }
+}
+
+fn array_of<T>(
+    path: &str,
+    value: Value,
+    parse: impl Fn(&str, Value) -> Parsed<T>,
+) -> Parsed<Vec<T>> {
+    match value {
+        Value::Array(a) => a
+            .into_iter()
+            .enumerate()
+            .map(|(i, v)| parse(&format!("{}[{}]", path, i), v))
+            .collect(),
+        other => wrong_type(path, "array", &other),
+    }
+}
+
+fn strings(path: &str, value: Value) -> Parsed<Vec<String>> {
+    array_of(path, value, string)
+}
+
+fn map_of<T>(
+    path: &str,
+    value: Value,
+    parse: impl Fn(&str, Value) -> Parsed<T>,
+) -> Parsed<BTreeMap<String, T>> {
+    dictionary(path, value)?
+        .into_iter()
+        .map(|(k, v)| parse(&join(path, &k), v).map(|t| (k, t)))
+        .collect()
+}
+
+fn one_or_many<T>(
+    path: &str,
+    value: Value,
+    parse: impl Fn(&str, Value) -> Parsed<T>,
+) -> Parsed<OneOrMany<T>> {
+    match value {
+        Value::Array(_) => array_of(path, value, parse).map(OneOrMany::Many),
+        one => parse(path, one).map(OneOrMany::One),
+    }
+}
+
+fn keep_alive(path: &str, value: Value) -> Parsed<KeepAlive> {
+    let dict = match value {
+        Value::Boolean(b) => return Ok(KeepAlive::Always(b)),
+        Value::Dictionary(d) => d,
+        other => return wrong_type(path, "boolean or dictionary", &other),
+    };
+
+    let mut keys = Keys::new(path, dict);
+    let bools = |path: &str, value| map_of(path, value, boolean);
+
+    Ok(KeepAlive::Conditions(KeepAliveConditions {
+        successful_exit: keys.take("SuccessfulExit", boolean)?,
+        crashed: keys.take("Crashed", boolean)?,
+        network_state: keys.take("NetworkState", boolean)?,
+        path_state: keys.take("PathState", bools)?,
+        other_job_enabled: keys.take("OtherJobEnabled", bools)?,
+        other: keys.rest(),
+    }))
+}
+
+fn calendar_interval(path: &str, value: Value) -> Parsed<CalendarInterval> {
+    let mut keys = Keys::new(path, dictionary(path, value)?);
+
+    let mut field = |key: &str| {
+        let (_, min, max) = CALENDAR_KEYS.iter().find(|(k, _, _)| *k == key).unwrap();
+        keys.take(key, within(*min, *max))
+    };
+
+    let interval = CalendarInterval {
+        minute: field("Minute")?,
+        hour: field("Hour")?,
+        day: field("Day")?,
+        weekday: field("Weekday")?,
+        month: field("Month")?,
+        other: Dictionary::new(),
+    };
+
+    // launchd ignores anything else, lint warns about it
+    Ok(CalendarInterval {
+        other: keys.rest(),
+        ..interval
+    })
+}
+
+fn resource_limits(path: &str, value: Value) -> Parsed<BTreeMap<String, u64>> {
+    let limits = map_of(path, value, unsigned)?;
+
+    match limits
+        .keys()
+        .find(|k| !RESOURCE_LIMITS.contains(&k.as_str()))
+    {
+        Some(key) => invalid(&join(path, key), "unknown resource limit".to_string()),
+        None => Ok(limits),
+    }
+}
+
+fn mach_service(path: &str, value: Value) -> Parsed<MachService> {
+    let dict = match value {
+        Value::Boolean(b) => return Ok(MachService::Enabled(b)),
+        Value::Dictionary(d) => d,
+        other => return wrong_type(path, "boolean or dictionary", &other),
+    };
+
+    let mut keys = Keys::new(path, dict);
+
+    Ok(MachService::Options(MachServiceOptions {
+        reset_at_close: keys.take("ResetAtClose", boolean)?,
+        hide_until_check_in: keys.take("HideUntilCheckIn", boolean)?,
+        other: keys.rest(),
+    }))
+}
+
+fn service_name(path: &str, value: Value) -> Parsed<ServiceName> {
+    match value {
+        Value::String(s) => Ok(ServiceName::Name(s)),
+        Value::Integer(_) => unsigned(path, value).map(ServiceName::Port),
+        other => wrong_type(path, "string or integer", &other),
+    }
+}
+
+fn socket(path: &str, value: Value) -> Parsed<Socket> {
+    let mut keys = Keys::new(path, dictionary(path, value)?);
+
+    Ok(Socket {
+        sock_type: keys.take("SockType", one_of(&SOCK_TYPES))?,
+        sock_passive: keys.take("SockPassive", boolean)?,
+        sock_node_name: keys.take("SockNodeName", string)?,
+        sock_service_name: keys.take("SockServiceName", service_name)?,
+        sock_family: keys.take("SockFamily", one_of(&SOCK_FAMILIES))?,
+        sock_protocol: keys.take("SockProtocol", string)?,
+        sock_path_name: keys.take("SockPathName", string)?,
+        sock_path_mode: keys.take("SockPathMode", unsigned)?,
+        other: keys.rest(),
+    })
+}
+
+/// Insert key if it has a value
+fn put(dict: &mut Dictionary, key: &str, value: Option<Value>) {
+    if let Some(value) = value {
+        dict.insert(key.to_string(), value);
+    }
+}
+
+fn one_or_many_value<T>(value: &OneOrMany<T>, to_value: impl Fn(&T) -> Value) -> Value {
+    match value {
+        OneOrMany::One(one) => to_value(one),
+        OneOrMany::Many(many) => Value::Array(many.iter().map(to_value).collect()),
+    }
+}
+
+fn map_value<T>(map: &BTreeMap<String, T>, to_value: impl Fn(&T) -> Value) -> Value {
+    Value::Dictionary(map.iter().map(|(k, v)| (k.clone(), to_value(v))).collect())
+}
+
+fn strings_value(strings: &[String]) -> Value {
+    Value::Array(strings.iter().cloned().map(Value::String).collect())
+}
+
+impl KeepAlive {
+    fn to_value(&self) -> Value {
+        let conditions = match self {
+            KeepAlive::Always(b) => return Value::Boolean(*b),
+            KeepAlive::Conditions(c) => c,
+        };
+
+        let mut dict = Dictionary::new();
+        let bools = |m: &BTreeMap<String, bool>| map_value(m, |b| Value::Boolean(*b));
+
+        put(
+            &mut dict,
+            "SuccessfulExit",
+            conditions.successful_exit.map(Value::from),
+        );
+        put(&mut dict, "Crashed", conditions.crashed.map(Value::from));
+        put(
+            &mut dict,
+            "NetworkState",
+            conditions.network_state.map(Value::from),
+        );
+        put(
+            &mut dict,
+            "PathState",
+            conditions.path_state.as_ref().map(bools),
+        );
+        put(
+            &mut dict,
+            "OtherJobEnabled",
+            conditions.other_job_enabled.as_ref().map(bools),
+        );
+        dict.extend(conditions.other.clone());
+
+        Value::Dictionary(dict)
+    }
+}
+
+impl CalendarInterval {
+    fn to_value(&self) -> Value {
+        let mut dict = Dictionary::new();
+
+        put(&mut dict, "Minute", self.minute.map(Value::from));
+        put(&mut dict, "Hour", self.hour.map(Value::from));
+        put(&mut dict, "Day", self.day.map(Value::from));
+        put(&mut dict, "Weekday", self.weekday.map(Value::from));
+        put(&mut dict, "Month", self.month.map(Value::from));
+        dict.extend(self.other.clone());
+
+        Value::Dictionary(dict)
+    }
+}
+
+impl MachService {
+    fn to_value(&self) -> Value {
+        let options = match self {
+            MachService::Enabled(b) => return Value::Boolean(*b),
+            MachService::Options(o) => o,
+        };
+
+        let mut dict = Dictionary::new();
+
+        put(
+            &mut dict,
+            "ResetAtClose",
+            options.reset_at_close.map(Value::from),
+        );
+        put(
+            &mut dict,
+            "HideUntilCheckIn",
+            options.hide_until_check_in.map(Value::from),
+        );
+        dict.extend(options.other.clone());
+
+        Value::Dictionary(dict)
+    }
+}
+
+impl Socket {
+    fn to_value(&self) -> Value {
+        let mut dict = Dictionary::new();
+        let service_name = self.sock_service_name.as_ref().map(|s| match s {
+            ServiceName::Name(name) => Value::String(name.clone()),
+            ServiceName::Port(port) => Value::from(*port),
+        });
+
+        put(
+            &mut dict,
+            "SockType",
+            self.sock_type.clone().map(Value::from),
+        );
+        put(&mut dict, "SockPassive", self.sock_passive.map(Value::from));
+        put(
+            &mut dict,
+            "SockNodeName",
+            self.sock_node_name.clone().map(Value::from),
+        );
+        put(&mut dict, "SockServiceName", service_name);
+        put(
+            &mut dict,
+            "SockFamily",
+            self.sock_family.clone().map(Value::from),
+        );
+        put(
+            &mut dict,
+            "SockProtocol",
+            self.sock_protocol.clone().map(Value::from),
+        );
+        put(
+            &mut dict,
+            "SockPathName",
+            self.sock_path_name.clone().map(Value::from),
+        );
+        put(
+            &mut dict,
+            "SockPathMode",
+            self.sock_path_mode.map(Value::from),
+        );
+
+        dict.extend(self.other.clone());
+
+        Value::Dictionary(dict)
+    }
+}
+
+impl JobSpec {
+    /// Parse and validate a job definition
+    pub fn from_value(value: &Value) -> Result<JobSpec, JobSpecError> {
+        let dict = value
+            .as_dictionary()
+            .cloned()
+            .ok_or(JobSpecError::NotADictionary)?;
+        let mut keys = Keys::new("", dict);
+
+        let spec = JobSpec {
+            label: keys
+                .take("Label", string)?
+                .ok_or_else(|| JobSpecError::MissingKey("Label".to_string()))?,
+            disabled: keys.take("Disabled", boolean)?,
+            user_name: keys.take("UserName", string)?,
+            group_name: keys.take("GroupName", string)?,
+            limit_load_to_session_type: keys
+                .take("LimitLoadToSessionType", |p, v| one_or_many(p, v, string))?,
+            program: keys.take("Program", string)?,
+            bundle_program: keys.take("BundleProgram", string)?,
+            program_arguments: keys.take("ProgramArguments", strings)?,
+            keep_alive: keys.take("KeepAlive", keep_alive)?,
+            run_at_load: keys.take("RunAtLoad", boolean)?,
+            root_directory: keys.take("RootDirectory", string)?,
+            working_directory: keys.take("WorkingDirectory", string)?,
+            environment_variables: keys
+                .take("EnvironmentVariables", |p, v| map_of(p, v, string))?,
+            exit_time_out: keys.take("ExitTimeOut", unsigned)?,
+            throttle_interval: keys.take("ThrottleInterval", unsigned)?,
+            watch_paths: keys.take("WatchPaths", strings)?,
+            queue_directories: keys.take("QueueDirectories", strings)?,
+            start_on_mount: keys.take("StartOnMount", boolean)?,
+            start_interval: keys.take("StartInterval", unsigned)?,
+            start_calendar_interval: keys.take("StartCalendarInterval", |p, v| {
+                one_or_many(p, v, calendar_interval)
+            })?,
+            standard_in_path: keys.take("StandardInPath", string)?,
+            standard_out_path: keys.take("StandardOutPath", string)?,
+            standard_error_path: keys.take("StandardErrorPath", string)?,
+            soft_resource_limits: keys.take("SoftResourceLimits", resource_limits)?,
+            hard_resource_limits: keys.take("HardResourceLimits", resource_limits)?,
+            nice: keys.take("Nice", signed)?,
+            process_type: keys.take("ProcessType", one_of(&PROCESS_TYPES))?,
+            abandon_process_group: keys.take("AbandonProcessGroup", boolean)?,
+            mach_services: keys.take("MachServices", |p, v| map_of(p, v, mach_service))?,
+            sockets: keys.take("Sockets", |p, v| {
+                map_of(p, v, |p, v| one_or_many(p, v, socket))
+            })?,
+            other: keys.rest(),
+        };
+
+        if spec.program.is_none()
+            && spec.bundle_program.is_none()
+            && spec
+                .program_arguments
+                .as_ref()
+                .is_none_or(|args| args.is_empty())
+        {
+            return Err(JobSpecError::MissingKey(
+                "Program, BundleProgram or ProgramArguments".to_string(),
+            ));
+        }
+
+        if let Some(nice) = spec.nice.filter(|n| !(-20..=20).contains(n)) {
+            return invalid("Nice", format!("{} is not within -20-20", nice));
+        }
+
+        Ok(spec)
+    }
+
+    /// The job definition as a plist: the modeled keys in the order above,
+    /// then the ones that are not modeled. Edits are saved from the parsed
+    /// plist instead, which keeps the user's key order.
+    #[allow(dead_code)]
+    pub fn to_value(&self) -> Value {
+        let mut dict = Dictionary::new();
+        let string = |s: &Option<String>| s.clone().map(Value::String);
+        let limits = |l: &BTreeMap<String, u64>| map_value(l, |n| Value::from(*n));
+
+        dict.insert("Label".to_string(), Value::String(self.label.clone()));
+        put(&mut dict, "Disabled", self.disabled.map(Value::from));
+        put(&mut dict, "UserName", string(&self.user_name));
+        put(&mut dict, "GroupName", string(&self.group_name));
+        put(
+            &mut dict,
+            "LimitLoadToSessionType",
+            self.limit_load_to_session_type
+                .as_ref()
+                .map(|s| one_or_many_value(s, |s| Value::String(s.clone()))),
+        );
+        put(&mut dict, "Program", string(&self.program));
+        put(&mut dict, "BundleProgram", string(&self.bundle_program));
+        put(
+            &mut dict,
+            "ProgramArguments",
+            self.program_arguments.as_deref().map(strings_value),
+        );
+        put(
+            &mut dict,
+            "KeepAlive",
+            self.keep_alive.as_ref().map(KeepAlive::to_value),
+        );
+        put(&mut dict, "RunAtLoad", self.run_at_load.map(Value::from));
+        put(&mut dict, "RootDirectory", string(&self.root_directory));
+        put(
+            &mut dict,
+            "WorkingDirectory",
+            string(&self.working_directory),
+        );
+        put(
+            &mut dict,
+            "EnvironmentVariables",
+            self.environment_variables
+                .as_ref()
+                .map(|e| map_value(e, |v| Value::String(v.clone()))),
+        );
+        put(
+            &mut dict,
+            "ExitTimeOut",
+            self.exit_time_out.map(Value::from),
+        );
+        put(
+            &mut dict,
+            "ThrottleInterval",
+            self.throttle_interval.map(Value::from),
+        );
+        put(
+            &mut dict,
+            "WatchPaths",
+            self.watch_paths.as_deref().map(strings_value),
+        );
+        put(
+            &mut dict,
+            "QueueDirectories",
+            self.queue_directories.as_deref().map(strings_value),
+        );
+        put(
+            &mut dict,
+            "StartOnMount",
+            self.start_on_mount.map(Value::from),
+        );
+        put(
+            &mut dict,
+            "StartInterval",
+            self.start_interval.map(Value::from),
+        );
+        put(
+            &mut dict,
+            "StartCalendarInterval",
+            self.start_calendar_interval
+                .as_ref()
+                .map(|c| one_or_many_value(c, CalendarInterval::to_value)),
+        );
+        put(&mut dict, "StandardInPath", string(&self.standard_in_path));
+        put(
+            &mut dict,
+            "StandardOutPath",
+            string(&self.standard_out_path),
+        );
+        put(
+            &mut dict,
+            "StandardErrorPath",
+            string(&self.standard_error_path),
+        );
+        put(
+            &mut dict,
+            "SoftResourceLimits",
+            self.soft_resource_limits.as_ref().map(limits),
+        );
+        put(
+            &mut dict,
+            "HardResourceLimits",
+            self.hard_resource_limits.as_ref().map(limits),
+        );
+        put(&mut dict, "Nice", self.nice.map(Value::from));
+        put(&mut dict, "ProcessType", string(&self.process_type));
+        put(
+            &mut dict,
+            "AbandonProcessGroup",
+            self.abandon_process_group.map(Value::from),
+        );
+        put(
+            &mut dict,
+            "MachServices",
+            self.mach_services
+                .as_ref()
+                .map(|m| map_value(m, MachService::to_value)),
+        );
+        put(
+            &mut dict,
+            "Sockets",
+            self.sockets
+                .as_ref()
+                .map(|s| map_value(s, |s| one_or_many_value(s, Socket::to_value))),
+        );
+        dict.extend(self.other.clone());
+
+        Value::Dictionary(dict)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use plist::Value;
+
+    use super::{
+        CalendarInterval, JobSpec, JobSpecError, KeepAlive, MachService, OneOrMany, ServiceName,
+    };
+
+    const JOB_SPEC: &str = include_str!("../../tests/fixtures/job_spec.plist");
+
+    fn parse(xml: &str) -> Result<JobSpec, JobSpecError> {
+        JobSpec::from_value(&Value::from_reader_xml(Cursor::new(xml)).unwrap())
+    }
+
+    /// A plist with just these keys after Label and Program
+    fn with_keys(keys: &str) -> String {
+        format!(
+            "<plist><dict><key>Label</key><string>com.example.agent</string>\
+             <key>Program</key><string>/usr/bin/true</string>{}</dict></plist>",
+            keys
+        )
+    }
+
+    #[test]
+    fn documented_keys() {
+        let spec = parse(JOB_SPEC).unwrap();
+
+        assert_eq!(spec.label, "com.example.agent");
+        assert_eq!(
+            spec.program_arguments,
+            Some(vec![
+                "/usr/local/bin/agent".to_string(),
+                "--serve".to_string()
+            ])
+        );
+
+        match &spec.keep_alive {
+            Some(KeepAlive::Conditions(c)) => {
+                assert_eq!(c.successful_exit, Some(false));
+                assert_eq!(
+                    c.path_state.as_ref().unwrap().get("/tmp/ready"),
+                    Some(&true)
+                );
+            }
+            other => panic!("KeepAlive {:?}", other),
+        }
+
+        assert_eq!(
+            spec.start_calendar_interval,
+            Some(OneOrMany::Many(vec![
+                CalendarInterval {
+                    hour: Some(3),
+                    minute: Some(30),
+                    ..Default::default()
+                },
+                CalendarInterval {
+                    weekday: Some(0),
+                    ..Default::default()
+                },
+            ]))
+        );
+        assert_eq!(
+            spec.limit_load_to_session_type,
+            Some(OneOrMany::One("Aqua".to_string()))
+        );
+        assert_eq!(
+            spec.mach_services
+                .as_ref()
+                .unwrap()
+                .get("com.example.agent.xpc"),
+            Some(&MachService::Enabled(true))
+        );
+
+        let listener = match &spec.sockets.as_ref().unwrap()["Listener"] {
+            OneOrMany::One(s) => s,
+            other => panic!("Listener {:?}", other),
+        };
+        assert_eq!(listener.sock_service_name, Some(ServiceName::Port(8080)));
+
+        assert_eq!(
+            spec.soft_resource_limits
+                .as_ref()
+                .unwrap()
+                .get("NumberOfFiles"),
+            Some(&1024)
+        );
+
+        // Keys launchk does not model are kept
+        assert!(spec.other.contains_key("AssociatedBundleIdentifiers"));
+    }
+
+    #[test]
+    fn round_trip() {
+        let original = Value::from_reader_xml(Cursor::new(JOB_SPEC)).unwrap();
+        let spec = JobSpec::from_value(&original).unwrap();
+
+        assert_eq!(spec.to_value(), original);
+
+        let mut binary = Vec::new();
+        spec.to_value().to_writer_binary(&mut binary).unwrap();
+        let from_binary = Value::from_reader(Cursor::new(binary)).unwrap();
+
+        assert_eq!(JobSpec::from_value(&from_binary).unwrap(), spec);
+    }
+
+    #[test]
+    fn precise_errors() {
+        let cases = [
+            (
+                "<key>RunAtLoad</key><string>yes</string>",
+                "RunAtLoad: expected boolean, found string",
+            ),
+            (
+                "<key>KeepAlive</key><dict><key>PathState</key><dict>\
+                 <key>/tmp/ready</key><integer>1</integer></dict></dict>",
+                "KeepAlive.PathState./tmp/ready: expected boolean, found integer",
+            ),
+            (
+                "<key>WatchPaths</key><array><string>/tmp</string><true/></array>",
+                "WatchPaths[1]: expected string, found boolean",
+            ),
+            (
+                "<key>StartCalendarInterval</key><dict><key>Hour</key><integer>24</integer></dict>",
+                "StartCalendarInterval.Hour: 24 is not within 0-23",
+            ),
+            (
+                "<key>StartInterval</key><integer>-1</integer>",
+                "StartInterval: -1 is negative",
+            ),
+            (
+                "<key>ProcessType</key><string>Fast</string>",
+                "ProcessType: Fast is not one of Background, Standard, Adaptive, Interactive",
+            ),
+            (
+                "<key>Nice</key><integer>40</integer>",
+                "Nice: 40 is not within -20-20",
+            ),
+        ];
+
+        for (keys, message) in cases.iter() {
+            assert_eq!(parse(&with_keys(keys)).unwrap_err().to_string(), *message);
+        }
+
+        assert_eq!(
+            parse("<plist><dict><key>Program</key><string>/usr/bin/true</string></dict></plist>"),
+            Err(JobSpecError::MissingKey("Label".to_string()))
+        );
+        assert_eq!(
+            parse("<plist><dict><key>Label</key><string>com.example.agent</string></dict></plist>"),
+            Err(JobSpecError::MissingKey(
+                "Program, BundleProgram or ProgramArguments".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn accepted_by_launchd() {
+        let spec = parse(&with_keys(
+            "<key>StartCalendarInterval</key><dict><key>Minutes</key><integer>5</integer></dict>",
+        ))
+        .unwrap();
+        let interval = match spec.start_calendar_interval {
+            Some(OneOrMany::One(interval)) => interval,
+            other => panic!("{:?}", other),
+        };
+        assert!(interval.other.contains_key("Minutes"));
+
+        let spec = parse(
+            "<plist><dict><key>Label</key><string>com.example.agent</string>\
+             <key>BundleProgram</key><string>Contents/MacOS/agent</string></dict></plist>",
+        )
+        .unwrap();
+        assert_eq!(spec.bundle_program.as_deref(), Some("Contents/MacOS/agent"));
+    }
+}
@@ -0,0 +1,538 @@
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::Path;
+
+use plist::{Dictionary, Value};
+
+use crate::launchd::entry_status::agent_uid;
+use crate::launchd::job_spec::{JobSpec, CALENDAR_KEYS};
+use crate::launchd::plist::{entry_kind, LaunchdEntryLocation, LaunchdEntryType};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Severity {
+    Warning,
+    /// launchd will refuse or fail to run the job
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(&format!("{:?}", self).to_ascii_lowercase())
+    }
+}
+
+/// A rule that did not pass
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Finding {
+    pub id: &'static str,
+    pub severity: Severity,
+    /// None if it is about the whole file
+    pub key: Option<String>,
+    pub message: String,
+    pub hint: &'static str,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}[{}] {}", self.severity, self.id, self.message)?;
+        write!(f, "  fix: {}", self.hint)
+    }
+}
+
+/// The plist being linted
+struct LintContext<'a> {
+    path: &'a Path,
+    value: &'a Value,
+    /// Empty if the plist is not a dictionary
+    dict: &'a Dictionary,
+    /// None if it is not in one of the launchd directories
+    kind: Option<(LaunchdEntryType, LaunchdEntryLocation)>,
+}
+
+/// A key at fault (None for the whole file), and what is wrong
+type Problem = (Option<String>, String);
+
+pub struct Rule {
+    pub id: &'static str,
+    pub severity: Severity,
+    pub description: &'static str,
+    pub hint: &'static str,
+    check: fn(&LintContext) -> Vec<Problem>,
+}
+
+const SCHEMA: &str = "schema";
+
+pub static RULES: [Rule; 11] = [
+    Rule {
+        id: SCHEMA,
+        severity: Severity::Error,
+        description: "Keys have the types and values launchd.plist(5) documents",
+        hint: "Fix the value of the key, see man launchd.plist",
+        check: check_schema,
+    },
+    Rule {
+        id: "label-filename",
+        severity: Severity::Warning,
+        description: "Label matches the file name",
+        hint: "Name the file <Label>.plist",
+        check: check_label_filename,
+    },
+    Rule {
+        id: "program-path",
+        severity: Severity::Error,
+        description: "Program (or ProgramArguments[0]) is an executable at an absolute path",
+        hint: "Install the program, chmod +x it, or use its absolute path",
+        check: check_program_path,
+    },
+    Rule {
+        id: "program-arguments-mismatch",
+        severity: Severity::Warning,
+        description: "ProgramArguments[0] is only argv[0] when Program is set",
+        hint: "Program is what runs, ProgramArguments[0] is just the name it sees",
+        check: check_program_arguments_mismatch,
+    },
+    Rule {
+        id: "keepalive-interval",
+        severity: Severity::Warning,
+        description: "KeepAlive jobs do not also set StartInterval",
+        hint: "Remove StartInterval, or KeepAlive for a periodic job",
+        check: check_keepalive_interval,
+    },
+    Rule {
+        id: "calendar-range",
+        severity: Severity::Error,
+        description: "StartCalendarInterval fields are in range",
+        hint: "Minute 0-59, Hour 0-23, Day 1-31, Weekday 0-7, Month 1-12",
+        check: check_calendar_range,
+    },
+    Rule {
+        id: "calendar-key",
+        severity: Severity::Warning,
+        description: "StartCalendarInterval only has Minute, Hour, Day, Weekday and Month",
+        hint: "launchd ignores other keys, check the spelling",
+        check: check_calendar_keys,
+    },
+    Rule {
+        id: "log-directory",
+        severity: Severity::Warning,
+        description: "The directories of StandardOutPath and StandardErrorPath exist",
+        hint: "Create the directory, launchd does not",
+        check: check_log_directory,
+    },
+    Rule {
+        id: "agent-username",
+        severity: Severity::Warning,
+        description: "Agents do not set UserName",
+        hint: "Remove UserName, agents run as the logged in user",
+        check: check_agent_username,
+    },
+    Rule {
+        id: "deprecated-key",
+        severity: Severity::Warning,
+        description: "No deprecated keys",
+        hint: "Remove the key, or use what replaces it",
+        check: check_deprecated_keys,
+    },
+    Rule {
+        id: "file-ownership",
+        severity: Severity::Error,
+        description: "The plist has the owner and mode launchd requires",
+        hint: "chown root:wheel (or you, for your agents) and chmod 644",
+        check: check_file_ownership,
+    },
+];
+
+/// Keys launchd no longer reads
+const DEPRECATED_KEYS: [(&str, &str); 4] = [
+    ("OnDemand", "use KeepAlive"),
+    ("ServiceIPC", "it is ignored"),
+    ("HopefullyExitsFirst", "it is ignored"),
+    ("HopefullyExitsLast", "it is ignored"),
+];
+
+fn string<'a>(ctx: &'a LintContext, key: &str) -> Option<&'a str> {
+    ctx.dict.get(key).and_then(Value::as_string)
+}
+
+fn first_argument<'a>(ctx: &'a LintContext) -> Option<&'a str> {
+    ctx.dict
+        .get("ProgramArguments")
+        .and_then(Value::as_array)
+        .and_then(|a| a.first())
+        .and_then(Value::as_string)
+}
+
+fn check_schema(ctx: &LintContext) -> Vec<Problem> {
+    match JobSpec::from_value(ctx.value) {
+        Ok(_) => vec![],
+        Err(e) => vec![(e.key().map(str::to_string), e.to_string())],
+    }
+}
+
+fn check_label_filename(ctx: &LintContext) -> Vec<Problem> {
+    let label = match string(ctx, "Label") {
+        Some(label) => label,
+        None => return vec![],
+    };
+    let stem = ctx.path.file_stem().map(|s| s.to_string_lossy());
+
+    if stem.as_deref() == Some(label) {
+        return vec![];
+    }
+
+    vec![(
+        Some("Label".to_string()),
+        format!("Label {} does not match the file name", label),
+    )]
+}
+
+fn check_program_path(ctx: &LintContext) -> Vec<Problem> {
+    let (key, program) = match (string(ctx, "Program"), first_argument(ctx)) {
+        (Some(program), _) => ("Program", program),
+        (None, Some(program)) => ("ProgramArguments[0]", program),
+        (None, None) => return vec![],
+    };
+
+    let problem = if !Path::new(program).is_absolute() {
+        "is not an absolute path"
+    } else {
+        match fs::metadata(program) {
+            Err(_) => "does not exist",
+            Ok(m) if m.is_dir() || m.permissions().mode() & 0o111 == 0 => "is not executable",
+            Ok(_) => return vec![],
+        }
+    };
+
+    vec![(Some(key.to_string()), format!("{} {}", program, problem))]
+}
+
+fn check_program_arguments_mismatch(ctx: &LintContext) -> Vec<Problem> {
+    match (string(ctx, "Program"), first_argument(ctx)) {
+        (Some(program), Some(argument)) if program != argument => vec![(
+            Some("ProgramArguments[0]".to_string()),
+            format!("Runs {}, with argv[0] {}", program, argument),
+        )],
+        _ => vec![],
+    }
+}
+
+fn check_keepalive_interval(ctx: &LintContext) -> Vec<Problem> {
+    let keep_alive = match ctx.dict.get("KeepAlive") {
+        None | Some(Value::Boolean(false)) => false,
+        Some(_) => true,
+    };
+
+    if keep_alive && ctx.dict.contains_key("StartInterval") {
+        vec![(
+            Some("StartInterval".to_string()),
+            "KeepAlive restarts the job, StartInterval runs it again".to_string(),
+        )]
+    } else {
+        vec![]
+    }
+}
+
+/// StartCalendarInterval dictionaries, with their key paths
+fn calendar_intervals<'a>(ctx: &'a LintContext) -> Vec<(String, &'a Dictionary)> {
+    match ctx.dict.get("StartCalendarInterval") {
+        Some(Value::Dictionary(d)) => vec![("StartCalendarInterval".to_string(), d)],
+        Some(Value::Array(a)) => a
+            .iter()
+            .enumerate()
+            .filter_map(|(i, v)| {
+                Some((format!("StartCalendarInterval[{}]", i), v.as_dictionary()?))
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn check_calendar_range(ctx: &LintContext) -> Vec<Problem> {
+    calendar_intervals(ctx)
+        .iter()
+        .flat_map(|(path, interval)| {
+            CALENDAR_KEYS.iter().filter_map(move |(key, min, max)| {
+                let n = interval.get(key)?.as_signed_integer()?;
+
+                if n >= *min as i64 && n <= *max as i64 {
+                    return None;
+                }
+
+                Some((
+                    Some(format!("{}.{}", path, key)),
+                    format!("{} is {}, must be {}-{}", key, n, min, max),
+                ))
+            })
+        })
+        .collect()
+}
+
+fn check_calendar_keys(ctx: &LintContext) -> Vec<Problem> {
+    calendar_intervals(ctx)
+        .iter()
+        .flat_map(|(path, interval)| {
+            interval
+                .keys()
+                .filter(|key| !CALENDAR_KEYS.iter().any(|(k, _, _)| k == key))
+                .map(move |key| {
+                    (
+                        Some(format!("{}.{}", path, key)),
+                        format!("{} is not a calendar key", key),
+                    )
+                })
+        })
+        .collect()
+}
+
+fn check_log_directory(ctx: &LintContext) -> Vec<Problem> {
+    ["StandardOutPath", "StandardErrorPath"]
+        .iter()
+        .filter_map(|key| {
+            let dir = Path::new(string(ctx, key)?).parent()?;
+
+            if dir.is_dir() {
+                return None;
+            }
+
+            Some((
+                Some(key.to_string()),
+                format!("{} does not exist", dir.display()),
+            ))
+        })
+        .collect()
+}
+
+fn check_agent_username(ctx: &LintContext) -> Vec<Problem> {
+    let is_agent = matches!(ctx.kind, Some((LaunchdEntryType::Agent, _)));
+
+    match string(ctx, "UserName") {
+        Some(user) if is_agent => vec![(
+            Some("UserName".to_string()),
+            format!("UserName {} is ignored for agents", user),
+        )],
+        _ => vec![],
+    }
+}
+
+fn check_deprecated_keys(ctx: &LintContext) -> Vec<Problem> {
+    DEPRECATED_KEYS
+        .iter()
+        .filter(|(key, _)| ctx.dict.contains_key(key))
+        .map(|(key, instead)| {
+            (
+                Some(key.to_string()),
+                format!("{} is deprecated, {}", key, instead),
+            )
+        })
+        .collect()
+}
+
+/// launchd refuses plists writable by others, and daemons (or agents
+/// outside your home) not owned by root
+fn check_file_ownership(ctx: &LintContext) -> Vec<Problem> {
+    let metadata = match fs::metadata(ctx.path) {
+        Ok(m) => m,
+        Err(_) => return vec![],
+    };
+
+    let mut problems = vec![];
+
+    if metadata.mode() & 0o022 != 0 {
+        problems.push((
+            None,
+            format!(
+                "Mode {:o} is group or world writable",
+                metadata.mode() & 0o777
+            ),
+        ));
+    }
+
+    let owner = match &ctx.kind {
+        Some((_, LaunchdEntryLocation::User)) => Some(agent_uid()),
+        Some(_) => Some(0),
+        None => None,
+    };
+
+    if let Some(owner) = owner.filter(|o| *o != metadata.uid() as u64) {
+        problems.push((
+            None,
+            format!("Owned by uid {}, must be uid {}", metadata.uid(), owner),
+        ));
+    }
+
+    problems
+}
+
+fn lint(ctx: &LintContext) -> Vec<Finding> {
+    let mut findings: Vec<Finding> = RULES
+        .iter()
+        .flat_map(|rule| {
+            (rule.check)(ctx)
+                .into_iter()
+                .map(move |(key, message)| Finding {
+                    id: rule.id,
+                    severity: rule.severity,
+                    key,
+                    message,
+                    hint: rule.hint,
+                })
+        })
+        .collect();
+
+    // A rule about the same key says more than the schema error
+    let keys: HashSet<Option<String>> = findings
+        .iter()
+        .filter(|f| f.id != SCHEMA && f.key.is_some())
+        .map(|f| f.key.clone())
+        .collect();
+    findings.retain(|f| f.id != SCHEMA || !keys.contains(&f.key));
+
+    // Errors first
+    findings.sort_by_key(|f| Reverse(f.severity));
+    findings
+}
+
+/// Check the plist at path against all rules
+pub fn lint_file(path: &Path) -> Result<Vec<Finding>, String> {
+    let value = Value::from_file(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let empty = Dictionary::new();
+
+    Ok(lint(&LintContext {
+        path,
+        value: &value,
+        dict: value.as_dictionary().unwrap_or(&empty),
+        kind: entry_kind(&path.to_string_lossy()),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::{Path, PathBuf};
+
+    use plist::{Dictionary, Value};
+
+    use crate::launchd::plist::{LaunchdEntryLocation, LaunchdEntryType};
+
+    use super::{lint, lint_file, LintContext, Severity, RULES};
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/lint")
+            .join(name)
+    }
+
+    /// Keys the rule reported for the fixture, as if it were an agent
+    fn check(rule_id: &str, name: &str) -> Vec<Option<String>> {
+        let path = fixture(name);
+        let value = Value::from_file(&path).unwrap();
+        let rule = RULES.iter().find(|r| r.id == rule_id).unwrap();
+
+        let ctx = LintContext {
+            path: &path,
+            value: &value,
+            dict: value.as_dictionary().unwrap(),
+            kind: Some((LaunchdEntryType::Agent, LaunchdEntryLocation::Global)),
+        };
+
+        (rule.check)(&ctx).into_iter().map(|(key, _)| key).collect()
+    }
+
+    fn keys(keys: &[&str]) -> Vec<Option<String>> {
+        keys.iter().map(|k| Some(k.to_string())).collect()
+    }
+
+    #[test]
+    fn good_plist_passes() {
+        for rule in RULES.iter().filter(|r| r.id != "file-ownership") {
+            assert!(
+                check(rule.id, "com.example.good.plist").is_empty(),
+                "{}",
+                rule.id
+            );
+        }
+    }
+
+    #[test]
+    fn each_rule() {
+        let cases = [
+            ("label-filename", keys(&["Label"])),
+            ("program-path", keys(&["Program"])),
+            ("program-arguments-mismatch", keys(&["ProgramArguments[0]"])),
+            ("keepalive-interval", keys(&["StartInterval"])),
+            (
+                "calendar-range",
+                keys(&[
+                    "StartCalendarInterval[0].Hour",
+                    "StartCalendarInterval[1].Month",
+                ]),
+            ),
+            ("calendar-key", keys(&["StartCalendarInterval[0].Hours"])),
+            ("log-directory", keys(&["StandardErrorPath"])),
+            ("agent-username", keys(&["UserName"])),
+            ("deprecated-key", keys(&["OnDemand", "ServiceIPC"])),
+        ];
+
+        for (rule_id, expected) in cases.iter() {
+            assert_eq!(&check(rule_id, "misnamed.plist"), expected, "{}", rule_id);
+        }
+
+        assert_eq!(check("schema", "not-executable.plist"), vec![]);
+        assert_eq!(
+            check("program-path", "not-executable.plist"),
+            keys(&["ProgramArguments[0]"])
+        );
+    }
+
+    #[test]
+    fn schema_defers_to_rules() {
+        let path = fixture("misnamed.plist");
+        let value = Value::from_file(&path).unwrap();
+        let ctx = LintContext {
+            path: &path,
+            value: &value,
+            dict: value.as_dictionary().unwrap(),
+            kind: None,
+        };
+
+        let findings = lint(&ctx);
+        let ids: Vec<&str> = findings.iter().map(|f| f.id).collect();
+
+        // The calendar error is reported once, by calendar-range
+        assert!(!ids.contains(&"schema"));
+        assert_eq!(findings[0].severity, Severity::Error);
+        assert!(findings[0]
+            .to_string()
+            .ends_with("\n  fix: Install the program, chmod +x it, or use its absolute path"));
+
+        let empty = Dictionary::new();
+        let not_a_dict = Value::Boolean(true);
+        let ctx = LintContext {
+            dict: &empty,
+            value: &not_a_dict,
+            ..ctx
+        };
+        assert_eq!(lint(&ctx)[0].id, "schema");
+    }
+
+    #[test]
+    fn world_writable() {
+        let path = std::env::temp_dir().join(format!("launchk-lint-{}.plist", std::process::id()));
+        fs::copy(fixture("com.example.good.plist"), &path).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o666)).unwrap();
+
+        let findings = lint_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let ownership: Vec<&String> = findings
+            .iter()
+            .filter(|f| f.id == "file-ownership")
+            .map(|f| &f.message)
+            .collect();
+        assert_eq!(ownership, vec!["Mode 666 is group or world writable"]);
+    }
+}
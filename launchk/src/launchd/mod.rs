@@ -1,7 +1,49 @@
+/// Replacing files without partial writes
+pub mod atomic_write;
+
 pub mod command;
 
+/// launchctl print output for a domain
+pub mod domain_info;
+
+/// launchctl dumpstate output
+pub mod dumpstate;
+
 pub mod entry_status;
+pub mod error;
+
+/// Last exit status and crash loops
+pub mod exit_status;
 pub mod job_type_filter;
 
+/// Backups and a journal of plist edits
+pub mod history;
+
+/// launchctl dumpjpcategory output
+pub mod jetsam;
+
+/// launchd.plist(5) job definitions
+pub mod job_spec;
+
+/// Checks for launchd.plist files
+pub mod lint;
+
+/// launchctl print-disabled output
+pub mod overrides;
+
+/// launchctl procinfo output
+pub mod procinfo;
+pub mod routine;
+
+/// launchctl print output for a service
+pub mod service_info;
+
 /// plist management
 pub mod plist;
+
+/// Changes between two plists by key path
+pub mod plist_diff;
+
+/// Helpers shared by tests
+#[cfg(test)]
+mod test_util;
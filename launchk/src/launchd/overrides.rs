@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::launchd::dumpstate::{parse_entries, StateBlock, StateEntry};
+
+/// An explicit enable or disable of a label in a domain. Labels
+/// without one use the plist's Disabled key.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum OverrideState {
+    Enabled,
+    Disabled,
+}
+
+impl OverrideState {
+    /// `enabled` and `disabled`, or `false` and `true` on older macOS
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "enabled" | "false" => Some(OverrideState::Enabled),
+            "disabled" | "true" => Some(OverrideState::Disabled),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for OverrideState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OverrideState::Enabled => write!(f, "enabled"),
+            OverrideState::Disabled => write!(f, "disabled"),
+        }
+    }
+}
+
+/// Parse print-disabled output, e.g. `"com.apple.ftpd" => disabled`.
+/// Lines that are not an override are skipped.
+pub fn parse_overrides(text: &str) -> HashMap<String, OverrideState> {
+    let root = parse_entries(text);
+
+    // Overrides are in a "disabled services" block, or at the top level
+    let blocks = root.entries.iter().filter_map(|e| match e {
+        StateEntry::Block(b) => Some(b),
+        _ => None,
+    });
+
+    std::iter::once(&root)
+        .chain(blocks)
+        .flat_map(block_overrides)
+        .collect()
+}
+
+/// The overrides in a block, e.g. a domain's `disabled services`
+pub fn block_overrides(block: &StateBlock) -> impl Iterator<Item = (String, OverrideState)> + '_ {
+    block.properties().filter_map(|(label, value)| {
+        OverrideState::parse(value).map(|state| (label.to_string(), state))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_overrides, OverrideState};
+
+    #[test]
+    fn enabled_and_disabled() {
+        let overrides = parse_overrides(
+            r#"disabled services = {
+	"com.apple.ftpd" => disabled
+	"com.apple.usbmuxd" => enabled
+	"org.example.my-agent_2" => disabled
+	"com.old.style" => true
+	"not an override"
+}
+"#,
+        );
+
+        assert_eq!(overrides.len(), 4);
+        assert_eq!(
+            overrides.get("com.apple.ftpd"),
+            Some(&OverrideState::Disabled)
+        );
+        assert_eq!(
+            overrides.get("com.apple.usbmuxd"),
+            Some(&OverrideState::Enabled)
+        );
+        assert_eq!(
+            overrides.get("org.example.my-agent_2"),
+            Some(&OverrideState::Disabled)
+        );
+        assert_eq!(
+            overrides.get("com.old.style"),
+            Some(&OverrideState::Disabled)
+        );
+    }
+}
@@ -5,15 +5,18 @@ use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::{Once, RwLock};
 
+use crate::launchd::atomic_write::create_temp;
+use crate::launchd::history::History;
+use crate::launchd::job_spec::JobSpec;
 use crate::launchd::job_type_filter::JobTypeFilter;
+use crate::launchd::plist_diff::{diff, Difference};
 use notify_debouncer_mini::{new_debouncer, notify::RecursiveMode, DebounceEventResult};
-use std::fs::File;
-use std::io::Read;
 use std::process::Command;
 use std::sync::mpsc::{channel, Receiver, Sender};
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::Duration;
 use tokio::runtime::Handle;
-use xpc_sys::enums::DomainType;
+use xpc_sys::enums::SessionType;
+use xpc_sys::service_target::ServiceTarget;
 
 pub static PLIST_MAP_INIT: Once = Once::new();
 
@@ -33,6 +36,10 @@ od -xc binary.plist
 */
 static PLIST_MAGIC: &str = "bplist00";
 
+pub fn is_binary_plist(bytes: &[u8]) -> bool {
+    bytes.starts_with(PLIST_MAGIC.as_bytes())
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum LaunchdEntryType {
     /// Runs on behalf of currently logged in user
@@ -59,25 +66,46 @@ pub enum LaunchdEntryLocation {
     User,
 }
 
-impl From<LaunchdEntryLocation> for DomainType {
-    fn from(value: LaunchdEntryLocation) -> Self {
-        match value {
-            LaunchdEntryLocation::Global | LaunchdEntryLocation::System => DomainType::System,
-            LaunchdEntryLocation::User => DomainType::User,
-        }
-    }
-}
-
 impl fmt::Display for LaunchdEntryLocation {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{:?}", self)
     }
 }
 
+/// The domain launchctl would load a job into. Daemons go in the system
+/// domain, agents in the user's GUI domain unless LimitLoadToSessionType
+/// says otherwise: Background and StandardIO agents go in the user domain,
+/// System agents in the system domain, LoginWindow agents in the login
+/// domain of `login_asid`. launchd only loads root owned plists into the
+/// system domain, so user agents never resolve to it.
+pub fn resolve_domain(
+    entry_type: &LaunchdEntryType,
+    entry_location: &LaunchdEntryLocation,
+    session_type: &SessionType,
+    uid: u64,
+    login_asid: Option<u64>,
+) -> ServiceTarget {
+    match (entry_type, entry_location, session_type) {
+        (LaunchdEntryType::Daemon, _, _) => ServiceTarget::system(),
+        (_, LaunchdEntryLocation::User, SessionType::System) => ServiceTarget::user(uid),
+        (_, _, SessionType::System) => ServiceTarget::system(),
+        (_, _, SessionType::Background) | (_, _, SessionType::StandardIO) => {
+            ServiceTarget::user(uid)
+        }
+        // The login domain is keyed by the gui domain's audit session. If
+        // launchd did not tell us that, like Aqua (and launchctl's default)
+        (_, _, SessionType::LoginWindow) => login_asid
+            .map(ServiceTarget::login)
+            .unwrap_or_else(|| ServiceTarget::gui(uid)),
+        _ => ServiceTarget::gui(uid),
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct LaunchdPlist {
     pub entry_type: LaunchdEntryType,
     pub entry_location: LaunchdEntryLocation,
+    pub limit_load_to_session_type: SessionType,
     pub plist_path: String,
     pub readonly: bool,
 }
@@ -151,37 +179,85 @@ async fn fsnotify_subscriber() {
     }
 }
 
+/// LimitLoadToSessionType is a string or an array of them,
+/// prefer Aqua if the agent loads in more than one session
+fn session_type_from_plist(value: Option<&plist::Value>) -> SessionType {
+    let session_types: Vec<SessionType> = match value {
+        Some(plist::Value::String(s)) => vec![s.clone().into()],
+        Some(plist::Value::Array(a)) => a
+            .iter()
+            .filter_map(|v| v.as_string())
+            .map(|s| s.to_string().into())
+            .collect(),
+        _ => vec![],
+    };
+
+    if session_types.contains(&SessionType::Aqua) {
+        SessionType::Aqua
+    } else {
+        session_types
+            .into_iter()
+            .next()
+            .unwrap_or(SessionType::Unknown)
+    }
+}
+
+/// Whether the plist at path is an agent or a daemon, and where it is
+/// installed. None if it is not in one of the launchd directories.
+pub fn entry_kind(path: &str) -> Option<(LaunchdEntryType, LaunchdEntryLocation)> {
+    let kinds = [
+        (
+            USER_LAUNCH_AGENTS.as_str(),
+            LaunchdEntryType::Agent,
+            LaunchdEntryLocation::User,
+        ),
+        (
+            ADMIN_LAUNCH_AGENTS,
+            LaunchdEntryType::Agent,
+            LaunchdEntryLocation::Global,
+        ),
+        (
+            ADMIN_LAUNCH_DAEMONS,
+            LaunchdEntryType::Daemon,
+            LaunchdEntryLocation::Global,
+        ),
+        (
+            SYSTEM_LAUNCH_AGENTS,
+            LaunchdEntryType::Agent,
+            LaunchdEntryLocation::System,
+        ),
+        (
+            SYSTEM_LAUNCH_DAEMONS,
+            LaunchdEntryType::Daemon,
+            LaunchdEntryLocation::System,
+        ),
+    ];
+
+    kinds
+        .iter()
+        .find(|(dir, _, _)| Path::new(path).starts_with(dir))
+        .map(|(_, entry_type, location)| (entry_type.clone(), location.clone()))
+}
+
 fn build_label_map_entry(plist_path: PathBuf) -> Option<(String, LaunchdPlist)> {
     let path_string = plist_path.to_string_lossy().to_string();
-    let label = plist::Value::from_file(&path_string).ok()?;
-    let label = label
-        .as_dictionary()
+    let value = plist::Value::from_file(&path_string).ok()?;
+    let dict = value.as_dictionary();
+    let label = dict
         .and_then(|d| d.get("Label"))
         .and_then(|v| v.as_string());
+    let limit_load_to_session_type =
+        session_type_from_plist(dict.and_then(|d| d.get("LimitLoadToSessionType")));
 
-    let entry_type = if path_string.starts_with(ADMIN_LAUNCH_DAEMONS)
-        || path_string.starts_with(SYSTEM_LAUNCH_DAEMONS)
-    {
-        LaunchdEntryType::Daemon
-    } else {
-        LaunchdEntryType::Agent
-    };
-
-    let entry_location = if path_string.starts_with(&*USER_LAUNCH_AGENTS) {
-        LaunchdEntryLocation::User
-    } else if path_string.starts_with(ADMIN_LAUNCH_AGENTS)
-        || path_string.starts_with(ADMIN_LAUNCH_DAEMONS)
-    {
-        LaunchdEntryLocation::Global
-    } else {
-        LaunchdEntryLocation::System
-    };
+    let (entry_type, entry_location) =
+        entry_kind(&path_string).unwrap_or((LaunchdEntryType::Agent, LaunchdEntryLocation::System));
 
     Some((
         label?.to_string(),
         LaunchdPlist {
             entry_location,
             entry_type,
+            limit_load_to_session_type,
             plist_path: path_string,
             readonly: plist_path
                 .metadata()
@@ -251,39 +327,77 @@ pub fn for_label<S: Into<String>>(label: S) -> Option<LaunchdPlist> {
     label_map.get(label.into().as_str()).cloned()
 }
 
-/// Given a LaunchdPlist, start editor pointing to temporary file
-/// and replace on exit. Uses plist crate to validate changes and
-/// help show contents for binary encoded files
-pub fn edit_and_replace(plist_meta: &LaunchdPlist) -> Result<(), String> {
-    if plist_meta.readonly {
-        return Err("plist is read-only!".to_string());
+/// A validated edit of a plist, not yet written back. Keeps the job it
+/// was opened for, the highlighted row can change before it is applied.
+pub struct PlistEdit {
+    label: String,
+    target: ServiceTarget,
+    /// Whether the job was loaded when the editor opened
+    loaded: bool,
+    plist_path: String,
+    is_binary: bool,
+    original: plist::Value,
+    edited: plist::Value,
+    /// What $EDITOR saved, so editing again starts from the last edit
+    temp_path: PathBuf,
+}
+
+impl PlistEdit {
+    pub fn label(&self) -> &str {
+        &self.label
     }
 
-    let mut file =
-        File::open(&plist_meta.plist_path).map_err(|_| "Couldn't read file".to_string())?;
+    pub fn target(&self) -> &ServiceTarget {
+        &self.target
+    }
 
-    // We want to write back in the correct format,
-    // can't assume we can safely write XML everywhere?
-    let mut magic_buf: [u8; 8] = [0; 8];
-    file.read_exact(&mut magic_buf)
-        .map_err(|_| "Couldn't read magic".to_string())?;
-    let is_binary = std::str::from_utf8(&magic_buf)
-        .map_err(|_| "Couldn't read magic".to_string())?
-        == PLIST_MAGIC;
+    pub fn loaded(&self) -> bool {
+        self.loaded
+    }
 
-    // plist -> validate with crate -> temp file
-    let og_plist = plist::Value::from_file(&plist_meta.plist_path).map_err(|e| e.to_string())?;
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("Must get ts");
-    let temp_path = Path::new(&*TMP_DIR).join(format!("{}", now.as_secs()));
-    og_plist
-        .to_file_xml(&temp_path)
+    pub fn plist_path(&self) -> &str {
+        &self.plist_path
+    }
+
+    /// The changes writing this edit back would make
+    pub fn diff(&self) -> Vec<Difference> {
+        diff(&self.original, &self.edited)
+    }
+
+    /// Open the last edit in $EDITOR again. If it does not parse or
+    /// validate, the previous edit is kept.
+    pub fn edit_again(&mut self) -> Result<(), String> {
+        self.edited = run_editor(&self.temp_path)?;
+        Ok(())
+    }
+
+    /// Write the edit back in the plist's format, backing up the plist
+    /// it replaces in `history`
+    pub fn apply(self, history: &History) -> Result<(), String> {
+        let mut bytes = vec![];
+
+        if self.is_binary {
+            self.edited.to_writer_binary(&mut bytes)
+        } else {
+            self.edited.to_writer_xml(&mut bytes)
+        }
         .map_err(|e| e.to_string())?;
 
-    // Start $EDITOR
+        history.replace(&self.label, &self.plist_path, &bytes)?;
+        self.discard();
+
+        Ok(())
+    }
+
+    pub fn discard(self) {
+        fs::remove_file(&self.temp_path).ok();
+    }
+}
+
+/// $EDITOR the temp file, then validate what it saved
+fn run_editor(temp_path: &Path) -> Result<plist::Value, String> {
     let exit = Command::new(&*EDITOR)
-        .arg(&temp_path)
+        .arg(temp_path)
         .status()
         .map_err(|e| format!("{} failed: {}", &*EDITOR, e))?;
 
@@ -291,21 +405,202 @@ pub fn edit_and_replace(plist_meta: &LaunchdPlist) -> Result<(), String> {
         return Err(format!("{} did not exit successfully", &*EDITOR));
     }
 
-    // temp file -> validate with crate -> original
-    let plist =
-        plist::Value::from_file(&temp_path).map_err(|e| format!("Changes not saved: {}", e))?;
+    read_validated(temp_path)
+}
+
+/// Parse a plist and check it as a JobSpec. The parsed value is what gets
+/// written back, so the keys stay in the order the user saved them.
+fn read_validated(path: &Path) -> Result<plist::Value, String> {
+    let plist = plist::Value::from_file(path).map_err(|e| format!("Changes not saved: {}", e))?;
+    JobSpec::from_value(&plist).map_err(|e| format!("Changes not saved: {}", e))?;
+
+    Ok(plist)
+}
 
-    if og_plist == plist {
-        return Err("No changes made".to_string());
+/// Given a LaunchdPlist, start editor pointing to temporary file
+/// and return the edit to preview before it replaces the plist. Changes
+/// are validated as a JobSpec, the plist crate helps show contents for
+/// binary encoded files
+pub fn edit_plist(
+    label: &str,
+    target: ServiceTarget,
+    loaded: bool,
+    plist_meta: &LaunchdPlist,
+) -> Result<PlistEdit, String> {
+    if plist_meta.readonly {
+        return Err("plist is read-only!".to_string());
     }
 
-    let writer = if is_binary {
-        plist::Value::to_file_binary
-    } else {
-        plist::Value::to_file_xml
+    // We want to write back in the correct format,
+    // can't assume we can safely write XML everywhere?
+    let is_binary = is_binary_plist(
+        &fs::read(&plist_meta.plist_path).map_err(|_| "Couldn't read file".to_string())?,
+    );
+
+    // plist -> validate with crate -> temp file
+    let og_plist = plist::Value::from_file(&plist_meta.plist_path).map_err(|e| e.to_string())?;
+    let (temp_path, temp_file) =
+        create_temp(Path::new(&*TMP_DIR), "launchk-edit").map_err(|e| e.to_string())?;
+
+    if let Err(e) = og_plist.to_writer_xml(temp_file) {
+        fs::remove_file(&temp_path).ok();
+        return Err(e.to_string());
+    }
+
+    // temp file -> validate with crate -> preview
+    let edited = match run_editor(&temp_path) {
+        Ok(edited) if edited != og_plist => edited,
+        result => {
+            fs::remove_file(&temp_path).ok();
+            return Err(result.err().unwrap_or("No changes made".to_string()));
+        }
+    };
+
+    Ok(PlistEdit {
+        label: label.to_string(),
+        target,
+        loaded,
+        plist_path: plist_meta.plist_path.clone(),
+        is_binary,
+        original: og_plist,
+        edited,
+        temp_path,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use xpc_sys::enums::SessionType;
+    use xpc_sys::service_target::ServiceTarget;
+
+    use super::{
+        entry_kind, read_validated, resolve_domain, session_type_from_plist, LaunchdEntryLocation,
+        LaunchdEntryType,
     };
+    use crate::launchd::job_spec::JobSpec;
+    use crate::launchd::test_util::scratch;
+
+    #[test]
+    fn domain_for_entry() {
+        use LaunchdEntryLocation::{Global, System, User};
+        use LaunchdEntryType::{Agent, Daemon};
+
+        use SessionType as S;
+
+        let (system, gui, user) = (
+            ServiceTarget::system(),
+            ServiceTarget::gui(501),
+            ServiceTarget::user(501),
+        );
+
+        let cases = [
+            (Daemon, System, S::Unknown, &system),
+            (Daemon, Global, S::Unknown, &system),
+            (Daemon, Global, S::Aqua, &system),
+            (Agent, System, S::Unknown, &gui),
+            (Agent, Global, S::Unknown, &gui),
+            (Agent, User, S::Unknown, &gui),
+            (Agent, Global, S::Aqua, &gui),
+            (Agent, User, S::Aqua, &gui),
+            (Agent, Global, S::LoginWindow, &gui),
+            (Agent, Global, S::Background, &user),
+            (Agent, User, S::Background, &user),
+            (Agent, System, S::StandardIO, &user),
+            (Agent, System, S::System, &system),
+            (Agent, Global, S::System, &system),
+            (Agent, User, S::System, &user),
+        ];
+
+        for (entry_type, location, session_type, target) in cases.iter() {
+            assert_eq!(
+                &resolve_domain(entry_type, location, session_type, 501, None),
+                *target,
+                "{} {} {}",
+                entry_type,
+                location,
+                session_type
+            );
+        }
+
+        assert_eq!(
+            resolve_domain(&Agent, &Global, &S::LoginWindow, 501, Some(100006)),
+            ServiceTarget::login(100006)
+        );
+    }
 
-    writer(&plist, &plist_meta.plist_path).map_err(|e| e.to_string())?;
+    #[test]
+    fn session_type_string_or_array() {
+        let string = plist::Value::String("Background".to_string());
+        let array = plist::Value::Array(vec![
+            plist::Value::String("LoginWindow".to_string()),
+            plist::Value::String("Aqua".to_string()),
+        ]);
+
+        assert_eq!(
+            session_type_from_plist(Some(&string)),
+            SessionType::Background
+        );
+        assert_eq!(session_type_from_plist(Some(&array)), SessionType::Aqua);
+        assert_eq!(session_type_from_plist(None), SessionType::Unknown);
+    }
 
-    Ok(())
+    #[test]
+    fn kind_from_path() {
+        assert_eq!(
+            entry_kind("/Library/LaunchDaemons/com.example.daemon.plist"),
+            Some((LaunchdEntryType::Daemon, LaunchdEntryLocation::Global))
+        );
+        assert_eq!(
+            entry_kind("/System/Library/LaunchAgents/com.apple.Finder.plist"),
+            Some((LaunchdEntryType::Agent, LaunchdEntryLocation::System))
+        );
+        assert_eq!(entry_kind("/Library/LaunchDaemonsBackup/x.plist"), None);
+        assert_eq!(entry_kind("/tmp/com.example.agent.plist"), None);
+    }
+
+    #[test]
+    fn keeps_key_order() {
+        // Not the order JobSpec::to_value writes, at the top and in KeepAlive
+        let mut keep_alive = plist::Dictionary::new();
+        keep_alive.insert("Crashed".to_string(), true.into());
+        keep_alive.insert("SuccessfulExit".to_string(), false.into());
+
+        let mut dict = plist::Dictionary::new();
+        dict.insert("com.example.custom".to_string(), "first".into());
+        dict.insert(
+            "KeepAlive".to_string(),
+            plist::Value::Dictionary(keep_alive),
+        );
+        dict.insert(
+            "ProgramArguments".to_string(),
+            vec!["/bin/true".into()].into(),
+        );
+        dict.insert("Label".to_string(), "com.example.order".into());
+
+        let mut saved = vec![];
+        plist::Value::Dictionary(dict)
+            .to_writer_xml(&mut saved)
+            .unwrap();
+
+        let dir = scratch("order");
+        let path = dir.join("com.example.order.plist");
+        std::fs::write(&path, &saved).unwrap();
+        let value = read_validated(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let mut written = vec![];
+        value.to_writer_xml(&mut written).unwrap();
+        assert_eq!(
+            String::from_utf8(written).unwrap(),
+            String::from_utf8(saved.clone()).unwrap()
+        );
+
+        let mut reordered = vec![];
+        JobSpec::from_value(&value)
+            .unwrap()
+            .to_value()
+            .to_writer_xml(&mut reordered)
+            .unwrap();
+        assert_ne!(reordered, saved);
+    }
 }
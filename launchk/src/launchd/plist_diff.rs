@@ -0,0 +1,198 @@
+use std::fmt;
+
+use plist::Value;
+
+/// What happened at a key path from one plist to the next
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Added(Value),
+    Removed(Value),
+    Changed(Value, Value),
+}
+
+/// A change at a key path, e.g. `KeepAlive.SuccessfulExit` or
+/// `ProgramArguments[1]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difference {
+    pub path: String,
+    pub change: Change,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.change {
+            Change::Added(new) => write!(f, "+ {} = {}", self.path, inline(new)),
+            Change::Removed(old) => write!(f, "- {} = {}", self.path, inline(old)),
+            Change::Changed(old, new) => {
+                write!(f, "~ {}: {} -> {}", self.path, inline(old), inline(new))
+            }
+        }
+    }
+}
+
+/// A value on one line
+fn inline(value: &Value) -> String {
+    match value {
+        Value::String(s) => format!("{:?}", s),
+        Value::Integer(i) => i.to_string(),
+        Value::Real(r) => r.to_string(),
+        Value::Boolean(b) => b.to_string(),
+        Value::Date(d) => d.to_xml_format(),
+        Value::Data(d) => format!("<{} bytes>", d.len()),
+        Value::Array(a) => format!(
+            "[{}]",
+            a.iter().map(inline).collect::<Vec<String>>().join(", ")
+        ),
+        Value::Dictionary(d) => format!(
+            "{{{}}}",
+            d.iter()
+                .map(|(k, v)| format!("{} = {}", k, inline(v)))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+        other => format!("{:?}", other),
+    }
+}
+
+/// Changes from `old` to `new` by key path, recursing into dictionaries
+/// and arrays. Keys are in the order of `old`, then the keys only in `new`.
+pub fn diff(old: &Value, new: &Value) -> Vec<Difference> {
+    let mut differences = vec![];
+    diff_at("", old, new, &mut differences);
+    differences
+}
+
+fn diff_at(path: &str, old: &Value, new: &Value, differences: &mut Vec<Difference>) {
+    match (old, new) {
+        (Value::Dictionary(old), Value::Dictionary(new)) => {
+            let key_path = |key: &str| match path {
+                "" => key.to_string(),
+                _ => format!("{}.{}", path, key),
+            };
+
+            for (key, old_value) in old {
+                match new.get(key) {
+                    Some(new_value) => diff_at(&key_path(key), old_value, new_value, differences),
+                    None => differences.push(Difference {
+                        path: key_path(key),
+                        change: Change::Removed(old_value.clone()),
+                    }),
+                }
+            }
+
+            for (key, new_value) in new.iter().filter(|(k, _)| !old.contains_key(k)) {
+                differences.push(Difference {
+                    path: key_path(key),
+                    change: Change::Added(new_value.clone()),
+                });
+            }
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            for i in 0..old.len().max(new.len()) {
+                let index_path = format!("{}[{}]", path, i);
+
+                match (old.get(i), new.get(i)) {
+                    (Some(o), Some(n)) => diff_at(&index_path, o, n, differences),
+                    (Some(o), None) => differences.push(Difference {
+                        path: index_path,
+                        change: Change::Removed(o.clone()),
+                    }),
+                    (None, Some(n)) => differences.push(Difference {
+                        path: index_path,
+                        change: Change::Added(n.clone()),
+                    }),
+                    (None, None) => {}
+                }
+            }
+        }
+        _ if old != new => differences.push(Difference {
+            path: path.to_string(),
+            change: Change::Changed(old.clone(), new.clone()),
+        }),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plist::{Dictionary, Value};
+
+    use super::{diff, Change, Difference};
+
+    fn dict(entries: Vec<(&str, Value)>) -> Value {
+        Value::Dictionary(
+            entries
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect::<Dictionary>(),
+        )
+    }
+
+    #[test]
+    fn key_paths() {
+        let old = dict(vec![
+            ("Label", "com.example.job".into()),
+            ("RunAtLoad", true.into()),
+            (
+                "ProgramArguments",
+                Value::Array(vec!["/bin/sh".into(), "-c".into(), "true".into()]),
+            ),
+            ("KeepAlive", dict(vec![("SuccessfulExit", false.into())])),
+        ]);
+        let new = dict(vec![
+            ("Label", "com.example.job".into()),
+            (
+                "ProgramArguments",
+                Value::Array(vec!["/bin/sh".into(), "-x".into()]),
+            ),
+            ("KeepAlive", dict(vec![("SuccessfulExit", true.into())])),
+            ("StartInterval", 60.into()),
+        ]);
+
+        let differences = diff(&old, &new);
+
+        assert_eq!(
+            differences,
+            vec![
+                Difference {
+                    path: "RunAtLoad".to_string(),
+                    change: Change::Removed(true.into()),
+                },
+                Difference {
+                    path: "ProgramArguments[1]".to_string(),
+                    change: Change::Changed("-c".into(), "-x".into()),
+                },
+                Difference {
+                    path: "ProgramArguments[2]".to_string(),
+                    change: Change::Removed("true".into()),
+                },
+                Difference {
+                    path: "KeepAlive.SuccessfulExit".to_string(),
+                    change: Change::Changed(false.into(), true.into()),
+                },
+                Difference {
+                    path: "StartInterval".to_string(),
+                    change: Change::Added(60.into()),
+                },
+            ]
+        );
+
+        let lines: Vec<String> = differences.iter().map(|d| d.to_string()).collect();
+        assert_eq!(lines[0], "- RunAtLoad = true");
+        assert_eq!(lines[1], "~ ProgramArguments[1]: \"-c\" -> \"-x\"");
+        assert_eq!(lines[4], "+ StartInterval = 60");
+
+        assert!(diff(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn type_change() {
+        let old = dict(vec![("KeepAlive", true.into())]);
+        let new = dict(vec![("KeepAlive", dict(vec![("Crashed", true.into())]))]);
+
+        assert_eq!(
+            diff(&old, &new)[0].to_string(),
+            "~ KeepAlive: true -> {Crashed = true}"
+        );
+    }
+}
@@ -0,0 +1,325 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::launchd::dumpstate::{parse_entries, StateBlock};
+
+/// A port name in the process, and what launchd knows about it
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct MachPort {
+    pub name: u32,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize)]
+pub struct MachInfo {
+    pub task_kernel: Option<MachPort>,
+    pub task_host: Option<MachPort>,
+    pub task_name: Option<MachPort>,
+    pub task_bootstrap: Option<MachPort>,
+}
+
+/// launchctl procinfo, see doc/launchctl_messages.md
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize)]
+pub struct ProcInfo {
+    /// Only printed by launchctl, not in the routine's shmem
+    pub program_path: Option<String>,
+    pub pid: Option<i64>,
+    pub arguments: Vec<String>,
+    pub environment: BTreeMap<String, String>,
+    /// Only printed by launchctl, not in the routine's shmem
+    pub mach_info: Option<MachInfo>,
+    pub responsible_pid: Option<i64>,
+    pub responsible_path: Option<String>,
+    pub spawn_type: Option<String>,
+    pub sandboxed: Option<bool>,
+    /// None if the process has none
+    pub entitlements: Option<BTreeMap<String, String>>,
+    /// None if the process is not signed
+    pub code_signing: Option<BTreeMap<String, String>>,
+    /// The rest of bsd proc info, e.g. ppid, uid and status
+    pub bsd_info: BTreeMap<String, String>,
+}
+
+/// Parse procinfo output. Anything missing is left empty.
+pub fn parse_procinfo(text: &str) -> ProcInfo {
+    let root = parse_entries(&brace_sections(text));
+    let get = |key: &str| root.get(key).map(str::to_string);
+
+    let arguments = root
+        .child("argument vector")
+        .map(|a| a.properties().map(|(_, v)| v.to_string()).collect())
+        .unwrap_or_default();
+
+    let mach_info = root.child("mach info").map(|m| {
+        let port = |task: &str| m.get(&format!("task-{} port", task)).and_then(parse_port);
+
+        MachInfo {
+            task_kernel: port("kernel"),
+            task_host: port("host"),
+            task_name: port("name"),
+            task_bootstrap: port("bootstrap"),
+        }
+    });
+
+    let bsd_info = properties(root.child("bsd proc info")).unwrap_or_default();
+
+    ProcInfo {
+        program_path: get("program path"),
+        pid: bsd_info.get("pid").and_then(|p| p.parse().ok()),
+        arguments,
+        environment: properties(root.child("environment vector")).unwrap_or_default(),
+        mach_info,
+        responsible_pid: root.get("responsible pid").and_then(|p| p.parse().ok()),
+        responsible_path: get("responsible path"),
+        spawn_type: get("spawn type"),
+        sandboxed: root.get("sandboxed").map(|s| s == "yes"),
+        entitlements: properties(root.child("entitlements")),
+        code_signing: properties(root.child("code signing info")),
+        bsd_info,
+    }
+}
+
+fn properties(block: Option<&StateBlock>) -> Option<BTreeMap<String, String>> {
+    block.map(|b| {
+        b.properties()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    })
+}
+
+/// `0xd07 (unknown)`
+fn parse_port(value: &str) -> Option<MachPort> {
+    let (name, description) = value.split_once(' ').unwrap_or((value, ""));
+    let name = u32::from_str_radix(name.trim_start_matches("0x"), 16).ok()?;
+
+    Some(MachPort {
+        name,
+        description: description.trim().to_string(),
+    })
+}
+
+/// `audit info` has no braces, its lines are indented instead.
+/// Give such sections braces so they parse like the others.
+fn brace_sections(text: &str) -> String {
+    let mut braced = String::new();
+    let mut depth = 0usize;
+    let mut in_section = false;
+    let mut lines = text.lines().peekable();
+
+    while let Some(line) = lines.next() {
+        if in_section && !line.starts_with('\t') {
+            braced.push_str("}\n");
+            in_section = false;
+        }
+
+        let trimmed = line.trim();
+        let next_indented = lines.peek().is_some_and(|l| l.starts_with('\t'));
+
+        if depth == 0
+            && !in_section
+            && next_indented
+            && !trimmed.is_empty()
+            && !trimmed.ends_with('{')
+            && !trimmed.contains(" = ")
+        {
+            braced.push_str(&format!("{} = {{\n", trimmed));
+            in_section = true;
+            continue;
+        }
+
+        if trimmed.ends_with('{') {
+            depth += 1;
+        } else if trimmed == "}" {
+            depth = depth.saturating_sub(1);
+        }
+
+        braced.push_str(line);
+        braced.push('\n');
+    }
+
+    if in_section {
+        braced.push_str("}\n");
+    }
+
+    braced
+}
+
+impl fmt::Display for ProcInfo {
+    /// Summary for the procinfo panel
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let number = |n: Option<i64>| n.map(|n| n.to_string());
+        let rows = [
+            ("Program", self.program_path.clone()),
+            ("PID", number(self.pid)),
+            ("Responsible PID", number(self.responsible_pid)),
+            ("Responsible path", self.responsible_path.clone()),
+            ("Spawn type", self.spawn_type.clone()),
+            ("Sandboxed", self.sandboxed.map(|s| s.to_string())),
+        ];
+
+        for (title, value) in rows.iter() {
+            writeln!(f, "{:<18}{}", title, value.as_deref().unwrap_or("-"))?;
+        }
+
+        writeln!(f, "\nArguments")?;
+        for arg in &self.arguments {
+            writeln!(f, "  {}", arg)?;
+        }
+
+        writeln!(f, "\nEnvironment")?;
+        for (k, v) in &self.environment {
+            writeln!(f, "  {}={}", k, v)?;
+        }
+
+        if let Some(mach_info) = &self.mach_info {
+            writeln!(f, "\nMach ports")?;
+
+            let ports = [
+                ("task-kernel", &mach_info.task_kernel),
+                ("task-host", &mach_info.task_host),
+                ("task-name", &mach_info.task_name),
+                ("task-bootstrap", &mach_info.task_bootstrap),
+            ];
+
+            for (name, port) in ports.iter() {
+                if let Some(port) = port {
+                    writeln!(f, "  {:<16}{:#x} {}", name, port.name, port.description)?;
+                }
+            }
+        }
+
+        for (title, map) in [
+            ("Entitlements", &self.entitlements),
+            ("Code signing", &self.code_signing),
+        ]
+        .iter()
+        {
+            match map {
+                Some(map) => {
+                    writeln!(f, "\n{}", title)?;
+                    for (k, v) in map.iter() {
+                        writeln!(f, "  {} = {}", k, v)?;
+                    }
+                }
+                None => writeln!(f, "\n{:<18}-", title)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::launchd::dumpstate::parse_entries;
+
+    use super::{brace_sections, parse_procinfo, MachPort};
+
+    const PROCINFO: &str = include_str!("../../tests/fixtures/procinfo.txt");
+
+    #[test]
+    fn doc_procinfo() {
+        let info = parse_procinfo(PROCINFO);
+
+        assert_eq!(
+            info.program_path.as_deref(),
+            Some("/usr/local/Cellar/redis/6.2.1/bin/redis-server")
+        );
+        assert_eq!(info.pid, Some(7475));
+        assert_eq!(info.responsible_pid, Some(7475));
+        assert_eq!(info.sandboxed, Some(false));
+        assert_eq!(info.arguments.len(), 3);
+        assert_eq!(
+            info.arguments[0],
+            "/usr/local/opt/redis/bin/redis-server 127.0.0.1:6379"
+        );
+        assert_eq!(
+            info.environment.get("HOME").map(String::as_str),
+            Some("/Users/mach")
+        );
+        assert_eq!(
+            info.bsd_info.get("status").map(String::as_str),
+            Some("stopped")
+        );
+        assert_eq!(info.entitlements, None);
+        assert_eq!(info.code_signing, None);
+        assert_eq!(info.spawn_type, None);
+
+        let mach_info = info.mach_info.unwrap();
+        assert_eq!(
+            mach_info.task_kernel,
+            Some(MachPort {
+                name: 0xd07,
+                description: "(unknown)".to_string()
+            })
+        );
+        assert_eq!(mach_info.task_bootstrap.map(|p| p.name), Some(0x1403));
+    }
+
+    #[test]
+    fn indented_section() {
+        let root = parse_entries(&brace_sections(PROCINFO));
+        let audit_info = root.child("audit info").unwrap();
+
+        assert_eq!(audit_info.get("session id"), Some("100006"));
+        assert_eq!(root.get("flags"), None);
+        assert_eq!(root.get("sandboxed"), Some("no"));
+    }
+
+    #[test]
+    fn shmem_only_with_signing() {
+        let info = parse_procinfo(
+            r#"argument count = 1
+argument vector = {
+	[0] = /usr/libexec/lskdd
+}
+environment vector = {
+}
+bsd proc info = {
+	pid = 612
+}
+responsible pid = 612
+spawn type = daemon (3)
+
+entitlements = {
+	"com.apple.private.security.storage.lskdd" => true
+}
+
+code signing info = {
+	identifier = com.apple.lskdd
+	team id = (none)
+	platform binary = 1
+}
+"#,
+        );
+
+        assert_eq!(info.program_path, None);
+        assert_eq!(info.mach_info, None);
+        assert_eq!(info.spawn_type.as_deref(), Some("daemon (3)"));
+        assert_eq!(
+            info.entitlements
+                .unwrap()
+                .get("com.apple.private.security.storage.lskdd")
+                .map(String::as_str),
+            Some("true")
+        );
+        assert_eq!(
+            info.code_signing
+                .unwrap()
+                .get("identifier")
+                .map(String::as_str),
+            Some("com.apple.lskdd")
+        );
+    }
+
+    #[test]
+    fn json() {
+        let json = serde_json::to_value(parse_procinfo(PROCINFO)).unwrap();
+
+        assert_eq!(json["pid"], 7475);
+        assert_eq!(json["mach_info"]["task_host"]["name"], 0x1203);
+        assert_eq!(json["entitlements"], serde_json::Value::Null);
+    }
+}
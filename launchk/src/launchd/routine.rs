@@ -0,0 +1,314 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use serde::de::DeserializeOwned;
+
+use xpc_sys::api::transport::XPCTransport;
+use xpc_sys::object::xpc_error::XPCError;
+use xpc_sys::object::xpc_error::XPCError::ValueError;
+use xpc_sys::object::xpc_value::{XPCValue, XPCValueMap};
+use xpc_sys::xpc_serde::from_xpc_value;
+
+use crate::launchd::error::LaunchdError;
+
+/// Routines launchd answers on the bootstrap pipe, see doc/launchctl_messages.md
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum LaunchdRoutine {
+    Kickstart,
+    Blame,
+    ProcInfo,
+    /// Shares its number with ProcInfo, told apart by the request
+    Print,
+    Bootstrap,
+    Bootout,
+    PrintCache,
+    Enable,
+    Disable,
+    Kill,
+    List,
+    ProcInfoPort,
+    ReadDisabled,
+    /// Shares its number with ReadDisabled, whose output is part of it
+    PrintDomain,
+    DumpState,
+    DumpJPCategory,
+}
+
+/// What we know about a routine's messages
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct RoutineSpec {
+    pub number: u64,
+    /// Sent by _xpc_pipe_interface_routine, not part of our request
+    pub subsystem: u64,
+    /// Keys that must be in the request
+    pub request_keys: &'static [&'static str],
+    /// Keys a successful reply has
+    pub reply_keys: &'static [&'static str],
+}
+
+const DOMAIN: &[&str] = &["handle", "type"];
+const SERVICE: &[&str] = &["name", "handle", "type"];
+const SHMEM: &[&str] = &["shmem", "handle", "type"];
+const NAMED_FD: &[&str] = &["name", "fd", "handle", "type"];
+const BYTES_WRITTEN: &[&str] = &["bytes-written"];
+
+impl LaunchdRoutine {
+    pub const ALL: [LaunchdRoutine; 16] = [
+        LaunchdRoutine::Kickstart,
+        LaunchdRoutine::Blame,
+        LaunchdRoutine::ProcInfo,
+        LaunchdRoutine::Print,
+        LaunchdRoutine::Bootstrap,
+        LaunchdRoutine::Bootout,
+        LaunchdRoutine::PrintCache,
+        LaunchdRoutine::Enable,
+        LaunchdRoutine::Disable,
+        LaunchdRoutine::Kill,
+        LaunchdRoutine::List,
+        LaunchdRoutine::ProcInfoPort,
+        LaunchdRoutine::ReadDisabled,
+        LaunchdRoutine::PrintDomain,
+        LaunchdRoutine::DumpState,
+        LaunchdRoutine::DumpJPCategory,
+    ];
+
+    pub fn spec(self) -> RoutineSpec {
+        let (number, subsystem, request_keys, reply_keys) = match self {
+            LaunchdRoutine::Kickstart => (702, 2, SERVICE, &[][..]),
+            LaunchdRoutine::Blame => (707, 2, SERVICE, &["reason"][..]),
+            LaunchdRoutine::ProcInfo => (708, 2, &["pid", "shmem"][..], BYTES_WRITTEN),
+            // Prints into fd, like launchctl print does to its tty
+            LaunchdRoutine::Print => (708, 2, NAMED_FD, &[][..]),
+            LaunchdRoutine::Bootstrap => (800, 3, &["paths", "handle", "type"][..], &[][..]),
+            LaunchdRoutine::Bootout => (801, 3, DOMAIN, &[][..]),
+            // handle is the PID
+            LaunchdRoutine::PrintCache => (803, 3, DOMAIN, &[][..]),
+            LaunchdRoutine::Enable => (808, 3, &["names", "handle", "type"][..], &[][..]),
+            LaunchdRoutine::Disable => (809, 3, &["names", "handle", "type"][..], &[][..]),
+            LaunchdRoutine::Kill => (812, 3, &["name", "signal", "handle", "type"][..], &[][..]),
+            LaunchdRoutine::List => (815, 3, DOMAIN, &[][..]),
+            // name is a port name in process
+            LaunchdRoutine::ProcInfoPort => {
+                (822, 3, &["process", "name", "handle", "type"][..], &[][..])
+            }
+            LaunchdRoutine::ReadDisabled => (828, 3, SHMEM, BYTES_WRITTEN),
+            LaunchdRoutine::PrintDomain => (828, 3, SHMEM, BYTES_WRITTEN),
+            LaunchdRoutine::DumpState => (834, 3, SHMEM, BYTES_WRITTEN),
+            LaunchdRoutine::DumpJPCategory => (837, 3, SHMEM, BYTES_WRITTEN),
+        };
+
+        RoutineSpec {
+            number,
+            subsystem,
+            request_keys,
+            reply_keys,
+        }
+    }
+
+    pub fn number(self) -> u64 {
+        self.spec().number
+    }
+
+    /// Check the request has every required key
+    pub fn validate(self, request: &XPCValueMap) -> Result<(), XPCError> {
+        match self
+            .spec()
+            .request_keys
+            .iter()
+            .find(|k| !request.contains_key(**k))
+        {
+            Some(key) => Err(ValueError(format!("{} request missing {}", self, key))),
+            None => Ok(()),
+        }
+    }
+
+    /// Validate and send the request, then check the reply for errors
+    /// and expected keys
+    pub fn call(
+        self,
+        transport: &dyn XPCTransport,
+        request: &mut XPCValueMap,
+    ) -> Result<XPCValue, LaunchdError> {
+        self.validate(request)
+            .map_err(|e| LaunchdError::from_xpc(self, request, e))?;
+
+        let reply = transport
+            .pipe_interface_routine(self.number(), request)
+            .map_err(|e| LaunchdError::from_xpc(self, request, e))?;
+
+        if let Some((target, code)) = reply_error(&reply) {
+            let mut err = LaunchdError::from_code(self, request, code);
+            err.target = target.or(err.target);
+            return Err(err);
+        }
+
+        match self
+            .spec()
+            .reply_keys
+            .iter()
+            .find(|k| reply.get(k).is_none())
+        {
+            Some(key) => Err(LaunchdError::from_xpc(
+                self,
+                request,
+                ValueError(format!("{} reply missing {}", self, key)),
+            )),
+            None => Ok(reply),
+        }
+    }
+
+    /// call, then deserialize the reply
+    pub fn call_as<T: DeserializeOwned>(
+        self,
+        transport: &dyn XPCTransport,
+        request: &mut XPCValueMap,
+    ) -> Result<T, LaunchdError> {
+        let reply = self.call(transport, request)?;
+        from_xpc_value(&reply).map_err(|e| LaunchdError::from_xpc(self, request, e.into()))
+    }
+}
+
+/// The "error" code, or the first of "errors" and the label it is for
+fn reply_error(reply: &XPCValue) -> Option<(Option<String>, i64)> {
+    if let Some(code) = reply.get("error").and_then(|e| e.as_i64()) {
+        return Some((None, code));
+    }
+
+    let mut errors: Vec<(&String, i64)> = reply
+        .get("errors")
+        .and_then(|e| e.as_dictionary())?
+        .iter()
+        .filter_map(|(label, e)| e.as_i64().map(|code| (label, code)))
+        .collect();
+
+    errors.sort();
+    errors
+        .first()
+        .map(|(label, code)| (Some(label.to_string()), *code))
+}
+
+/// The first routine with the number, e.g. ProcInfo for 708 and
+/// ReadDisabled for 828
+impl TryFrom<u64> for LaunchdRoutine {
+    type Error = XPCError;
+
+    fn try_from(number: u64) -> Result<Self, Self::Error> {
+        LaunchdRoutine::ALL
+            .iter()
+            .copied()
+            .find(|r| r.number() == number)
+            .ok_or_else(|| ValueError(format!("Unknown routine {}", number)))
+    }
+}
+
+impl fmt::Display for LaunchdRoutine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} ({})", self, self.number())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use xpc_sys::api::dict_builder::DictBuilder;
+    use xpc_sys::api::transport::XPCTransport;
+    use xpc_sys::object::xpc_error::XPCError;
+    use xpc_sys::object::xpc_value::{XPCValue, XPCValueMap};
+
+    use crate::launchd::error::LaunchdErrorKind;
+
+    use super::LaunchdRoutine;
+
+    /// Replies with an empty dictionary, or fails if it is called at all
+    struct Empty(bool);
+
+    impl XPCTransport for Empty {
+        fn pipe_interface_routine(
+            &self,
+            _routine: u64,
+            _request: &mut XPCValueMap,
+        ) -> Result<XPCValue, XPCError> {
+            assert!(self.0, "Must not send");
+            Ok(XPCValue::Dictionary(XPCValueMap::new()))
+        }
+    }
+
+    #[test]
+    fn numbers_round_trip() {
+        for routine in LaunchdRoutine::ALL.iter() {
+            assert_eq!(
+                LaunchdRoutine::try_from(routine.number()).map(|r| r.number()),
+                Ok(routine.number())
+            );
+            assert_eq!(routine.spec().subsystem, routine.number() / 100 - 5);
+        }
+
+        assert_eq!(LaunchdRoutine::try_from(708), Ok(LaunchdRoutine::ProcInfo));
+        assert_eq!(
+            LaunchdRoutine::try_from(828),
+            Ok(LaunchdRoutine::ReadDisabled)
+        );
+        assert!(LaunchdRoutine::try_from(999).is_err());
+    }
+
+    #[test]
+    fn invalid_request_is_not_sent() {
+        let mut request = XPCValueMap::new().entry("name", "com.apple.lskdd");
+
+        assert_eq!(
+            LaunchdRoutine::Blame
+                .call(&Empty(false), &mut request)
+                .map_err(|e| e.kind),
+            Err(LaunchdErrorKind::Xpc(XPCError::ValueError(
+                "Blame (707) request missing handle".to_string()
+            )))
+        );
+    }
+
+    #[test]
+    fn errors_dict_names_target() {
+        struct Disabled;
+
+        impl XPCTransport for Disabled {
+            fn pipe_interface_routine(
+                &self,
+                _routine: u64,
+                _request: &mut XPCValueMap,
+            ) -> Result<XPCValue, XPCError> {
+                let errors = XPCValueMap::new().entry("com.example.agent", XPCValue::Int64(119));
+                Ok(XPCValueMap::new().entry("errors", errors).into())
+            }
+        }
+
+        let mut request = XPCValueMap::new()
+            .entry("names", vec!["com.example.agent"])
+            .entry("handle", 0u64)
+            .entry("type", 1u64);
+
+        let err = LaunchdRoutine::Enable
+            .call(&Disabled, &mut request)
+            .unwrap_err();
+
+        assert_eq!(err.kind, LaunchdErrorKind::Disabled);
+        assert_eq!(err.code, Some(119));
+        assert_eq!(err.target.as_deref(), Some("com.example.agent"));
+    }
+
+    #[test]
+    fn reply_keys_checked() {
+        let mut request = XPCValueMap::new()
+            .entry("shmem", XPCValue::shmem(16))
+            .entry("handle", 0u64)
+            .entry("type", 1u64);
+
+        assert_eq!(
+            LaunchdRoutine::DumpState
+                .call(&Empty(true), &mut request)
+                .map_err(|e| e.kind),
+            Err(LaunchdErrorKind::Xpc(XPCError::ValueError(
+                "DumpState (834) reply missing bytes-written".to_string()
+            )))
+        );
+    }
+}
@@ -0,0 +1,258 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::launchd::dumpstate::{parse_blocks, StateBlock, StateEntry};
+
+/// A MachServices endpoint of the service
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Endpoint {
+    pub name: String,
+    pub port: Option<u32>,
+    pub active: bool,
+}
+
+/// A LaunchEvents trigger of the service
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct EventTrigger {
+    pub name: String,
+    pub stream: Option<String>,
+    pub descriptor: BTreeMap<String, String>,
+}
+
+/// launchctl print for a service
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ServiceInfo {
+    /// As launchd printed it, e.g. `gui/501/com.apple.usernoted`
+    pub name: String,
+    pub path: Option<String>,
+    pub state: Option<String>,
+    pub program: Option<String>,
+    pub arguments: Vec<String>,
+    pub pid: Option<i64>,
+    pub runs: Option<u64>,
+    /// None if it never exited
+    pub last_exit_code: Option<i64>,
+    /// e.g. `EX_CONFIG`, `never exited` or the terminating signal
+    pub last_exit_reason: Option<String>,
+    pub spawn_type: Option<String>,
+    pub endpoints: Vec<Endpoint>,
+    pub event_triggers: Vec<EventTrigger>,
+    /// e.g. `keepalive` and `runatload`
+    pub properties: Vec<String>,
+}
+
+/// Parse print output for a service. Anything missing is left empty.
+pub fn parse_service_info(text: &str) -> ServiceInfo {
+    let block = parse_blocks(text).into_iter().next().unwrap_or_default();
+    let get = |key: &str| block.get(key).map(str::to_string);
+
+    let arguments = block
+        .child("arguments")
+        .map(|a| {
+            a.entries
+                .iter()
+                .filter_map(|e| match e {
+                    StateEntry::Item(arg) => Some(arg.clone()),
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let (last_exit_code, mut last_exit_reason) = block
+        .get("last exit code")
+        .map(parse_exit_code)
+        .unwrap_or_default();
+
+    if let Some(signal) = block.get("last terminating signal") {
+        last_exit_reason = Some(signal.to_string());
+    }
+
+    ServiceInfo {
+        name: block.name.clone(),
+        path: get("path"),
+        state: get("state"),
+        program: get("program"),
+        arguments,
+        pid: block.get("pid").and_then(|p| p.parse().ok()),
+        runs: block.get("runs").and_then(|r| r.parse().ok()),
+        last_exit_code,
+        last_exit_reason,
+        spawn_type: get("spawn type"),
+        endpoints: children(&block, "endpoints").map(endpoint).collect(),
+        event_triggers: children(&block, "event triggers")
+            .map(event_trigger)
+            .collect(),
+        properties: block
+            .get("properties")
+            .map(|p| p.split(" | ").map(str::to_string).collect())
+            .unwrap_or_default(),
+    }
+}
+
+/// The blocks nested in the block called name
+fn children<'a>(block: &'a StateBlock, name: &str) -> impl Iterator<Item = &'a StateBlock> {
+    block
+        .child(name)
+        .into_iter()
+        .flat_map(|c| c.entries.iter())
+        .filter_map(|e| match e {
+            StateEntry::Block(b) => Some(b),
+            _ => None,
+        })
+}
+
+fn endpoint(block: &StateBlock) -> Endpoint {
+    Endpoint {
+        name: block.name.clone(),
+        port: block
+            .get("port")
+            .and_then(|p| u32::from_str_radix(p.trim_start_matches("0x"), 16).ok()),
+        active: block.get("active") == Some("1"),
+    }
+}
+
+fn event_trigger(block: &StateBlock) -> EventTrigger {
+    EventTrigger {
+        name: block.name.clone(),
+        stream: block.get("stream").map(str::to_string),
+        descriptor: block
+            .child("descriptor")
+            .map(|d| {
+                d.properties()
+                    .map(|(k, v)| (k.to_string(), v.trim_matches('"').to_string()))
+                    .collect()
+            })
+            .unwrap_or_default(),
+    }
+}
+
+/// `78: EX_CONFIG`, `0` or `(never exited)`
+fn parse_exit_code(value: &str) -> (Option<i64>, Option<String>) {
+    let (code, reason) = value.split_once(':').unwrap_or((value, ""));
+
+    match code.trim().parse() {
+        Ok(code) => (
+            Some(code),
+            Some(reason.trim().to_string()).filter(|r| !r.is_empty()),
+        ),
+        Err(_) => (
+            None,
+            Some(value.trim_matches(|c| c == '(' || c == ')').to_string()),
+        ),
+    }
+}
+
+impl fmt::Display for ServiceInfo {
+    /// Summary for the print panel
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let number = |n: Option<i64>| n.map(|n| n.to_string());
+        let last_exit = match (self.last_exit_code, &self.last_exit_reason) {
+            (Some(code), Some(reason)) => Some(format!("{} ({})", code, reason)),
+            (Some(code), None) => Some(code.to_string()),
+            (None, reason) => reason.clone(),
+        };
+
+        let rows = [
+            ("State", self.state.clone()),
+            ("Path", self.path.clone()),
+            ("Program", self.program.clone()),
+            ("PID", number(self.pid)),
+            ("Runs", self.runs.map(|r| r.to_string())),
+            ("Last exit", last_exit),
+            ("Spawn type", self.spawn_type.clone()),
+            ("Properties", Some(self.properties.join(", "))),
+        ];
+
+        for (title, value) in rows.iter() {
+            writeln!(f, "{:<18}{}", title, value.as_deref().unwrap_or("-"))?;
+        }
+
+        writeln!(f, "\nArguments")?;
+        for arg in &self.arguments {
+            writeln!(f, "  {}", arg)?;
+        }
+
+        writeln!(f, "\nEndpoints")?;
+        for endpoint in &self.endpoints {
+            let port = endpoint
+                .port
+                .map(|p| format!("{:#x}", p))
+                .unwrap_or("-".to_string());
+            let active = if endpoint.active { "active" } else { "" };
+
+            writeln!(f, "  {:<10}{:<8}{}", port, active, endpoint.name)?;
+        }
+
+        writeln!(f, "\nEvent triggers")?;
+        for trigger in &self.event_triggers {
+            writeln!(
+                f,
+                "  {} ({})",
+                trigger.name,
+                trigger.stream.as_deref().unwrap_or("-")
+            )?;
+            for (k, v) in &trigger.descriptor {
+                writeln!(f, "    {} = {}", k, v)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_exit_code, parse_service_info};
+
+    const PRINT: &str = include_str!("../../tests/fixtures/print.txt");
+
+    #[test]
+    fn running_agent() {
+        let info = parse_service_info(PRINT);
+
+        assert_eq!(info.name, "gui/501/com.apple.usernoted");
+        assert_eq!(info.state.as_deref(), Some("running"));
+        assert_eq!(info.program.as_deref(), Some("/usr/sbin/usernoted"));
+        assert_eq!(info.arguments, vec!["/usr/sbin/usernoted"]);
+        assert_eq!(info.pid, Some(640));
+        assert_eq!(info.runs, Some(3));
+        assert_eq!(info.last_exit_code, Some(0));
+        assert_eq!(info.last_exit_reason, None);
+        assert_eq!(info.spawn_type.as_deref(), Some("interactive (4)"));
+        assert!(info.properties.contains(&"keepalive".to_string()));
+
+        let ports: Vec<(Option<u32>, bool)> =
+            info.endpoints.iter().map(|e| (e.port, e.active)).collect();
+        assert_eq!(ports, vec![(Some(0x7a03), true), (Some(0x7b03), false)]);
+
+        let trigger = &info.event_triggers[0];
+        assert_eq!(trigger.name, "com.apple.usernoted.notifyd");
+        assert_eq!(
+            trigger.stream.as_deref(),
+            Some("com.apple.notifyd.matching")
+        );
+        assert_eq!(
+            trigger.descriptor.get("Notification").map(String::as_str),
+            Some("com.apple.usernoted.wake")
+        );
+    }
+
+    #[test]
+    fn exit_codes() {
+        assert_eq!(
+            parse_exit_code("78: EX_CONFIG"),
+            (Some(78), Some("EX_CONFIG".to_string()))
+        );
+        assert_eq!(
+            parse_exit_code("(never exited)"),
+            (None, Some("never exited".to_string()))
+        );
+
+        let info = parse_service_info(
+            "com.example.crash = {\n\tlast exit code = 1\n\tlast terminating signal = Abort trap: 6\n}",
+        );
+        assert_eq!(info.last_exit_code, Some(1));
+        assert_eq!(info.last_exit_reason.as_deref(), Some("Abort trap: 6"));
+    }
+}
@@ -0,0 +1,9 @@
+use std::fs;
+use std::path::PathBuf;
+
+/// A fresh directory in $TMPDIR for this test run, remove it when done
+pub fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("launchk-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -12,17 +12,87 @@ extern crate plist;
 use cursive::view::Resizable;
 use cursive::views::{NamedView, Panel};
 use git_version::git_version;
+use std::env;
+use std::path::Path;
 use std::process::exit;
+use std::sync::Arc;
+#[cfg(target_os = "macos")]
+use xpc_sys::api::transport::{BootstrapPipeTransport, RecordingTransport};
+use xpc_sys::api::transport::{ReplayTransport, XPCTransport};
 
+use crate::launchd::lint::{lint_file, Severity, RULES};
 use crate::launchd::plist::{init_plist_map, PLIST_MAP_INIT};
 use crate::tui::root::RootLayout;
 
 mod launchd;
 mod tui;
 
+/// Talk to launchd, unless LAUNCHK_REPLAY points at a recording to serve
+/// replies from. LAUNCHK_RECORD records every routine sent to launchd.
+#[cfg(target_os = "macos")]
+fn make_transport() -> Arc<dyn XPCTransport> {
+    if let Ok(path) = env::var("LAUNCHK_REPLAY") {
+        return Arc::new(ReplayTransport::open(path).expect("Must read LAUNCHK_REPLAY"));
+    }
+
+    let live: Arc<dyn XPCTransport> = Arc::new(BootstrapPipeTransport);
+
+    match env::var("LAUNCHK_RECORD") {
+        Ok(path) => {
+            Arc::new(RecordingTransport::create(live, path).expect("Must create LAUNCHK_RECORD"))
+        }
+        Err(_) => live,
+    }
+}
+
+/// There is no launchd to talk to, so only recordings can be browsed
+#[cfg(not(target_os = "macos"))]
+fn make_transport() -> Arc<dyn XPCTransport> {
+    let path = env::var("LAUNCHK_REPLAY").expect("LAUNCHK_REPLAY is required off macOS");
+    Arc::new(ReplayTransport::open(path).expect("Must read LAUNCHK_REPLAY"))
+}
+
+/// launchk lint <plist>...: print the findings for each plist, failing if
+/// any has an error. Without plists, print the rules.
+fn lint_cli(paths: &[String]) -> i32 {
+    if paths.is_empty() {
+        for rule in RULES.iter() {
+            println!("{:<28}{:<9}{}", rule.id, rule.severity, rule.description);
+        }
+        return 0;
+    }
+
+    let mut status = 0;
+
+    for path in paths {
+        match lint_file(Path::new(path)) {
+            Ok(findings) => {
+                for finding in &findings {
+                    println!("{}: {}", path, finding);
+                }
+
+                if findings.iter().any(|f| f.severity == Severity::Error) {
+                    status = 1;
+                }
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                status = 1;
+            }
+        }
+    }
+
+    status
+}
+
 fn main() {
     env_logger::init();
 
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("lint") {
+        exit(lint_cli(&args[1..]));
+    }
+
     let runtime = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
@@ -35,7 +105,7 @@ fn main() {
     siv.load_toml(include_str!("tui/style.toml"))
         .expect("Must load styles");
 
-    let root_layout = RootLayout::new(&mut siv, runtime.handle());
+    let root_layout = RootLayout::new(&mut siv, runtime.handle(), make_transport());
     let root_layout = NamedView::new("root_layout", root_layout);
 
     let panel = Panel::new(root_layout)
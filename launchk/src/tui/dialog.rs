@@ -1,16 +1,32 @@
+use std::collections::HashSet;
+use std::fs::DirBuilder;
+use std::io::Write;
+use std::os::unix::fs::DirBuilderExt;
 use std::sync::mpsc::Sender;
 
+use cursive::traits::{Nameable, Resizable, Scrollable};
 use cursive::Cursive;
 use cursive::{
     view::Margins,
-    views::{Dialog, TextView},
+    views::{Checkbox, Dialog, LinearLayout, SelectView, TextView},
 };
 
+use crate::launchd::atomic_write::create_temp;
+use crate::launchd::command::SIGNALS;
+use crate::launchd::history::{History, JournalEntry};
+use crate::launchd::jetsam::parse_dumpjpcategory;
+use crate::launchd::lint::Finding;
+use crate::launchd::plist_diff::Difference;
+use crate::launchd::procinfo::{parse_procinfo, ProcInfo};
+use crate::launchd::service_info::parse_service_info;
+use crate::tui::jetsam_list::view::JetsamListView;
 use crate::tui::omnibox::command::OmniboxCommand;
 use crate::tui::omnibox::command::OMNIBOX_COMMANDS;
 use crate::tui::omnibox::view::OmniboxEvent;
+use crate::tui::pager::show_pager;
 use crate::tui::root::CbSinkMessage;
-use xpc_sys::csr::{csr_check, CsrConfig};
+use crate::tui::sysinfo::csr_allows;
+use xpc_sys::csr::CsrConfig;
 
 /// XPC "error" key can be present with no failure..."notice"?
 pub fn show_notice(msg: String, title: Option<String>) -> CbSinkMessage {
@@ -56,7 +72,7 @@ pub fn show_csr_info() -> CbSinkMessage {
     let csr_flags = (0..11)
         .map(|s| {
             let mask = CsrConfig::from_bits(1 << s).expect("Must be in CsrConfig");
-            format!("{:?}: {}", mask, unsafe { csr_check(mask.bits()) } == 0)
+            format!("{:?}: {}", mask, csr_allows(&mask))
         })
         .collect::<Vec<String>>();
 
@@ -93,3 +109,247 @@ pub fn show_help() -> CbSinkMessage {
         )
     })
 }
+
+/// Write procinfo as JSON to a new file in the state dir, returning what happened
+fn export_procinfo(info: &ProcInfo) -> String {
+    let history = History::user();
+    let prefix = format!("procinfo-{}.json", info.pid.unwrap_or_default());
+
+    serde_json::to_string_pretty(info)
+        .map_err(|e| e.to_string())
+        .and_then(|json| {
+            // Not in $TMPDIR, where the name could be taken before us
+            DirBuilder::new()
+                .recursive(true)
+                .mode(0o700)
+                .create(history.dir())
+                .and_then(|()| create_temp(history.dir(), &prefix))
+                .and_then(|(path, mut file)| file.write_all(json.as_bytes()).map(|()| path))
+                .map_err(|e| e.to_string())
+        })
+        .map(|path| format!("Wrote {}", path.display()))
+        .unwrap_or_else(|e| format!("Cannot export procinfo: {}", e))
+}
+
+/// Lint findings for a plist, errors first
+pub fn show_lint(name: String, findings: Vec<Finding>) -> CbSinkMessage {
+    let text = if findings.is_empty() {
+        "No findings".to_string()
+    } else {
+        findings
+            .iter()
+            .map(|f| f.to_string())
+            .collect::<Vec<String>>()
+            .join("\n\n")
+    };
+
+    Box::new(move |siv| {
+        siv.add_layer(
+            Dialog::around(TextView::new(text).scrollable())
+                .title(format!("lint {}", name))
+                .dismiss_button("OK"),
+        )
+    })
+}
+
+/// Preview an edit before it is written back
+pub fn show_edit_preview(
+    tx: Sender<OmniboxEvent>,
+    name: String,
+    differences: Vec<Difference>,
+    reload: bool,
+) -> CbSinkMessage {
+    let text = if differences.is_empty() {
+        "No changes".to_string()
+    } else {
+        differences
+            .iter()
+            .map(|d| d.to_string())
+            .collect::<Vec<String>>()
+            .join("\n")
+    };
+
+    Box::new(move |siv| {
+        let (apply_tx, again_tx, discard_tx) = (tx.clone(), tx.clone(), tx.clone());
+
+        let reload_checkbox = LinearLayout::horizontal()
+            .child(
+                Checkbox::new()
+                    .with_checked(reload)
+                    .with_name("reload_after_edit"),
+            )
+            .child(TextView::new(" Bootout and bootstrap after applying"));
+
+        let content = LinearLayout::vertical()
+            .child(TextView::new(text).scrollable().max_height(20))
+            .child(TextView::new(" "))
+            .child(reload_checkbox);
+
+        siv.add_layer(
+            Dialog::around(content)
+                .title(format!("Changes to {}", name))
+                .button("Apply", move |s| {
+                    let reload = s
+                        .call_on_name("reload_after_edit", |c: &mut Checkbox| c.is_checked())
+                        .unwrap_or(false);
+                    apply_tx
+                        .send(OmniboxEvent::Command(OmniboxCommand::ApplyEdit(reload)))
+                        .expect("Must apply edit");
+                    s.pop_layer();
+                })
+                .button("Re-edit", move |s| {
+                    again_tx
+                        .send(OmniboxEvent::Command(OmniboxCommand::EditAgain))
+                        .expect("Must edit again");
+                    s.pop_layer();
+                })
+                .button("Discard", move |s| {
+                    discard_tx
+                        .send(OmniboxEvent::Command(OmniboxCommand::DiscardEdit))
+                        .expect("Must discard edit");
+                    s.pop_layer();
+                }),
+        )
+    })
+}
+
+/// Edits made to a plist, newest first. Enter asks to revert one.
+pub fn show_history(
+    tx: Sender<OmniboxEvent>,
+    name: String,
+    entries: Vec<JournalEntry>,
+) -> CbSinkMessage {
+    Box::new(move |siv| {
+        let title = format!("history {}", name);
+
+        if entries.is_empty() {
+            siv.add_layer(
+                Dialog::around(TextView::new("launchk has not edited this plist"))
+                    .title(title)
+                    .dismiss_button("OK"),
+            );
+            return;
+        }
+
+        let mut versions = SelectView::new();
+        for entry in entries {
+            versions.add_item(entry.to_string(), entry);
+        }
+
+        versions.set_on_submit(move |s, entry: &JournalEntry| {
+            let confirm = OmniboxCommand::Confirm(
+                format!("Restore {} as it was before {}?", name, entry.timestamp()),
+                vec![OmniboxCommand::RevertTo(entry.backup.clone())],
+            );
+            tx.send(OmniboxEvent::Command(confirm))
+                .expect("Must send revert");
+            s.pop_layer();
+        });
+
+        siv.add_layer(
+            Dialog::around(versions.scrollable())
+                .title(title)
+                .dismiss_button("Cancel"),
+        )
+    })
+}
+
+/// Pick a signal to send to the highlighted service
+pub fn show_signal_picker(tx: Sender<OmniboxEvent>, name: String) -> CbSinkMessage {
+    Box::new(move |siv| {
+        let mut signals = SelectView::new();
+        for (sig_name, signal) in SIGNALS.iter() {
+            signals.add_item(format!("{:<10}{}", sig_name, signal), *signal);
+        }
+
+        signals.set_on_submit(move |s, signal: &i64| {
+            tx.send(OmniboxEvent::Command(OmniboxCommand::Kill(*signal)))
+                .expect("Must send kill");
+            s.pop_layer();
+        });
+
+        siv.add_layer(
+            Dialog::around(signals)
+                .title(format!("kill {}", name))
+                .dismiss_button("Cancel"),
+        )
+    })
+}
+
+/// procinfo summary, with the raw output in $PAGER and export to JSON
+pub fn show_procinfo(cb_sink: Sender<CbSinkMessage>, raw: Vec<u8>) -> CbSinkMessage {
+    let info = parse_procinfo(&String::from_utf8_lossy(&raw));
+
+    Box::new(move |siv| {
+        let summary = TextView::new(info.to_string()).scrollable();
+        let title = format!("procinfo {}", info.pid.unwrap_or_default());
+
+        siv.add_layer(
+            Dialog::around(summary)
+                .title(title)
+                .button("Raw", move |s| {
+                    s.pop_layer();
+                    if let Err(e) = show_pager(&cb_sink, &raw) {
+                        show_notice(e, None)(s);
+                    }
+                })
+                .button("Export JSON", move |s| {
+                    show_notice(export_procinfo(&info), None)(s);
+                })
+                .dismiss_button("OK"),
+        )
+    })
+}
+
+/// print summary for a service, with the raw output in $PAGER
+pub fn show_service_info(cb_sink: Sender<CbSinkMessage>, raw: Vec<u8>) -> CbSinkMessage {
+    let info = parse_service_info(&String::from_utf8_lossy(&raw));
+
+    Box::new(move |siv| {
+        let summary = TextView::new(info.to_string()).scrollable();
+
+        siv.add_layer(
+            Dialog::around(summary)
+                .title(info.name)
+                .button("Raw", move |s| {
+                    s.pop_layer();
+                    if let Err(e) = show_pager(&cb_sink, &raw) {
+                        show_notice(e, None)(s);
+                    }
+                })
+                .dismiss_button("OK"),
+        )
+    })
+}
+
+/// dumpjpcategory as a table, joined with the running jobs by label.
+/// Only the labels matching label_filter are shown if it is set.
+pub fn show_jetsam(
+    cb_sink: Sender<CbSinkMessage>,
+    raw: Vec<u8>,
+    running_jobs: HashSet<String>,
+    label_filter: String,
+) -> CbSinkMessage {
+    let entries = parse_dumpjpcategory(&String::from_utf8_lossy(&raw))
+        .into_iter()
+        .filter(|e| {
+            label_filter.is_empty()
+                || e.label()
+                    .is_some_and(|l| l.to_ascii_lowercase().contains(&label_filter))
+        })
+        .collect();
+
+    Box::new(move |siv| {
+        siv.add_layer(
+            Dialog::around(JetsamListView::new(entries, running_jobs).full_screen())
+                .title("Jetsam (sort: p priority, l limit, n name)")
+                .button("Raw", move |s| {
+                    s.pop_layer();
+                    if let Err(e) = show_pager(&cb_sink, &raw) {
+                        show_notice(e, None)(s);
+                    }
+                })
+                .dismiss_button("OK"),
+        )
+    })
+}
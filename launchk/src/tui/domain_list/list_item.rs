@@ -0,0 +1,25 @@
+use xpc_sys::service_target::ServiceTarget;
+
+use crate::launchd::domain_info::DomainInfo;
+use crate::tui::table::table_list_view::TableListItem;
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct DomainListItem {
+    pub target: ServiceTarget,
+    pub info: DomainInfo,
+}
+
+impl TableListItem for DomainListItem {
+    fn as_row(&self) -> Vec<String> {
+        let count = |c: Option<u64>| c.map(|c| c.to_string()).unwrap_or("-".to_string());
+
+        vec![
+            self.target.to_string(),
+            count(self.info.active_count),
+            count(self.info.on_demand_count),
+            format!("{}/{}", self.info.running_count(), self.info.services.len()),
+            self.info.endpoints.len().to_string(),
+            self.info.overrides.len().to_string(),
+        ]
+    }
+}
@@ -0,0 +1,2 @@
+mod list_item;
+pub mod view;
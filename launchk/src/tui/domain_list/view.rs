@@ -0,0 +1,86 @@
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+
+use cursive::event::{Event, EventResult, Key};
+use cursive::traits::Scrollable;
+use cursive::view::ViewWrapper;
+use cursive::views::{Dialog, TextView};
+use cursive::{View, XY};
+use xpc_sys::service_target::ServiceTarget;
+
+use crate::launchd::domain_info::DomainInfo;
+use crate::tui::domain_list::list_item::DomainListItem;
+use crate::tui::omnibox::command::OmniboxCommand;
+use crate::tui::omnibox::view::OmniboxEvent;
+use crate::tui::table::table_list_view::TableListView;
+
+/// Domains tab: the domains launchd has for the user. Enter filters the
+/// service list to the highlighted one, i shows its details and c
+/// clears the filter.
+pub struct DomainListView {
+    omnibox_tx: Sender<OmniboxEvent>,
+    table_list_view: TableListView<DomainListItem>,
+}
+
+impl DomainListView {
+    pub fn new(omnibox_tx: Sender<OmniboxEvent>) -> Self {
+        let table_list_view = TableListView::new(vec![
+            ("Domain", None),
+            ("Active", Some(8)),
+            ("On-demand", Some(10)),
+            ("Running", Some(10)),
+            ("Endpoints", Some(10)),
+            ("Overrides", Some(10)),
+        ]);
+
+        Self {
+            omnibox_tx,
+            table_list_view,
+        }
+    }
+
+    pub fn replace(&mut self, domains: Vec<(ServiceTarget, DomainInfo)>) {
+        self.table_list_view.replace_and_preserve_selection(
+            domains
+                .into_iter()
+                .map(|(target, info)| DomainListItem { target, info }),
+        );
+    }
+
+    fn send(&self, cmd: OmniboxCommand) -> EventResult {
+        self.omnibox_tx
+            .send(OmniboxEvent::Command(cmd))
+            .expect("Must send command");
+
+        EventResult::Consumed(None)
+    }
+
+    fn show_details(item: Arc<DomainListItem>) -> EventResult {
+        EventResult::with_cb(move |s| {
+            s.add_layer(
+                Dialog::around(TextView::new(item.info.to_string()).scrollable())
+                    .title(item.info.name.clone())
+                    .dismiss_button("OK"),
+            );
+        })
+    }
+}
+
+impl ViewWrapper for DomainListView {
+    wrap_impl!(self.table_list_view: TableListView<DomainListItem>);
+
+    fn wrap_on_event(&mut self, event: Event) -> EventResult {
+        match (event, self.table_list_view.get_highlighted_row()) {
+            (Event::Key(Key::Enter), Some(item)) => {
+                self.send(OmniboxCommand::DomainFilter(item.target.clone()))
+            }
+            (Event::Char('i'), Some(item)) => Self::show_details(item),
+            (Event::Char('c'), _) => self.send(OmniboxCommand::ClearDomainFilter),
+            (event, _) => self.table_list_view.on_event(event),
+        }
+    }
+
+    fn wrap_layout(&mut self, size: XY<usize>) {
+        self.table_list_view.layout(size);
+    }
+}
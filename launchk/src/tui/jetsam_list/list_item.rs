@@ -0,0 +1,44 @@
+use crate::launchd::jetsam::{JetsamEntry, MemoryLimit};
+use crate::tui::table::table_list_view::TableListItem;
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct JetsamListItem {
+    pub entry: JetsamEntry,
+    /// Whether the label is in the service list's running jobs,
+    /// None for processes without a label
+    pub loaded: Option<bool>,
+}
+
+impl TableListItem for JetsamListItem {
+    fn as_row(&self) -> Vec<String> {
+        let limit = |l: Option<MemoryLimit>| l.map(|l| l.to_string()).unwrap_or("-".to_string());
+
+        let priority = match (self.entry.priority, &self.entry.band) {
+            (Some(p), Some(band)) => format!("{} {}", p, band),
+            (Some(p), None) => p.to_string(),
+            _ => "-".to_string(),
+        };
+
+        let freeze = if self.entry.freeze_flags.is_empty() {
+            "-".to_string()
+        } else {
+            "✔".to_string()
+        };
+
+        let loaded = match self.loaded {
+            Some(true) => "✔",
+            Some(false) => "✘",
+            None => "-",
+        };
+
+        vec![
+            self.entry.name.clone(),
+            self.entry.category.clone().unwrap_or("-".to_string()),
+            priority,
+            limit(self.entry.effective_limit()),
+            limit(self.entry.inactive_limit),
+            freeze,
+            loaded.to_string(),
+        ]
+    }
+}
@@ -0,0 +1,77 @@
+use std::collections::HashSet;
+
+use cursive::event::{Event, EventResult};
+use cursive::view::ViewWrapper;
+use cursive::{View, XY};
+
+use crate::launchd::jetsam::{sort_entries, JetsamEntry, JetsamSort};
+use crate::tui::jetsam_list::list_item::JetsamListItem;
+use crate::tui::table::table_list_view::TableListView;
+
+/// Jetsam entries from dumpjpcategory, sorted with `p` (priority),
+/// `l` (limit) and `n` (name)
+pub struct JetsamListView {
+    entries: Vec<JetsamEntry>,
+    running_jobs: HashSet<String>,
+    sort: JetsamSort,
+    table_list_view: TableListView<JetsamListItem>,
+}
+
+impl JetsamListView {
+    pub fn new(entries: Vec<JetsamEntry>, running_jobs: HashSet<String>) -> Self {
+        let mut view = Self {
+            entries,
+            running_jobs,
+            sort: JetsamSort::Priority,
+            table_list_view: TableListView::new(vec![
+                ("Name", None),
+                ("Category", Some(12)),
+                ("Priority", Some(16)),
+                ("Active", Some(10)),
+                ("Inactive", Some(10)),
+                ("Freeze", Some(7)),
+                ("Loaded", Some(7)),
+            ]),
+        };
+
+        view.present_entries();
+        view
+    }
+
+    fn present_entries(&mut self) {
+        sort_entries(&mut self.entries, self.sort);
+
+        let items: Vec<JetsamListItem> = self
+            .entries
+            .iter()
+            .map(|entry| JetsamListItem {
+                loaded: entry.label().map(|l| self.running_jobs.contains(l)),
+                entry: entry.clone(),
+            })
+            .collect();
+
+        self.table_list_view.replace_and_preserve_selection(items);
+    }
+}
+
+impl ViewWrapper for JetsamListView {
+    wrap_impl!(self.table_list_view: TableListView<JetsamListItem>);
+
+    fn wrap_on_event(&mut self, event: Event) -> EventResult {
+        let sort = match event {
+            Event::Char('p') => JetsamSort::Priority,
+            Event::Char('l') => JetsamSort::Limit,
+            Event::Char('n') => JetsamSort::Name,
+            _ => return self.table_list_view.on_event(event),
+        };
+
+        self.sort = sort;
+        self.present_entries();
+
+        EventResult::Consumed(None)
+    }
+
+    fn wrap_layout(&mut self, size: XY<usize>) {
+        self.table_list_view.layout(size);
+    }
+}
@@ -1,4 +1,6 @@
 mod dialog;
+mod domain_list;
+mod jetsam_list;
 mod omnibox;
 mod pager;
 pub mod root;
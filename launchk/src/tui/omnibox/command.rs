@@ -1,5 +1,5 @@
 use std::fmt;
-use xpc_sys::enums::DomainType;
+use xpc_sys::service_target::ServiceTarget;
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum OmniboxCommand {
@@ -11,16 +11,35 @@ pub enum OmniboxCommand {
     EnableRequest,
     DisableRequest,
     Blame,
-    Bootstrap(DomainType),
-    Bootout(DomainType),
-    Enable(DomainType),
-    Disable(DomainType),
+    Bootstrap(ServiceTarget),
+    Bootout(ServiceTarget),
+    Enable(ServiceTarget),
+    Disable(ServiceTarget),
+    Kickstart,
+    Restart,
+    KillRequest,
+    // Signal number
+    Kill(i64),
     Edit,
+    // Write the previewed edit back, then bootout and bootstrap if set
+    ApplyEdit(bool),
+    EditAgain,
+    DiscardEdit,
+    History,
+    Revert,
+    // Backup file name from the journal
+    RevertTo(String),
+    Lint,
     FocusServiceList,
     CSRInfo,
     DumpState,
     DumpJetsamPropertiesCategory,
+    Domains,
+    // Only show the services in this domain
+    DomainFilter(ServiceTarget),
+    ClearDomainFilter,
     ProcInfo,
+    Print,
     Sudo,
     Help,
     Quit,
@@ -32,7 +51,7 @@ impl fmt::Display for OmniboxCommand {
     }
 }
 
-pub static OMNIBOX_COMMANDS: [(&str, &str, OmniboxCommand); 12] = [
+pub static OMNIBOX_COMMANDS: [(&str, &str, OmniboxCommand); 20] = [
     (
         "blame",
         "Why launchd launched the service",
@@ -58,7 +77,37 @@ pub static OMNIBOX_COMMANDS: [(&str, &str, OmniboxCommand); 12] = [
         "Disable highlighted job (prevents load)",
         OmniboxCommand::DisableRequest,
     ),
+    (
+        "kickstart",
+        "Start highlighted service if it is not running",
+        OmniboxCommand::Kickstart,
+    ),
+    (
+        "restart",
+        "SIGKILL, wait up to 2s, then start highlighted service (can race KeepAlive)",
+        OmniboxCommand::Restart,
+    ),
+    (
+        "kill",
+        "Send a signal to highlighted service",
+        OmniboxCommand::KillRequest,
+    ),
     ("edit", "Edit plist with $EDITOR", OmniboxCommand::Edit),
+    (
+        "history",
+        "Edits launchk made to highlighted plist, Enter to revert",
+        OmniboxCommand::History,
+    ),
+    (
+        "revert",
+        "Undo the last edit launchk made to highlighted plist",
+        OmniboxCommand::Revert,
+    ),
+    (
+        "lint",
+        "Check highlighted plist against the lint rules",
+        OmniboxCommand::Lint,
+    ),
     ("csrinfo", "See all CSR flags", OmniboxCommand::CSRInfo),
     (
         "dumpstate",
@@ -70,11 +119,21 @@ pub static OMNIBOX_COMMANDS: [(&str, &str, OmniboxCommand); 12] = [
         "launchctl dumpjpcategory",
         OmniboxCommand::DumpJetsamPropertiesCategory,
     ),
+    (
+        "domains",
+        "Show the domains tab, Enter filters the list to one",
+        OmniboxCommand::Domains,
+    ),
     (
         "procinfo",
         "launchctl procinfo for highlighted process",
         OmniboxCommand::ProcInfo,
     ),
+    (
+        "print",
+        "launchctl print for highlighted service (or Enter)",
+        OmniboxCommand::Print,
+    ),
     ("help", "Show all commands", OmniboxCommand::Help),
     ("exit", "Exit", OmniboxCommand::Quit),
 ];
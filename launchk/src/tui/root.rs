@@ -1,15 +1,20 @@
 use std::collections::VecDeque;
 
 use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
 
 use cursive::event::{Event, EventResult, Key};
-use cursive::traits::{Resizable, Scrollable};
-use cursive::view::ViewWrapper;
-use cursive::views::{LinearLayout, NamedView, Panel};
+use cursive::traits::{Nameable, Resizable, Scrollable};
+use cursive::view::{Finder, ViewWrapper};
+use cursive::views::{HideableView, LinearLayout, NamedView, Panel, ResizedView, TextView};
 use cursive::{Cursive, Vec2, View};
 
 use tokio::runtime::Handle;
+use xpc_sys::api::transport::XPCTransport;
+use xpc_sys::service_target::ServiceTarget;
 
+use crate::launchd::domain_info::DomainInfo;
+use crate::tui::domain_list::view::DomainListView;
 use crate::tui::omnibox::command::OmniboxCommand;
 use crate::tui::omnibox::subscribed_view::{
     OmniboxResult, OmniboxSubscribedView, OmniboxSubscriber, Subscribable,
@@ -26,8 +31,11 @@ pub struct RootLayout {
     layout: LinearLayout,
     omnibox_tx: Sender<OmniboxEvent>,
     runtime_handle: Handle,
+    transport: Arc<dyn XPCTransport>,
     cbsink_channel: Sender<CbSinkMessage>,
     key_ring: VecDeque<Event>,
+    tab: Tab,
+    domain_filter: Option<ServiceTarget>,
 }
 
 #[derive(Debug)]
@@ -35,9 +43,19 @@ enum RootLayoutChildren {
     #[allow(dead_code)]
     SysInfo,
     Omnibox,
+    TabBar,
     ServiceList,
+    DomainList,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tab {
+    Services,
+    Domains,
+}
+
+type DomainListTab = HideableView<ResizedView<NamedView<DomainListView>>>;
+
 async fn poll_omnibox(cb_sink: Sender<CbSinkMessage>, rx: Receiver<OmniboxEvent>) {
     loop {
         let recv = rx.recv().expect("Must receive event");
@@ -55,7 +73,11 @@ async fn poll_omnibox(cb_sink: Sender<CbSinkMessage>, rx: Receiver<OmniboxEvent>
 }
 
 impl RootLayout {
-    pub fn new(siv: &mut Cursive, runtime_handle: &Handle) -> Self {
+    pub fn new(
+        siv: &mut Cursive,
+        runtime_handle: &Handle,
+        transport: Arc<dyn XPCTransport>,
+    ) -> Self {
         let (omnibox, omnibox_tx, omnibox_rx) = OmniboxView::new(runtime_handle);
         let cbsink_channel = RootLayout::cbsink_channel(siv);
 
@@ -66,7 +88,10 @@ impl RootLayout {
             cbsink_channel,
             layout: LinearLayout::vertical(),
             runtime_handle: runtime_handle.clone(),
+            transport,
             key_ring: VecDeque::with_capacity(3),
+            tab: Tab::Services,
+            domain_filter: None,
         };
 
         siv.set_fps(2);
@@ -81,21 +106,93 @@ impl RootLayout {
             .full_width()
             .max_height(3);
 
-        let service_list = ServiceListView::new(&self.runtime_handle, self.cbsink_channel.clone())
-            .full_width()
-            .full_height()
-            .scrollable()
-            .subscribable();
+        let service_list = ServiceListView::new(
+            &self.runtime_handle,
+            self.cbsink_channel.clone(),
+            self.omnibox_tx.clone(),
+            self.transport.clone(),
+        )
+        .full_width()
+        .full_height()
+        .scrollable()
+        .subscribable();
+
+        let domain_list = DomainListView::new(self.omnibox_tx.clone())
+            .with_name("domain_list")
+            .full_screen();
 
         self.with_view_mut(|v| {
             v.add_child(sysinfo);
             v.add_child(omnibox);
-            v.add_child(service_list);
+            v.add_child(TextView::new(""));
+            v.add_child(HideableView::new(service_list));
+            v.add_child(HideableView::new(domain_list).hidden());
         });
 
+        self.show_tab(Tab::Services);
+    }
+
+    /// Show one tab, hide the other and say which is showing
+    fn show_tab(&mut self, tab: Tab) {
+        self.tab = tab;
+
+        if let Some(v) = self
+            .layout
+            .get_child_mut(RootLayoutChildren::ServiceList as usize)
+            .and_then(|v| v.as_any_mut().downcast_mut::<HideableView<OmniboxSubscribedView>>())
+        {
+            v.set_visible(tab == Tab::Services);
+        }
+
+        if let Some(v) = self
+            .layout
+            .get_child_mut(RootLayoutChildren::DomainList as usize)
+            .and_then(|v| v.as_any_mut().downcast_mut::<DomainListTab>())
+        {
+            v.set_visible(tab == Tab::Domains);
+        }
+
+        let services = match &self.domain_filter {
+            Some(target) => format!("Services in {}", target),
+            None => "Services".to_string(),
+        };
+
+        let tab_bar = match tab {
+            Tab::Services => format!(" [{}]  Domains", services),
+            Tab::Domains => format!(
+                " {}  [Domains]  (Enter: filter, i: details, c: show all)",
+                services
+            ),
+        };
+
+        if let Some(v) = self
+            .layout
+            .get_child_mut(RootLayoutChildren::TabBar as usize)
+            .and_then(|v| v.as_any_mut().downcast_mut::<TextView>())
+        {
+            v.set_content(tab_bar);
+        }
+
+        self.focus_tab();
+    }
+
+    fn focus_tab(&mut self) {
+        let child = match self.tab {
+            Tab::Services => RootLayoutChildren::ServiceList,
+            Tab::Domains => RootLayoutChildren::DomainList,
+        };
+
         self.layout
-            .set_focus_index(RootLayoutChildren::ServiceList as usize)
-            .unwrap();
+            .set_focus_index(child as usize)
+            .expect("Must focus tab");
+    }
+
+    /// Fill the domains tab and switch to it
+    fn show_domains(&mut self, domains: Vec<(ServiceTarget, DomainInfo)>) {
+        self.layout
+            .call_on_name("domain_list", |v: &mut DomainListView| v.replace(domains));
+
+        self.show_tab(Tab::Domains);
     }
 
     /// Cursive uses a different crate for its channels (?), so this is some glue
@@ -126,8 +223,9 @@ impl RootLayout {
         let target = self
             .layout
             .get_child_mut(RootLayoutChildren::ServiceList as usize)
-            .and_then(|v| v.as_any_mut().downcast_mut::<OmniboxSubscribedView>())
-            .expect("Must forward to ServiceList");
+            .and_then(|v| v.as_any_mut().downcast_mut::<HideableView<OmniboxSubscribedView>>())
+            .expect("Must forward to ServiceList")
+            .get_inner_mut();
 
         let omnibox_events = [self_event, target.on_omnibox(recv)];
 
@@ -200,6 +298,28 @@ impl ViewWrapper for RootLayout {
             | Event::Key(Key::Backspace) => {
                 self.focus_and_forward(RootLayoutChildren::Omnibox, event)
             }
+            // Print the highlighted service, unless typing a command
+            Event::Key(Key::Enter)
+                if self.layout.get_focus_index() == RootLayoutChildren::ServiceList as usize =>
+            {
+                self.omnibox_tx
+                    .send(OmniboxEvent::Command(OmniboxCommand::Print))
+                    .expect("Must send print");
+                EventResult::Consumed(None)
+            }
+            // Switch tabs, unless completing a command
+            Event::Key(Key::Tab)
+                if self.layout.get_focus_index() != RootLayoutChildren::Omnibox as usize =>
+            {
+                match self.tab {
+                    Tab::Services => self
+                        .omnibox_tx
+                        .send(OmniboxEvent::Command(OmniboxCommand::Domains))
+                        .expect("Must send domains"),
+                    Tab::Domains => self.show_tab(Tab::Services),
+                }
+                EventResult::Consumed(None)
+            }
             // TODO: wtf?
             // After exiting $EDITOR, for some reason we get a termcap issue. iTerm and Apple Terminal
             // exhibit the same behavior. This was the easiest way to solve the problem for now.
@@ -255,9 +375,18 @@ impl OmniboxSubscriber for RootLayout {
             }
             // Triggered when toggling to idle
             OmniboxEvent::Command(OmniboxCommand::FocusServiceList) => {
-                self.layout
-                    .set_focus_index(RootLayoutChildren::ServiceList as usize)
-                    .expect("Must focus SL");
+                self.focus_tab();
+                Ok(None)
+            }
+            // Drilled into a domain (or out of one) from the domains tab
+            OmniboxEvent::Command(OmniboxCommand::DomainFilter(target)) => {
+                self.domain_filter = Some(target);
+                self.show_tab(Tab::Services);
+                Ok(None)
+            }
+            OmniboxEvent::Command(OmniboxCommand::ClearDomainFilter) => {
+                self.domain_filter = None;
+                self.show_tab(Tab::Services);
                 Ok(None)
             }
             OmniboxEvent::Command(OmniboxCommand::Confirm(p, c)) => {
@@ -277,3 +406,12 @@ impl OmniboxSubscriber for RootLayout {
         }
     }
 }
+
+/// Show the domains tab with these domains
+pub fn show_domains(domains: Vec<(ServiceTarget, DomainInfo)>) -> CbSinkMessage {
+    Box::new(move |siv| {
+        siv.call_on_name("root_layout", |v: &mut NamedView<RootLayout>| {
+            v.get_mut().show_domains(domains);
+        });
+    })
+}
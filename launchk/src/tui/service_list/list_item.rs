@@ -1,6 +1,9 @@
 use crate::launchd::entry_status::LaunchdEntryStatus;
+use crate::launchd::exit_status::LastExit;
 use crate::launchd::job_type_filter::JobTypeFilter;
+use crate::launchd::overrides::OverrideState;
 use crate::tui::table::table_list_view::TableListItem;
+use cursive::theme::{BaseColor, Color, Style};
 use xpc_sys::enums::SessionType;
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
@@ -8,6 +11,22 @@ pub struct ServiceListItem {
     pub name: String,
     pub status: LaunchdEntryStatus,
     pub job_type_filter: JobTypeFilter,
+    /// None if the plist decides
+    pub override_state: Option<OverrideState>,
+    /// Got a new PID too many times recently
+    pub crash_looping: bool,
+}
+
+impl ServiceListItem {
+    /// Last exited non-zero or from a signal, or is crash looping
+    pub fn is_failing(&self) -> bool {
+        self.crash_looping
+            || self
+                .status
+                .last_exit
+                .as_ref()
+                .is_some_and(LastExit::is_failure)
+    }
 }
 
 impl TableListItem for ServiceListItem {
@@ -30,22 +49,40 @@ impl TableListItem for ServiceListItem {
             "-".to_string()
         };
 
-        let mut loaded = if self.job_type_filter.intersects(JobTypeFilter::LOADED) {
+        let loaded = if self.job_type_filter.intersects(JobTypeFilter::LOADED) {
             "✔".to_string()
         } else {
             "✘".to_string()
         };
 
-        if self.job_type_filter.intersects(JobTypeFilter::DISABLED) {
-            loaded = format!("{} disabled", loaded)
-        }
+        let enabled = match &self.override_state {
+            Some(state) => format!("{} (override)", state),
+            None => "default".to_string(),
+        };
+
+        let last_exit = match (&self.status.last_exit, self.crash_looping) {
+            (Some(last_exit), true) => format!("{} (crash loop)", last_exit),
+            (None, true) => "(crash loop)".to_string(),
+            (Some(last_exit), false) => last_exit.to_string(),
+            (None, false) => "-".to_string(),
+        };
 
         vec![
             self.name.clone(),
             session_type,
             entry_type,
             pid,
-            loaded.to_string(),
+            loaded,
+            enabled,
+            last_exit,
         ]
     }
+
+    fn style(&self) -> Style {
+        if self.is_failing() {
+            Style::from(Color::Light(BaseColor::Red))
+        } else {
+            Style::none()
+        }
+    }
 }
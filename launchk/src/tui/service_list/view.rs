@@ -1,10 +1,12 @@
 use std::cmp::Ordering;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::future::pending;
 use std::ops::Deref;
-use std::ptr::slice_from_raw_parts;
+use std::path::Path;
+use std::sync::atomic::{self, AtomicBool};
 use std::sync::mpsc::Sender;
-use std::sync::{Arc, RwLock};
-use std::time::Duration;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 
 use cursive::direction::Direction;
 use cursive::event::EventResult;
@@ -13,55 +15,142 @@ use cursive::view::ViewWrapper;
 use cursive::{Cursive, CursiveExt, View, XY};
 use sudo::RunningAs;
 
-use crate::launchd::command::{blame, bootout, bootstrap, dumpjpcategory, dumpstate, list_all, procinfo, read_disabled_hashset};
-use crate::launchd::command::{disable, enable};
+use crate::launchd::command::{
+    blame, bootout, bootstrap, dumpjpcategory, dumpstate, gui_asid, list_all, print_domains,
+    print_service, procinfo, read_overrides,
+};
+use crate::launchd::command::{disable, enable, kickstart, kill, restart, KickstartMode};
+use crate::launchd::dumpstate::parse_dumpstate;
+use crate::launchd::entry_status::{agent_uid, get_entry_status, login_asid, refresh_entry_status};
+use crate::launchd::error::LaunchdError;
+use crate::launchd::exit_status::CrashLoopDetector;
+use crate::launchd::history::History;
 use crate::launchd::job_type_filter::JobTypeFilter;
-use crate::launchd::plist::{edit_and_replace, LABEL_TO_ENTRY_CONFIG};
-use crate::launchd::{
-    entry_status::get_entry_status, entry_status::LaunchdEntryStatus,
+use crate::launchd::lint::lint_file;
+use crate::launchd::overrides::OverrideState;
+use crate::launchd::plist::{edit_plist, LaunchdEntryLocation, PlistEdit, LABEL_TO_ENTRY_CONFIG};
+use crate::tui::dialog::{
+    show_csr_info, show_edit_preview, show_history, show_jetsam, show_lint, show_notice,
+    show_procinfo, show_service_info, show_signal_picker,
 };
-use crate::tui::dialog::{show_csr_info, show_notice};
 use crate::tui::omnibox::command::OmniboxCommand;
 use tokio::runtime::Handle;
 use tokio::time::interval;
-use xpc_sys::enums::DomainType;
-use xpc_sys::rs_geteuid;
+use xpc_sys::api::async_routine::spawn_routine;
+use xpc_sys::api::transport::XPCTransport;
+use xpc_sys::object::xpc_error::XPCError;
+use xpc_sys::service_target::ServiceTarget;
 
 use crate::tui::omnibox::state::OmniboxState;
 use crate::tui::omnibox::subscribed_view::{OmniboxResult, OmniboxSubscriber};
 use crate::tui::omnibox::view::{OmniboxError, OmniboxEvent, OmniboxMode};
 use crate::tui::pager::show_pager;
-use crate::tui::root::CbSinkMessage;
+use crate::tui::root::{show_domains, CbSinkMessage};
 use crate::tui::service_list::list_item::ServiceListItem;
 use crate::tui::table::table_list_view::TableListView;
 
-/// Polls XPC for job list
+/// How long to wait on launchd before showing an error
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(15);
+const POLL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A job is crash looping if it got this many new PIDs within the window
+const CRASH_LOOP_RESTARTS: usize = 3;
+const CRASH_LOOP_WINDOW: Duration = Duration::from_secs(60);
+
+/// Either waiting on launchd or the command itself failed
+fn flatten<T>(result: Result<Result<T, LaunchdError>, XPCError>) -> Result<T, String> {
+    result
+        .map_err(|e| e.to_string())
+        .and_then(|r| r.map_err(|e| e.to_string()))
+}
+
+/// Job list, the status of every loaded job with it, and overrides
+fn poll_state(
+    transport: &dyn XPCTransport,
+    crash_loop_detector: &Mutex<CrashLoopDetector>,
+) -> Result<ServiceListState, LaunchdError> {
+    let listed = list_all(transport);
+
+    // Lasts as long as the login, so only asked for until launchd has it
+    let login_asid = login_asid().or_else(|| gui_asid(transport, agent_uid()));
+    refresh_entry_status(&listed, login_asid);
+
+    let pids = listed
+        .iter()
+        .map(|(label, (_, service))| (label.clone(), service.pid))
+        .collect();
+    let running_jobs = listed.into_keys().collect();
+    let crash_loops = crash_loop_detector
+        .lock()
+        .expect("Must record PIDs")
+        .record(&pids, Instant::now());
+
+    // Daemons and the agents of the user launchk is run by (or sudo from)
+    let mut override_targets = vec![ServiceTarget::system()];
+    if agent_uid() != 0 {
+        override_targets.push(ServiceTarget::gui(agent_uid()));
+    }
+
+    override_targets
+        .into_iter()
+        .map(|target| read_overrides(transport, &target).map(|o| (target, o)))
+        .collect::<Result<_, _>>()
+        .map(|overrides| ServiceListState {
+            running_jobs,
+            overrides,
+            crash_loops,
+        })
+}
+
+/// Clears the polling flag when a poll ends, even if it panicked
+struct PollGuard(Arc<AtomicBool>);
+
+impl Drop for PollGuard {
+    fn drop(&mut self) {
+        self.0.store(false, atomic::Ordering::SeqCst);
+    }
+}
+
+/// Polls XPC for job list, and the status of every loaded job with it
 async fn poll_running_jobs(
+    transport: Arc<dyn XPCTransport>,
     service_list_state: Arc<RwLock<ServiceListState>>,
     cb_sink: Sender<CbSinkMessage>,
 ) {
     let mut interval = interval(Duration::from_secs(1));
+    let crash_loop_detector = Arc::new(Mutex::new(CrashLoopDetector::new(
+        CRASH_LOOP_RESTARTS,
+        CRASH_LOOP_WINDOW,
+    )));
+    let polling = Arc::new(AtomicBool::new(false));
 
     loop {
         interval.tick().await;
 
-        match service_list_state.try_write() {
-            Ok(mut w) => {
-                let running_jobs = list_all();
-
-                let disabled_job_domain = if rs_geteuid() == 0 {
-                    DomainType::System
-                } else {
-                    DomainType::User
-                };
+        // A poll that timed out keeps running on its blocking thread
+        if polling.swap(true, atomic::Ordering::SeqCst) {
+            log::debug!("poll_running_jobs: last poll still running, skipping");
+            continue;
+        }
 
-                let disabled_jobs = read_disabled_hashset(disabled_job_domain).unwrap();
+        let transport = transport.clone();
+        let crash_loop_detector = crash_loop_detector.clone();
+        let guard = PollGuard(polling.clone());
+        let poll = move || {
+            let _guard = guard;
+            poll_state(transport.as_ref(), &crash_loop_detector)
+        };
 
-                *w = ServiceListState {
-                    running_jobs,
-                    disabled_jobs,
-                }
+        let new_state = match flatten(spawn_routine(poll, POLL_TIMEOUT, pending()).await) {
+            Ok(state) => state,
+            Err(e) => {
+                log::error!("poll_running_jobs: {}", e);
+                continue;
             }
+        };
+
+        match service_list_state.try_write() {
+            Ok(mut w) => *w = new_state,
             Err(_) => continue,
         }
 
@@ -72,15 +161,25 @@ async fn poll_running_jobs(
 #[derive(Default)]
 struct ServiceListState {
     running_jobs: HashSet<String>,
-    disabled_jobs: HashSet<String>,
+    /// Enabled and disabled overrides by domain
+    overrides: HashMap<ServiceTarget, HashMap<String, OverrideState>>,
+    /// Labels that keep getting new PIDs
+    crash_loops: HashSet<String>,
 }
 
 pub struct ServiceListView {
+    runtime_handle: Handle,
+    transport: Arc<dyn XPCTransport>,
     state: Arc<RwLock<ServiceListState>>,
     cb_sink: Sender<CbSinkMessage>,
+    omnibox_tx: Sender<OmniboxEvent>,
     table_list_view: TableListView<ServiceListItem>,
     label_filter: Arc<RwLock<String>>,
     job_type_filter: Arc<RwLock<JobTypeFilter>>,
+    /// Only the services in this domain, if set
+    domain_filter: Arc<RwLock<Option<ServiceTarget>>>,
+    /// An edit shown in the preview dialog, until it is applied or discarded
+    pending_edit: Mutex<Option<PlistEdit>>,
 }
 
 enum ServiceListError {
@@ -88,25 +187,38 @@ enum ServiceListError {
 }
 
 impl ServiceListView {
-    pub fn new(runtime_handle: &Handle, cb_sink: Sender<CbSinkMessage>) -> Self {
+    pub fn new(
+        runtime_handle: &Handle,
+        cb_sink: Sender<CbSinkMessage>,
+        omnibox_tx: Sender<OmniboxEvent>,
+        transport: Arc<dyn XPCTransport>,
+    ) -> Self {
         let service_list_state = Arc::new(RwLock::new(ServiceListState::default()));
 
         runtime_handle.spawn(poll_running_jobs(
+            transport.clone(),
             service_list_state.clone(),
             cb_sink.clone(),
         ));
 
         Self {
+            runtime_handle: runtime_handle.clone(),
+            transport,
             state: service_list_state,
             cb_sink,
+            omnibox_tx,
             label_filter: Arc::new(RwLock::new("".into())),
             job_type_filter: Arc::new(RwLock::new(JobTypeFilter::launchk_default())),
+            domain_filter: Arc::new(RwLock::new(None)),
+            pending_edit: Mutex::new(None),
             table_list_view: TableListView::new(vec![
                 ("Name", None),
                 ("Session", Some(10)),
                 ("Type", Some(8)),
                 ("PID", Some(6)),
                 ("Loaded", Some(6)),
+                ("Enabled", Some(20)),
+                ("Last Exit", Some(22)),
             ]),
         }
     }
@@ -122,8 +234,9 @@ impl ServiceListView {
             .map_err(|_| ServiceListError::PresentationError)?;
 
         let ServiceListState {
-            disabled_jobs,
+            overrides,
             running_jobs,
+            crash_loops,
         } = state.deref();
 
         let name_filter = self
@@ -134,6 +247,10 @@ impl ServiceListView {
             .job_type_filter
             .read()
             .map_err(|_| ServiceListError::PresentationError)?;
+        let domain_filter = self
+            .domain_filter
+            .read()
+            .map_err(|_| ServiceListError::PresentationError)?;
 
         let running_no_plist = running_jobs.iter().filter(|r| !plists.contains_key(*r));
 
@@ -150,8 +267,20 @@ impl ServiceListView {
                 }
 
                 let status = get_entry_status(label);
+
+                if domain_filter
+                    .as_ref()
+                    .is_some_and(|d| status.domain.domain_target() != *d)
+                {
+                    return None;
+                }
+
                 let is_loaded = running_jobs.contains(label);
-                let is_disabled = disabled_jobs.contains(label);
+                let override_state = overrides
+                    .get(&status.domain.domain_target())
+                    .and_then(|o| o.get(label))
+                    .copied();
+                let is_disabled = override_state == Some(OverrideState::Disabled);
 
                 let entry_job_type_filter = status
                     .plist
@@ -174,6 +303,8 @@ impl ServiceListView {
                     status,
                     name: label.clone(),
                     job_type_filter: entry_job_type_filter,
+                    override_state,
+                    crash_looping: crash_loops.contains(label),
                 })
             })
             .collect();
@@ -195,6 +326,47 @@ impl ServiceListView {
         Ok(items)
     }
 
+    /// Run a launchd command off the UI thread, then show what it returned
+    /// with `done`. Errors and timeouts are shown as a notice.
+    fn spawn_command<T, F, D>(&self, command: F, done: D) -> OmniboxResult
+    where
+        T: Send + 'static,
+        F: FnOnce(&dyn XPCTransport) -> Result<T, LaunchdError> + Send + 'static,
+        D: FnOnce(T) -> CbSinkMessage + Send + 'static,
+    {
+        let transport = self.transport.clone();
+        let cb_sink = self.cb_sink.clone();
+
+        self.runtime_handle.spawn(async move {
+            let call = move || command(transport.as_ref());
+
+            let msg = match flatten(spawn_routine(call, COMMAND_TIMEOUT, pending()).await) {
+                Ok(t) => done(t),
+                Err(e) => show_notice(e, None),
+            };
+
+            cb_sink.send(msg).expect("Must show result");
+        });
+
+        Ok(None)
+    }
+
+    /// spawn_command, then page through the bytes it returned
+    fn spawn_pager<F>(&self, command: F) -> OmniboxResult
+    where
+        F: FnOnce(&dyn XPCTransport) -> Result<Vec<u8>, LaunchdError> + Send + 'static,
+    {
+        let cb_sink = self.cb_sink.clone();
+
+        self.spawn_command(command, move |bytes| {
+            Box::new(move |siv: &mut Cursive| {
+                if let Err(e) = show_pager(&cb_sink, &bytes) {
+                    show_notice(e, None)(siv);
+                }
+            })
+        })
+    }
+
     fn handle_state_update(&mut self, state: OmniboxState) -> OmniboxResult {
         let OmniboxState {
             mode,
@@ -237,8 +409,37 @@ impl ServiceListView {
         Ok(None)
     }
 
+    /// Show the changes of the pending edit, reloading the job after
+    /// applying by default if it is loaded
+    fn preview_edit(&self) -> OmniboxResult {
+        let pending_edit = self
+            .pending_edit
+            .lock()
+            .map_err(|_| OmniboxError::StateError)?;
+        let edit = pending_edit
+            .as_ref()
+            .ok_or_else(|| OmniboxError::CommandError("No edit to preview".to_string()))?;
+
+        self.cb_sink
+            .send(show_edit_preview(
+                self.omnibox_tx.clone(),
+                edit.label().to_string(),
+                edit.diff(),
+                edit.loaded(),
+            ))
+            .expect("Must show edit preview");
+
+        Ok(None)
+    }
+
     fn handle_plist_command(&self, cmd: OmniboxCommand, item: Arc<ServiceListItem>) -> OmniboxResult {
-        let ServiceListItem { name, status, .. } = item.deref();
+        let ServiceListItem {
+            name,
+            status,
+            job_type_filter,
+            ..
+        } = item.deref();
+        let loaded = job_type_filter.intersects(JobTypeFilter::LOADED);
 
         let plist = status
             .clone()
@@ -247,23 +448,94 @@ impl ServiceListView {
 
         match cmd {
             OmniboxCommand::Edit => {
-                let edited = edit_and_replace(&plist)
-                    .map_err(OmniboxError::CommandError)
-                    .map(|()| Option::<OmniboxCommand>::None);
+                let target = status.domain.clone().with_name(name.clone());
+                let edited =
+                    edit_plist(name, target, loaded, &plist).map_err(OmniboxError::CommandError);
 
                 // Reinit curses
                 self.cb_sink
                     .send(Box::new(|siv: &mut Cursive| siv.run()))
                     .expect("Must clear");
 
-                edited
+                *self
+                    .pending_edit
+                    .lock()
+                    .map_err(|_| OmniboxError::StateError)? = Some(edited?);
+
+                self.preview_edit()
+            }
+            OmniboxCommand::Bootstrap(domain) => {
+                let target = domain.with_name(name.clone());
+                let plist_path = plist.plist_path.clone();
+                self.spawn_command(
+                    move |t| bootstrap(t, &target, plist_path),
+                    |_| Box::new(Cursive::noop),
+                )
+            }
+            OmniboxCommand::Bootout(domain) => {
+                let target = domain.with_name(name.clone());
+                self.spawn_command(
+                    move |t| bootout(t, &target),
+                    |_| Box::new(Cursive::noop),
+                )
+            }
+            OmniboxCommand::History => {
+                let entries = History::user()
+                    .entries(name)
+                    .map_err(OmniboxError::CommandError)?;
+
+                self.cb_sink
+                    .send(show_history(self.omnibox_tx.clone(), name.clone(), entries))
+                    .expect("Must show history");
+
+                Ok(None)
+            }
+            OmniboxCommand::Revert => {
+                let entries = History::user()
+                    .entries(name)
+                    .map_err(OmniboxError::CommandError)?;
+                let last = entries.first().ok_or_else(|| {
+                    OmniboxError::CommandError(format!("launchk has not edited {}", name))
+                })?;
+
+                Ok(Some(OmniboxCommand::Confirm(
+                    format!("Restore {} as it was before {}?", name, last.timestamp()),
+                    vec![OmniboxCommand::RevertTo(last.backup.clone())],
+                )))
+            }
+            OmniboxCommand::RevertTo(backup) => {
+                let history = History::user();
+                let entries = history.entries(name).map_err(OmniboxError::CommandError)?;
+                let entry = entries
+                    .iter()
+                    .find(|e| e.backup == backup)
+                    .ok_or_else(|| OmniboxError::CommandError(format!("No backup {}", backup)))?;
+
+                history.revert(entry).map_err(OmniboxError::CommandError)?;
+
+                self.cb_sink
+                    .send(show_notice(
+                        format!(
+                            "Restored {} as it was before {}. Bootout and bootstrap to load it.",
+                            entry.path,
+                            entry.timestamp()
+                        ),
+                        None,
+                    ))
+                    .expect("Must show notice");
+
+                Ok(None)
+            }
+            OmniboxCommand::Lint => {
+                let findings = lint_file(Path::new(&plist.plist_path))
+                    .map_err(OmniboxError::CommandError)?;
+
+                self.cb_sink
+                    .send(show_lint(name.clone(), findings))
+                    .expect("Must show lint");
+
+                Ok(None)
             }
-            OmniboxCommand::Bootstrap(dt) => bootstrap(name, dt, &plist.plist_path)
-                .map(|_| None)
-                .map_err(|e| OmniboxError::CommandError(e.to_string())),
-            OmniboxCommand::Bootout(dt) => bootout(name, dt)
-                .map(|_| None)
-                .map_err(|e| OmniboxError::CommandError(e.to_string())),
             _ => Ok(None),
         }
     }
@@ -274,8 +546,7 @@ impl ServiceListView {
         let need_escalate = status
             .clone()
             .plist
-            .map(|p| p.entry_location.into())
-            .map(|d: DomainType| d == DomainType::System)
+            .map(|p| p.entry_location != LaunchdEntryLocation::User)
             .unwrap_or(false);
 
         match cmd {
@@ -284,29 +555,29 @@ impl ServiceListView {
             | OmniboxCommand::ProcInfo
             | OmniboxCommand::BootstrapRequest
             | OmniboxCommand::BootoutRequest
-            | OmniboxCommand::Edit => {
-                if (sudo::check() != RunningAs::Root) && need_escalate {
-                    return Ok(Some(OmniboxCommand::Confirm(
-                        "This requires root privileges. Sudo and restart?".to_string(),
-                        vec![OmniboxCommand::Quit, OmniboxCommand::Sudo],
-                    )));
-                }
+            | OmniboxCommand::Kickstart
+            | OmniboxCommand::Restart
+            | OmniboxCommand::KillRequest
+            | OmniboxCommand::Revert
+            | OmniboxCommand::RevertTo(_)
+            | OmniboxCommand::Edit
+                if (sudo::check() != RunningAs::Root) && need_escalate =>
+            {
+                return Ok(Some(OmniboxCommand::Confirm(
+                    "This requires root privileges. Sudo and restart?".to_string(),
+                    vec![OmniboxCommand::Quit, OmniboxCommand::Sudo],
+                )));
             }
             _ => (),
         };
 
         match cmd {
             OmniboxCommand::Blame => {
-                let LaunchdEntryStatus { domain, .. } = status;
-                let response =
-                    blame(name, domain).map_err(|e| OmniboxError::CommandError(e.to_string()))?;
-                self.cb_sink
-                    .send(show_notice(
-                        response.to_string(),
-                        Some("Reason".to_string()),
-                    ))
-                    .unwrap();
-                Ok(None)
+                let target = status.domain.with_name(name);
+                self.spawn_command(
+                    move |t| blame(t, &target),
+                    |reason| show_notice(reason, Some("Reason".to_string())),
+                )
             }
             OmniboxCommand::BootstrapRequest => {
                 Ok(Some(OmniboxCommand::Bootstrap(status.domain)))
@@ -320,26 +591,57 @@ impl ServiceListView {
             OmniboxCommand::DisableRequest => {
                 Ok(Some(OmniboxCommand::Disable(status.domain)))
             }
-            OmniboxCommand::Enable(dt) => enable(name, dt)
-                .map(|_| None)
-                .map_err(|e| OmniboxError::CommandError(e.to_string())),
-            OmniboxCommand::Disable(dt) => disable(name, dt)
-                .map(|_| None)
-                .map_err(|e| OmniboxError::CommandError(e.to_string())),
-            OmniboxCommand::ProcInfo => {
+            OmniboxCommand::Enable(domain) => {
+                let target = domain.with_name(name);
+                self.spawn_command(move |t| enable(t, &target), |_| Box::new(Cursive::noop))
+            }
+            OmniboxCommand::Disable(domain) => {
+                let target = domain.with_name(name);
+                self.spawn_command(move |t| disable(t, &target), |_| Box::new(Cursive::noop))
+            }
+            OmniboxCommand::Kickstart => {
+                let target = status.domain.with_name(name);
+                self.spawn_command(
+                    move |t| kickstart(t, &target, KickstartMode::IfNotRunning),
+                    |_| Box::new(Cursive::noop),
+                )
+            }
+            OmniboxCommand::Restart => {
+                let target = status.domain.with_name(name);
+                self.spawn_command(move |t| restart(t, &target), |_| Box::new(Cursive::noop))
+            }
+            OmniboxCommand::KillRequest => {
                 if status.pid == 0 {
-                    return Err(OmniboxError::CommandError(format!("No PID for {}", name)));
+                    return Err(OmniboxError::CommandError(format!("{} is not running", name)));
                 }
-                let (size, shmem) =
-                    procinfo(status.pid).map_err(|e| OmniboxError::CommandError(e.to_string()))?;
-
-                show_pager(&self.cb_sink, unsafe {
-                    &*slice_from_raw_parts(shmem.region as *mut u8, size)
-                })
-                .map_err(OmniboxError::CommandError)?;
+                self.cb_sink
+                    .send(show_signal_picker(self.omnibox_tx.clone(), name))
+                    .expect("Must show signal picker");
 
                 Ok(None)
             }
+            OmniboxCommand::Kill(signal) => {
+                let target = status.domain.with_name(name);
+                self.spawn_command(move |t| kill(t, &target, signal), |_| Box::new(Cursive::noop))
+            }
+            OmniboxCommand::ProcInfo => {
+                if status.pid == 0 {
+                    return Err(OmniboxError::CommandError(format!("No PID for {}", name)));
+                }
+                let (pid, cb_sink) = (status.pid, self.cb_sink.clone());
+                self.spawn_command(
+                    move |t| procinfo(t, pid),
+                    move |raw| show_procinfo(cb_sink, raw),
+                )
+            }
+            OmniboxCommand::Print => {
+                let target = status.domain.with_name(name);
+                let cb_sink = self.cb_sink.clone();
+                self.spawn_command(
+                    move |t| print_service(t, &target),
+                    move |raw| show_service_info(cb_sink, raw),
+                )
+            }
             OmniboxCommand::CSRInfo => {
                 self.cb_sink
                     .send(show_csr_info())
@@ -348,30 +650,32 @@ impl ServiceListView {
                 Ok(None)
             }
             OmniboxCommand::DumpState => {
-                let (size, shmem) =
-                    dumpstate().map_err(|e| OmniboxError::CommandError(e.to_string()))?;
+                let label_filter = self
+                    .label_filter
+                    .read()
+                    .map_err(|_| OmniboxError::StateError)?
+                    .to_ascii_lowercase();
 
-                log::info!("shmem response sz {}", size);
+                self.spawn_pager(move |t| {
+                    let state = dumpstate(t)?;
+                    log::info!("shmem response sz {}", state.len());
 
-                show_pager(&self.cb_sink, unsafe {
-                    &*slice_from_raw_parts(shmem.region as *mut u8, size)
-                })
-                    .map_err(OmniboxError::CommandError)?;
+                    if label_filter.is_empty() {
+                        return Ok(state);
+                    }
 
-                Ok(None)
-            }
-            OmniboxCommand::DumpJetsamPropertiesCategory => {
-                let (size, shmem) =
-                    dumpjpcategory().map_err(|e| OmniboxError::CommandError(e.to_string()))?;
+                    // Only the services in the filtered list
+                    let filtered = parse_dumpstate(&String::from_utf8_lossy(&state))
+                        .filter_services(|s| s.name.to_ascii_lowercase().contains(&label_filter));
 
-                show_pager(&self.cb_sink, unsafe {
-                    &*slice_from_raw_parts(shmem.region as *mut u8, size)
+                    Ok(filtered.to_string().into_bytes())
                 })
-                    .map_err(OmniboxError::CommandError)?;
-
-                Ok(None)
             }
             OmniboxCommand::Edit
+            | OmniboxCommand::History
+            | OmniboxCommand::Revert
+            | OmniboxCommand::RevertTo(_)
+            | OmniboxCommand::Lint
             | OmniboxCommand::Bootout(_)
             | OmniboxCommand::Bootstrap(_) => self.handle_plist_command(cmd, item),
             _ => Ok(None),
@@ -381,14 +685,96 @@ impl ServiceListView {
     fn handle_general_command(&self, cmd: OmniboxCommand) -> OmniboxResult {
         match cmd {
             OmniboxCommand::DumpJetsamPropertiesCategory => {
-                let (size, shmem) =
-                    dumpjpcategory().map_err(|e| OmniboxError::CommandError(e.to_string()))?;
+                let running_jobs = self
+                    .state
+                    .read()
+                    .map_err(|_| OmniboxError::StateError)?
+                    .running_jobs
+                    .clone();
+                let label_filter = self
+                    .label_filter
+                    .read()
+                    .map_err(|_| OmniboxError::StateError)?
+                    .to_ascii_lowercase();
+                let cb_sink = self.cb_sink.clone();
 
-                show_pager(&self.cb_sink, unsafe {
-                    &*slice_from_raw_parts(shmem.region as *mut u8, size)
+                self.spawn_command(dumpjpcategory, move |raw| {
+                    show_jetsam(cb_sink, raw, running_jobs, label_filter)
                 })
+            }
+            OmniboxCommand::Domains => {
+                self.spawn_command(|t| print_domains(t, agent_uid()), show_domains)
+            }
+            OmniboxCommand::DomainFilter(target) => {
+                let mut domain_filter = self
+                    .domain_filter
+                    .try_write()
+                    .map_err(|_| OmniboxError::StateError)?;
+                *domain_filter = Some(target);
+                Ok(None)
+            }
+            OmniboxCommand::ClearDomainFilter => {
+                let mut domain_filter = self
+                    .domain_filter
+                    .try_write()
+                    .map_err(|_| OmniboxError::StateError)?;
+                *domain_filter = None;
+                Ok(None)
+            }
+            OmniboxCommand::EditAgain => {
+                let edited = self
+                    .pending_edit
+                    .lock()
+                    .map_err(|_| OmniboxError::StateError)?
+                    .as_mut()
+                    .ok_or_else(|| OmniboxError::CommandError("No edit to preview".to_string()))?
+                    .edit_again();
+
+                // Reinit curses
+                self.cb_sink
+                    .send(Box::new(|siv: &mut Cursive| siv.run()))
+                    .expect("Must clear");
+
+                // The last valid edit is kept, show why this one was not
+                self.preview_edit()?;
+                edited.map_err(OmniboxError::CommandError).map(|()| None)
+            }
+            OmniboxCommand::ApplyEdit(reload) => {
+                let edit = self
+                    .pending_edit
+                    .lock()
+                    .map_err(|_| OmniboxError::StateError)?
+                    .take()
+                    .ok_or_else(|| OmniboxError::CommandError("No edit to apply".to_string()))?;
+
+                let target = edit.target().clone();
+                let plist_path = edit.plist_path().to_string();
+
+                edit.apply(&History::user())
                     .map_err(OmniboxError::CommandError)?;
 
+                if !reload {
+                    return Ok(None);
+                }
+
+                self.spawn_command(
+                    move |t| {
+                        bootout(t, &target)?;
+                        bootstrap(t, &target, plist_path)
+                    },
+                    |_| Box::new(Cursive::noop),
+                )
+            }
+            OmniboxCommand::DiscardEdit => {
+                if let Some(edit) = self
+                    .pending_edit
+                    .lock()
+                    .map_err(|_| OmniboxError::StateError)?
+                    .take()
+                {
+                    edit.discard();
+                }
+
                 Ok(None)
             }
             _ => Ok(None)
@@ -421,6 +807,12 @@ impl OmniboxSubscriber for ServiceListView {
                 self.handle_state_update(state),
             OmniboxEvent::Command(
                 cmd @ OmniboxCommand::DumpJetsamPropertiesCategory
+                | cmd @ OmniboxCommand::Domains
+                | cmd @ OmniboxCommand::DomainFilter(..)
+                | cmd @ OmniboxCommand::ClearDomainFilter
+                | cmd @ OmniboxCommand::EditAgain
+                | cmd @ OmniboxCommand::ApplyEdit(_)
+                | cmd @ OmniboxCommand::DiscardEdit
             ) => self.handle_general_command(cmd),
             OmniboxEvent::Command(cmd) if active_item.is_some() =>
                 self.handle_item_command(cmd, active_item.unwrap()),
@@ -4,17 +4,38 @@ use cursive::utils::markup::StyledString;
 use cursive::views::{LinearLayout, ResizedView, TextView};
 
 use sudo::RunningAs;
-use xpc_sys::csr::{csr_check, CsrConfig};
-use xpc_sys::rs_sysctlbyname;
+use xpc_sys::csr::CsrConfig;
+#[cfg(target_os = "macos")]
+use xpc_sys::{csr::csr_check, rs_sysctlbyname};
+
+#[cfg(target_os = "macos")]
+fn sysctl(name: &str) -> String {
+    unsafe { rs_sysctlbyname(name).unwrap_or_default() }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn sysctl(_name: &str) -> String {
+    "".to_string()
+}
+
+/// csr_check() == 0, there is no SIP off macOS
+#[cfg(target_os = "macos")]
+pub fn csr_allows(mask: &CsrConfig) -> bool {
+    unsafe { csr_check(mask.bits()) == 0 }
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn csr_allows(_mask: &CsrConfig) -> bool {
+    false
+}
 
 pub fn make_layout() -> LinearLayout {
     let bold = Style::from(Color::Light(BaseColor::White)).combine(Effect::Bold);
     let mut layout = LinearLayout::horizontal();
 
     let mut macos = StyledString::styled("macOS: ", bold);
-    let osproductversion =
-        unsafe { rs_sysctlbyname("kern.osproductversion").unwrap_or("".to_string()) };
-    let osversion = unsafe { rs_sysctlbyname("kern.osversion").unwrap_or("".to_string()) };
+    let osproductversion = sysctl("kern.osproductversion");
+    let osversion = sysctl("kern.osversion");
     macos.append_plain(format!("{} ({})", osproductversion, osversion));
 
     if sudo::check() == RunningAs::Root {
@@ -25,13 +46,10 @@ pub fn make_layout() -> LinearLayout {
 
     // If granted CSR_ALLOW_UNTRUSTED_KEXTS, SIP is probably off
     let mut sip = StyledString::styled("SIP: ", bold);
-    let sip_data = unsafe {
-        format!(
-            "{}",
-            csr_check(CsrConfig::ALLOW_UNTRUSTED_KEXTS.bits()) != 0
-        )
-    };
-    sip.append_plain(sip_data);
+    sip.append_plain(format!(
+        "{}",
+        !csr_allows(&CsrConfig::ALLOW_UNTRUSTED_KEXTS)
+    ));
 
     layout.add_child(ResizedView::with_full_width(TextView::new(sip)));
 
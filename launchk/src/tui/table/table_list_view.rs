@@ -5,7 +5,9 @@ use std::collections::hash_map::DefaultHasher;
 use std::sync::{Arc, RwLock};
 
 use cursive::event::{Event, EventResult};
+use cursive::theme::Style;
 use cursive::traits::{Resizable, Scrollable};
+use cursive::utils::markup::StyledString;
 use cursive::view::ViewWrapper;
 use cursive::views::{LinearLayout, ResizedView, ScrollView, SelectView};
 use cursive::{Vec2, View};
@@ -15,6 +17,11 @@ use crate::tui::table::table_headers::TableHeaders;
 use super::column_sizer::ColumnSizer;
 pub trait TableListItem {
     fn as_row(&self) -> Vec<String>;
+
+    /// Style of the whole row, e.g. to highlight failures
+    fn style(&self) -> Style {
+        Style::none()
+    }
 }
 
 /// A "table" implemented on top of SelectView<T> where we
@@ -104,7 +111,10 @@ impl<T: 'static + TableListItem + Send + Sync> TableListView<T> {
         let current_selection = sv.selected_id().unwrap_or(0);
 
         sv.clear();
-        sv.add_all(rows);
+        sv.add_all(rows.into_iter().map(|(row, item)| {
+            let style = item.style();
+            (StyledString::styled(row, style), item)
+        }));
         sv.set_selection(current_selection);
     }
 
@@ -1,13 +1,24 @@
+#[cfg(target_os = "macos")]
 extern crate bindgen;
+#[cfg(target_os = "macos")]
 extern crate xcrun;
 
+#[cfg(target_os = "macos")]
 use std::env;
+#[cfg(target_os = "macos")]
 use std::path::PathBuf;
 
+#[cfg(target_os = "macos")]
 use xcrun::SDK;
 
+#[cfg(target_os = "macos")]
 static MACOS_INCLUDE_PATH: &str = "/usr/include";
 
+/// No SDK to generate bindings from, only the portable modules are built
+#[cfg(not(target_os = "macos"))]
+fn main() {}
+
+#[cfg(target_os = "macos")]
 fn main() {
     let sdk_path = xcrun::find_sdk(SDK::macOS(None))
         .and_then(|pb| pb.to_str().map(String::from))
@@ -0,0 +1,139 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::task::spawn_blocking;
+use tokio::time::timeout;
+
+use crate::api::transport::XPCTransport;
+use crate::object::xpc_error::XPCError;
+use crate::object::xpc_error::XPCError::{Cancelled, PipeRoutineError, TimeoutError};
+use crate::object::xpc_value::{XPCValue, XPCValueMap};
+
+/// Run a blocking routine call on tokio's blocking pool, so it does not
+/// hold up the executor.
+///
+/// Gives up with TimeoutError after `limit`, or Cancelled once `cancel`
+/// completes (pass `std::future::pending()` to never cancel). A routine
+/// that launchd never answers cannot be interrupted: the call keeps its
+/// blocking thread and the result is dropped.
+pub async fn spawn_routine<T, F, C>(routine: F, limit: Duration, cancel: C) -> Result<T, XPCError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+    C: Future<Output = ()>,
+{
+    let call = timeout(limit, spawn_blocking(routine));
+
+    tokio::select! {
+        result = call => match result {
+            Ok(Ok(t)) => Ok(t),
+            Ok(Err(e)) => Err(PipeRoutineError(format!("Routine did not finish: {}", e))),
+            Err(_) => Err(TimeoutError(format!("Timed out after {:?}", limit))),
+        },
+        _ = cancel => Err(Cancelled),
+    }
+}
+
+/// XPCTransport::pipe_interface_routine with spawn_routine. The request
+/// is returned with the reply, with any shmem the routine wrote into.
+pub async fn pipe_interface_routine_async<C: Future<Output = ()>>(
+    transport: Arc<dyn XPCTransport>,
+    routine: u64,
+    mut request: XPCValueMap,
+    limit: Duration,
+    cancel: C,
+) -> Result<(XPCValue, XPCValueMap), XPCError> {
+    let call = move || {
+        transport
+            .pipe_interface_routine(routine, &mut request)
+            .map(|reply| (reply, request))
+    };
+
+    spawn_routine(call, limit, cancel).await?
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::pending;
+    use std::sync::Arc;
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    use tokio::sync::oneshot;
+
+    use crate::api::dict_builder::DictBuilder;
+    use crate::api::transport::XPCTransport;
+    use crate::object::xpc_error::XPCError;
+    use crate::object::xpc_value::{XPCValue, XPCValueMap};
+
+    use super::{pipe_interface_routine_async, spawn_routine};
+
+    /// Sleeps, then writes into the shmem
+    struct Slow(Duration);
+
+    impl XPCTransport for Slow {
+        fn pipe_interface_routine(
+            &self,
+            routine: u64,
+            request: &mut XPCValueMap,
+        ) -> Result<XPCValue, XPCError> {
+            sleep(self.0);
+            request.insert("shmem".to_string(), XPCValue::Shmem(b"ok".to_vec().into()));
+            Ok(XPCValueMap::new().entry("routine", routine).into())
+        }
+    }
+
+    #[tokio::test]
+    async fn reply_and_request() {
+        let (reply, request) = pipe_interface_routine_async(
+            Arc::new(Slow(Duration::from_millis(0))),
+            834,
+            XPCValueMap::new().entry("shmem", XPCValue::shmem(2)),
+            Duration::from_secs(5),
+            pending(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(reply.get("routine"), Some(&XPCValue::UInt64(834)));
+        assert_eq!(
+            request.get("shmem"),
+            Some(&XPCValue::Shmem(b"ok".to_vec().into()))
+        );
+    }
+
+    #[tokio::test]
+    async fn timeout_is_distinct() {
+        let result = pipe_interface_routine_async(
+            Arc::new(Slow(Duration::from_millis(200))),
+            815,
+            XPCValueMap::new(),
+            Duration::from_millis(10),
+            pending(),
+        )
+        .await;
+
+        assert_eq!(
+            result,
+            Err(XPCError::TimeoutError("Timed out after 10ms".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn cancel() {
+        let (tx, rx) = oneshot::channel::<()>();
+        tx.send(()).unwrap();
+
+        let result = spawn_routine(
+            || sleep(Duration::from_millis(200)),
+            Duration::from_secs(5),
+            async {
+                rx.await.ok();
+            },
+        )
+        .await;
+
+        assert_eq!(result, Err(XPCError::Cancelled));
+    }
+}
@@ -1,37 +1,51 @@
-use crate::enums::{DomainType, SessionType};
-use crate::object::xpc_object::XPCObject;
-use crate::object::xpc_object::{MachPortType, XPCHashMap};
-use crate::{get_bootstrap_port, rs_geteuid};
-use mach2::port::mach_port_t;
-
-/// Builder methods for XPCHashMap
-pub trait DictBuilder {
+use crate::enums::{DomainType, MachPortType, SessionType};
+#[cfg(target_os = "macos")]
+use crate::get_bootstrap_port;
+use crate::mach_port_t;
+#[cfg(target_os = "macos")]
+use crate::object::xpc_object::{XPCHashMap, XPCObject};
+use crate::object::xpc_value::{XPCValue, XPCValueMap};
+use crate::rs_geteuid;
+use crate::service_target::ServiceTarget;
+#[cfg(target_os = "macos")]
+use std::sync::Arc;
+
+/// Builder methods for XPCHashMap and XPCValueMap
+pub trait DictBuilder: Sized {
+    /// What dictionary values are stored as
+    type Value: From<bool> + From<u64> + From<String> + From<(MachPortType, mach_port_t)>;
+
     /// Add entry
-    fn entry<S: Into<String>, O: Into<XPCObject>>(self, key: S, value: O) -> XPCHashMap;
+    fn entry<S: Into<String>, O: Into<Self::Value>>(self, key: S, value: O) -> Self;
 
     /// Add entry if option is Some()
-    fn entry_if_present<S: Into<String>, O: Into<XPCObject>>(
+    fn entry_if_present<S: Into<String>, O: Into<Self::Value>>(
         self,
         key: S,
         value: Option<O>,
-    ) -> XPCHashMap;
+    ) -> Self {
+        if let Some(v) = value {
+            self.entry(key, v)
+        } else {
+            self
+        }
+    }
 
     /// Add entry if option is Some()
-    fn entry_if<S: Into<String>, O: Into<XPCObject>>(
-        self,
-        pred: bool,
-        key: S,
-        value: O,
-    ) -> XPCHashMap;
+    fn entry_if<S: Into<String>, O: Into<Self::Value>>(self, pred: bool, key: S, value: O) -> Self {
+        if pred {
+            self.entry(key, value)
+        } else {
+            self
+        }
+    }
 
-    /// Extend an existing XPCHashMap
-    fn extend(self, other: &XPCHashMap) -> XPCHashMap;
+    /// Extend an existing map
+    fn extend(self, other: &Self) -> Self;
 
     /// Adds "domain_port" with get_bootstrap_port() -> _xpc_type_mach_send
-    fn with_domain_port_as_bootstrap_port(self) -> XPCHashMap
-    where
-        Self: Sized,
-    {
+    #[cfg(target_os = "macos")]
+    fn with_domain_port_as_bootstrap_port(self) -> Self {
         self.entry(
             "domain-port",
             (MachPortType::Send, unsafe {
@@ -41,33 +55,21 @@ pub trait DictBuilder {
     }
 
     /// Adds provided session type or falls back on Aqua
-    fn with_session_type_or_default(self, session: Option<SessionType>) -> XPCHashMap
-    where
-        Self: Sized,
-    {
+    fn with_session_type_or_default(self, session: Option<SessionType>) -> Self {
         self.entry("session", session.unwrap_or(SessionType::Aqua).to_string())
     }
 
     /// Adds provided handle or falls back on 0
-    fn with_handle_or_default(self, handle: Option<u64>) -> XPCHashMap
-    where
-        Self: Sized,
-    {
+    fn with_handle_or_default(self, handle: Option<u64>) -> Self {
         self.entry("handle", handle.unwrap_or(0))
     }
 
     /// Adds provided DomainType, falls back on 7 (requestor's domain)
-    fn with_domain_type_or_default(self, t: Option<DomainType>) -> XPCHashMap
-    where
-        Self: Sized,
-    {
+    fn with_domain_type_or_default(self, t: Option<DomainType>) -> Self {
         self.entry("type", t.unwrap_or(DomainType::RequestorDomain) as u64)
     }
 
-    fn handle_and_type_from_domain(self, t: DomainType) -> XPCHashMap
-    where
-        Self: Sized,
-    {
+    fn handle_and_type_from_domain(self, t: DomainType) -> Self {
         self
             // no handle for system
             .entry_if(t == DomainType::System, "handle", 0u64)
@@ -76,43 +78,77 @@ pub trait DictBuilder {
             .entry_if(t == DomainType::User, "handle", rs_geteuid() as u64)
             .entry_if(t == DomainType::User, "type", 8u64)
     }
+
+    /// Adds "handle", "type" and, if the target names a service, "name".
+    /// Unknown is not a launchd domain type, so it is left out.
+    fn with_service_target(self, target: &ServiceTarget) -> Self {
+        self.entry("handle", target.handle)
+            .entry_if(
+                target.domain != DomainType::Unknown,
+                "type",
+                target.domain.clone() as u64,
+            )
+            .entry_if_present("name", target.name.clone())
+    }
 }
 
+#[cfg(target_os = "macos")]
 impl DictBuilder for XPCHashMap {
+    type Value = XPCObject;
+
     fn entry<S: Into<String>, O: Into<XPCObject>>(mut self, key: S, value: O) -> XPCHashMap {
         let xpc_object: XPCObject = value.into();
         self.insert(key.into(), xpc_object.into());
         self
     }
 
-    fn entry_if_present<S: Into<String>, O: Into<XPCObject>>(
-        self,
-        key: S,
-        value: Option<O>,
-    ) -> XPCHashMap {
-        if let Some(v) = value {
-            self.entry(key, v)
-        } else {
-            self
-        }
+    fn extend(mut self, other: &XPCHashMap) -> XPCHashMap {
+        let m = &mut self;
+        m.extend(other.iter().map(|(s, o)| (s.clone(), Arc::clone(o))));
+        self
     }
+}
 
-    fn entry_if<S: Into<String>, O: Into<XPCObject>>(
-        self,
-        pred: bool,
-        key: S,
-        value: O,
-    ) -> XPCHashMap {
-        if pred {
-            self.entry(key, value)
-        } else {
-            self
-        }
+/// Same builder for owned values, so requests can be made without libxpc
+impl DictBuilder for XPCValueMap {
+    type Value = XPCValue;
+
+    fn entry<S: Into<String>, O: Into<XPCValue>>(mut self, key: S, value: O) -> XPCValueMap {
+        self.insert(key.into(), value.into());
+        self
     }
 
-    fn extend(mut self, other: &XPCHashMap) -> XPCHashMap {
+    fn extend(mut self, other: &XPCValueMap) -> XPCValueMap {
         let m = &mut self;
-        m.extend(other.iter().map(|(s, o)| (s.clone(), o.clone())));
+        m.extend(other.iter().map(|(s, v)| (s.clone(), v.clone())));
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::enums::DomainType;
+    use crate::object::xpc_value::{XPCValue, XPCValueMap};
+    use crate::service_target::ServiceTarget;
+
+    use super::DictBuilder;
+
+    #[test]
+    fn system_handle_and_type() {
+        let request = XPCValueMap::new().handle_and_type_from_domain(DomainType::System);
+
+        assert_eq!(request.get("handle"), Some(&XPCValue::UInt64(0)));
+        assert_eq!(request.get("type"), Some(&XPCValue::UInt64(1)));
+        assert!(XPCValueMap::new()
+            .handle_and_type_from_domain(DomainType::PID)
+            .is_empty());
+    }
+
+    #[test]
+    fn unknown_domain_has_no_type() {
+        let request =
+            XPCValueMap::new().with_service_target(&ServiceTarget::new(DomainType::Unknown, 0));
+
+        assert_eq!(request.get("type"), None);
+    }
+}
@@ -1,2 +1,5 @@
+#[cfg(feature = "tokio")]
+pub mod async_routine;
 pub mod dict_builder;
 pub mod pipe_routine;
+pub mod transport;
@@ -1,13 +1,20 @@
+#[cfg(target_os = "macos")]
 use crate::object::try_xpc_into_rust::TryXPCIntoRust;
 use crate::object::xpc_error::XPCError;
 use crate::object::xpc_error::XPCError::PipeRoutineError;
+#[cfg(target_os = "macos")]
 use crate::object::xpc_object::{XPCHashMap, XPCObject};
+use crate::object::xpc_value::XPCValue;
+use crate::rs_xpc_strerror;
+#[cfg(target_os = "macos")]
 use crate::{
-    _xpc_pipe_interface_routine, get_xpc_bootstrap_pipe, rs_xpc_strerror, xpc_object_t,
-    xpc_pipe_routine, xpc_pipe_routine_with_flags, xpc_pipe_t,
+    _xpc_pipe_interface_routine, get_xpc_bootstrap_pipe, xpc_object_t, xpc_pipe_routine,
+    xpc_pipe_routine_with_flags, xpc_pipe_t,
 };
+#[cfg(target_os = "macos")]
 use std::ptr::null_mut;
 
+#[cfg(target_os = "macos")]
 fn check_error(errno: i32) -> Result<(), XPCError> {
     if errno != 0 {
         Err(PipeRoutineError(rs_xpc_strerror(errno)))
@@ -16,6 +23,7 @@ fn check_error(errno: i32) -> Result<(), XPCError> {
     }
 }
 
+#[cfg(target_os = "macos")]
 pub fn pipe_routine<S: Into<XPCObject>>(
     xpc_pipe: Option<xpc_pipe_t>,
     dict: S,
@@ -33,6 +41,7 @@ pub fn pipe_routine<S: Into<XPCObject>>(
     }
 }
 
+#[cfg(target_os = "macos")]
 pub fn pipe_routine_with_flags<S: Into<XPCObject>>(
     xpc_pipe: Option<xpc_pipe_t>,
     dict: S,
@@ -53,6 +62,7 @@ pub fn pipe_routine_with_flags<S: Into<XPCObject>>(
     }
 }
 
+#[cfg(target_os = "macos")]
 pub fn pipe_interface_routine<S: Into<XPCObject>>(
     xpc_pipe: Option<xpc_pipe_t>,
     routine: u64,
@@ -81,6 +91,7 @@ pub fn pipe_interface_routine<S: Into<XPCObject>>(
     }
 }
 
+#[cfg(target_os = "macos")]
 pub fn handle_reply_dict_errors(reply: XPCObject) -> Result<XPCObject, XPCError> {
     let dict: XPCHashMap = reply.clone().to_rust()?;
 
@@ -112,3 +123,32 @@ pub fn handle_reply_dict_errors(reply: XPCObject) -> Result<XPCObject, XPCError>
         Ok(reply)
     }
 }
+
+/// handle_reply_dict_errors for an owned reply
+pub fn handle_reply_value_errors(reply: XPCValue) -> Result<XPCValue, XPCError> {
+    if let Some(errcode) = reply.get("error").and_then(|e| e.as_i64()) {
+        return Err(PipeRoutineError(format!(
+            "{}: {}",
+            errcode,
+            rs_xpc_strerror(errcode as i32)
+        )));
+    }
+
+    let errors: Vec<String> = reply
+        .get("errors")
+        .and_then(|e| e.as_dictionary())
+        .map(|errors_dict| {
+            errors_dict
+                .values()
+                .flat_map(|e| e.as_i64())
+                .map(|e_i64| format!("{}: {}", e_i64, rs_xpc_strerror(e_i64 as i32)))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if errors.is_empty() {
+        Ok(reply)
+    } else {
+        Err(PipeRoutineError(errors.join("\n")))
+    }
+}
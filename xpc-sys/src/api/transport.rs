@@ -0,0 +1,374 @@
+#[cfg(target_os = "macos")]
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(target_os = "macos")]
+use crate::api::pipe_routine::pipe_interface_routine;
+#[cfg(target_os = "macos")]
+use crate::object::try_xpc_into_rust::TryXPCIntoRust;
+use crate::object::xpc_error::XPCError;
+use crate::object::xpc_error::XPCError::{IOError, PipeRoutineError, ValueError};
+#[cfg(target_os = "macos")]
+use crate::object::xpc_object::XPCObject;
+use crate::object::xpc_value::{XPCFd, XPCValue, XPCValueMap};
+
+/// Something that answers launchd routines.
+///
+/// Requests and replies are owned values, so anything written against
+/// this trait can be driven by recorded fixtures instead of launchd.
+pub trait XPCTransport: Send + Sync {
+    /// Send `request` to `routine` and return the reply dictionary. Shmem
+    /// values in the request are in/out: afterwards they hold what the
+    /// routine wrote into them.
+    fn pipe_interface_routine(
+        &self,
+        routine: u64,
+        request: &mut XPCValueMap,
+    ) -> Result<XPCValue, XPCError>;
+}
+
+/// _xpc_pipe_interface_routine on the bootstrap pipe
+#[cfg(target_os = "macos")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BootstrapPipeTransport;
+
+#[cfg(target_os = "macos")]
+impl XPCTransport for BootstrapPipeTransport {
+    fn pipe_interface_routine(
+        &self,
+        routine: u64,
+        request: &mut XPCValueMap,
+    ) -> Result<XPCValue, XPCError> {
+        let object = XPCObject::try_from(XPCValue::Dictionary(request.clone()))?;
+
+        // Hand over the same xpc_object_t (not an xpc_copy) so we can
+        // read back any shmem the routine wrote into
+        let sent = unsafe { XPCObject::from_raw_retain(object.as_ptr()) };
+        let reply = pipe_interface_routine(None, routine, sent, None)?;
+
+        // Shmem comes back mapped in whole pages, keep the lengths we sent
+        let mut after: XPCValue = object.to_rust()?;
+        keep_shmem_lengths(&mut after, &XPCValue::Dictionary(request.clone()));
+
+        if let Some(after) = after.into_dictionary() {
+            *request = after;
+        }
+
+        reply.to_rust()
+    }
+}
+
+/// One call as written by RecordingTransport, one JSON object per line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedCall {
+    pub routine: u64,
+    /// The request after the call, so shmem holds what was written
+    pub request: XPCValueMap,
+    pub reply: Result<XPCValue, XPCError>,
+}
+
+/// Passes calls through to another transport and logs each one
+pub struct RecordingTransport<W: Write + Send> {
+    inner: Arc<dyn XPCTransport>,
+    out: Mutex<W>,
+}
+
+impl RecordingTransport<File> {
+    /// Record to a new file at `path`
+    pub fn create<P: AsRef<Path>>(inner: Arc<dyn XPCTransport>, path: P) -> Result<Self, XPCError> {
+        let file = File::create(path).map_err(|e| IOError(e.to_string()))?;
+        Ok(Self::new(inner, file))
+    }
+}
+
+impl<W: Write + Send> RecordingTransport<W> {
+    pub fn new(inner: Arc<dyn XPCTransport>, out: W) -> Self {
+        Self {
+            inner,
+            out: Mutex::new(out),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out.into_inner().expect("Must unlock")
+    }
+
+    fn write(&self, call: &RecordedCall) -> Result<(), XPCError> {
+        let mut out = self.out.lock().map_err(|e| IOError(e.to_string()))?;
+        let line = serde_json::to_string(call).map_err(|e| ValueError(e.to_string()))?;
+
+        writeln!(out, "{}", line)
+            .and_then(|_| out.flush())
+            .map_err(|e| IOError(e.to_string()))
+    }
+}
+
+impl<W: Write + Send> XPCTransport for RecordingTransport<W> {
+    fn pipe_interface_routine(
+        &self,
+        routine: u64,
+        request: &mut XPCValueMap,
+    ) -> Result<XPCValue, XPCError> {
+        let reply = self.inner.pipe_interface_routine(routine, request);
+
+        let call = RecordedCall {
+            routine,
+            request: request.clone(),
+            reply: reply.clone(),
+        };
+
+        if let Err(e) = self.write(&call) {
+            log::error!("Unable to record routine {}: {}", routine, e);
+        }
+
+        reply
+    }
+}
+
+/// Serves replies from recorded calls.
+///
+/// A request matches a recorded call with the same routine and request,
+/// ignoring shmem contents, mach port names and descriptors. Matching
+/// calls are served in recorded order, then the last one repeats (e.g.
+/// for polling).
+pub struct ReplayTransport {
+    calls: Vec<RecordedCall>,
+    served: Mutex<Vec<bool>>,
+}
+
+impl ReplayTransport {
+    pub fn new(calls: Vec<RecordedCall>) -> Self {
+        let served = vec![false; calls.len()];
+
+        Self {
+            calls,
+            served: Mutex::new(served),
+        }
+    }
+
+    /// Read calls from JSON lines, as written by RecordingTransport
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self, XPCError> {
+        let mut calls = vec![];
+
+        for (i, line) in reader.lines().enumerate() {
+            let line = line.map_err(|e| IOError(e.to_string()))?;
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let call: RecordedCall = serde_json::from_str(&line)
+                .map_err(|e| ValueError(format!("line {}: {}", i + 1, e)))?;
+
+            calls.push(call);
+        }
+
+        Ok(Self::new(calls))
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, XPCError> {
+        let file = File::open(path).map_err(|e| IOError(e.to_string()))?;
+        Self::from_reader(BufReader::new(file))
+    }
+}
+
+impl XPCTransport for ReplayTransport {
+    fn pipe_interface_routine(
+        &self,
+        routine: u64,
+        request: &mut XPCValueMap,
+    ) -> Result<XPCValue, XPCError> {
+        let wanted = without_buffers(&XPCValue::Dictionary(request.clone()));
+        let mut served = self.served.lock().map_err(|e| IOError(e.to_string()))?;
+
+        let matching: Vec<usize> = self
+            .calls
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| {
+                c.routine == routine
+                    && without_buffers(&XPCValue::Dictionary(c.request.clone())) == wanted
+            })
+            .map(|(i, _)| i)
+            .collect();
+
+        let index = matching
+            .iter()
+            .find(|i| !served[**i])
+            .or_else(|| matching.last())
+            .copied()
+            .ok_or_else(|| {
+                PipeRoutineError(format!("No recorded reply for routine {}", routine))
+            })?;
+
+        served[index] = true;
+
+        let call = &self.calls[index];
+        for (key, value) in request.iter_mut() {
+            if let Some(recorded) = call.request.get(key) {
+                copy_shmem(value, recorded);
+            }
+        }
+
+        call.reply.clone()
+    }
+}
+
+/// Blank out what changes between runs or is written by the routine
+fn without_buffers(value: &XPCValue) -> XPCValue {
+    match value {
+        XPCValue::Shmem(_) => XPCValue::shmem(0),
+        XPCValue::MachSend(_) => XPCValue::MachSend(0),
+        XPCValue::MachRecv(_) => XPCValue::MachRecv(0),
+        XPCValue::Fd(_) => XPCValue::Fd(XPCFd::default()),
+        XPCValue::Endpoint(_) => XPCValue::Endpoint(String::new()),
+        XPCValue::Array(a) => XPCValue::Array(a.iter().map(without_buffers).collect()),
+        XPCValue::Dictionary(d) => XPCValue::Dictionary(
+            d.iter()
+                .map(|(k, v)| (k.clone(), without_buffers(v)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// Truncate shmem in `value` to the length of the same shmem in `sent`
+#[cfg(target_os = "macos")]
+fn keep_shmem_lengths(value: &mut XPCValue, sent: &XPCValue) {
+    match (value, sent) {
+        (XPCValue::Shmem(after), XPCValue::Shmem(before)) => after.truncate(before.len()),
+        (XPCValue::Array(after), XPCValue::Array(before)) => {
+            for (a, b) in after.iter_mut().zip(before) {
+                keep_shmem_lengths(a, b);
+            }
+        }
+        (XPCValue::Dictionary(after), XPCValue::Dictionary(before)) => {
+            for (key, a) in after.iter_mut() {
+                if let Some(b) = before.get(key) {
+                    keep_shmem_lengths(a, b);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Write recorded shmem contents into the caller's buffers
+fn copy_shmem(value: &mut XPCValue, recorded: &XPCValue) {
+    match (value, recorded) {
+        (XPCValue::Shmem(dst), XPCValue::Shmem(src)) => dst.write(src.contents()),
+        (XPCValue::Array(dst), XPCValue::Array(src)) => {
+            for (d, s) in dst.iter_mut().zip(src) {
+                copy_shmem(d, s);
+            }
+        }
+        (XPCValue::Dictionary(dst), XPCValue::Dictionary(src)) => {
+            for (key, d) in dst.iter_mut() {
+                if let Some(s) = src.get(key) {
+                    copy_shmem(d, s);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    use crate::api::dict_builder::DictBuilder;
+    use crate::object::xpc_error::XPCError;
+    use crate::object::xpc_value::{XPCValue, XPCValueMap};
+
+    use super::{RecordingTransport, ReplayTransport, XPCTransport};
+
+    /// Writes "ok" into any shmem and replies with the routine number
+    struct Fake;
+
+    impl XPCTransport for Fake {
+        fn pipe_interface_routine(
+            &self,
+            routine: u64,
+            request: &mut XPCValueMap,
+        ) -> Result<XPCValue, XPCError> {
+            if let Some(XPCValue::Shmem(s)) = request.get_mut("shmem") {
+                s.write(b"ok");
+            }
+
+            Ok(XPCValueMap::new().entry("routine", routine).into())
+        }
+    }
+
+    fn request() -> XPCValueMap {
+        XPCValueMap::new()
+            .entry("handle", 0u64)
+            .entry("type", 1u64)
+            .entry("shmem", XPCValue::shmem(16))
+    }
+
+    #[test]
+    fn record_then_replay() {
+        let recording = RecordingTransport::new(Arc::new(Fake), vec![]);
+        recording
+            .pipe_interface_routine(834, &mut request())
+            .unwrap();
+
+        let replay = ReplayTransport::from_reader(Cursor::new(recording.into_inner())).unwrap();
+
+        let mut req = request();
+        let reply = replay.pipe_interface_routine(834, &mut req).unwrap();
+
+        assert_eq!(reply.get("routine"), Some(&XPCValue::UInt64(834)));
+        let mut written = vec![0; 16];
+        written[..2].copy_from_slice(b"ok");
+        assert_eq!(req.get("shmem"), Some(&XPCValue::Shmem(written.into())));
+    }
+
+    #[test]
+    fn replay_in_order_then_repeat() {
+        let calls = (0..2u64)
+            .map(|i| super::RecordedCall {
+                routine: 815,
+                request: request(),
+                reply: Ok(XPCValueMap::new().entry("n", i).into()),
+            })
+            .collect();
+
+        let replay = ReplayTransport::new(calls);
+        let n = |r: Result<XPCValue, XPCError>| r.unwrap().get("n").and_then(|n| n.as_u64());
+
+        assert_eq!(
+            n(replay.pipe_interface_routine(815, &mut request())),
+            Some(0)
+        );
+        assert_eq!(
+            n(replay.pipe_interface_routine(815, &mut request())),
+            Some(1)
+        );
+        assert_eq!(
+            n(replay.pipe_interface_routine(815, &mut request())),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn replay_unmatched() {
+        let replay = ReplayTransport::new(vec![]);
+        let mut req: XPCValueMap = HashMap::new();
+
+        assert_eq!(
+            replay.pipe_interface_routine(815, &mut req),
+            Err(XPCError::PipeRoutineError(
+                "No recorded reply for routine 815".to_string()
+            ))
+        );
+    }
+}
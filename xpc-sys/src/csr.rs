@@ -1,4 +1,6 @@
-use std::{fmt, fmt::Formatter, os::raw::c_int};
+#[cfg(target_os = "macos")]
+use std::os::raw::c_int;
+use std::{fmt, fmt::Formatter};
 
 pub type csr_config_t = u32;
 
@@ -46,6 +48,7 @@ impl fmt::Debug for CsrConfig {
     }
 }
 
+#[cfg(target_os = "macos")]
 extern "C" {
     /// 0 if has mask
     pub fn csr_check(mask: csr_config_t) -> c_int;
@@ -1,12 +1,12 @@
-use std::convert::TryFrom;
 use std::fmt;
-use std::sync::Arc;
+#[cfg(target_os = "macos")]
+use std::{convert::TryFrom, sync::Arc};
 
-use crate::object::try_xpc_into_rust::TryXPCIntoRust;
-use crate::object::xpc_error::XPCError;
-use crate::object::xpc_object::XPCObject;
-use crate::object::xpc_type;
-use crate::object::xpc_type::check_xpc_type;
+#[cfg(target_os = "macos")]
+use crate::object::{
+    try_xpc_into_rust::TryXPCIntoRust, xpc_error::XPCError, xpc_object::XPCObject, xpc_type,
+    xpc_type::check_xpc_type,
+};
 
 /// LimitLoadToSessionType key in XPC response
 /// https://developer.apple.com/library/archive/technotes/tn2083/_index.html
@@ -58,6 +58,7 @@ impl From<String> for SessionType {
     }
 }
 
+#[cfg(target_os = "macos")]
 impl TryFrom<Arc<XPCObject>> for SessionType {
     type Error = XPCError;
 
@@ -78,6 +79,7 @@ pub enum DomainType {
     PID = 5,
     RequestorUserDomain = 6,
     RequestorDomain = 7,
+    GUI = 8,
     Unknown,
 }
 
@@ -91,6 +93,7 @@ impl From<u64> for DomainType {
             5 => DomainType::PID,
             6 => DomainType::RequestorUserDomain,
             7 => DomainType::RequestorDomain,
+            8 => DomainType::GUI,
             _ => DomainType::Unknown,
         }
     }
@@ -101,3 +104,10 @@ impl fmt::Display for DomainType {
         write!(f, "{:?}", self)
     }
 }
+
+/// Enum used for selecting between _xpc_type_mach_send and _xpc_type_mach_recv
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum MachPortType {
+    Send,
+    Recv,
+}
@@ -4,37 +4,65 @@
 
 #[macro_use]
 extern crate bitflags;
+#[cfg(target_os = "macos")]
 #[macro_use]
 extern crate lazy_static;
 
 pub use libc::MAP_SHARED;
-use libc::{geteuid, strerror, sysctlbyname, KERN_SUCCESS, MACH_PORT_NULL};
+use libc::{geteuid, strerror};
+// The bindings have their own uid_t
+#[cfg(not(target_os = "macos"))]
+use libc::uid_t;
+#[cfg(target_os = "macos")]
+use libc::{sysctlbyname, KERN_SUCCESS, MACH_PORT_NULL};
+#[cfg(target_os = "macos")]
 use mach2::bootstrap::bootstrap_port;
+#[cfg(target_os = "macos")]
 use mach2::kern_return::kern_return_t;
+#[cfg(target_os = "macos")]
 use mach2::mach_port::mach_port_deallocate;
+#[cfg(target_os = "macos")]
 use mach2::message::mach_msg_type_number_t;
-use mach2::port::mach_port_t;
+#[cfg(target_os = "macos")]
+pub use mach2::port::mach_port_t;
+#[cfg(target_os = "macos")]
 use mach2::task::mach_ports_lookup;
+#[cfg(target_os = "macos")]
 use mach2::traps::mach_task_self;
-use std::ffi::{CStr, CString};
+use std::ffi::CStr;
+#[cfg(target_os = "macos")]
+use std::ffi::CString;
+#[cfg(target_os = "macos")]
 use std::os::raw::{c_char, c_int, c_long, c_void};
+#[cfg(target_os = "macos")]
 use std::ptr::null_mut;
 
+// Bindings are generated from the macOS SDK, see build.rs. Elsewhere only
+// the modules that do not call into libxpc are built.
+#[cfg(target_os = "macos")]
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
+/// Port names are still carried around in XPCValue off macOS
+#[cfg(not(target_os = "macos"))]
+pub type mach_port_t = u32;
+
 //
 pub mod api;
 pub mod csr;
 pub mod enums;
 pub mod object;
+pub mod service_target;
+pub mod xpc_serde;
 //
 
+#[cfg(target_os = "macos")]
 pub type xpc_pipe_t = *mut c_void;
 
 // Some extra private API definitions. Thanks:
 //
 // https://developer.apple.com/documentation/kernel/mach
 // https://chromium.googlesource.com/chromium/src.git/+/47.0.2507.2/sandbox/mac/xpc_private_stubs.sig
+#[cfg(target_os = "macos")]
 extern "C" {
     // Can decode i64 returned in "errors" for XPC responses
     pub fn xpc_strerror(err: c_int) -> *const c_char;
@@ -75,12 +103,14 @@ extern "C" {
     pub static _os_alloc_once_table: [_os_alloc_once_s; 10];
 }
 
+#[cfg(target_os = "macos")]
 #[repr(C)]
 pub struct _os_alloc_once_s {
     pub once: c_long,
     pub ptr: *mut c_void,
 }
 
+#[cfg(target_os = "macos")]
 #[repr(C)]
 pub struct xpc_global_data {
     pub a: u64,
@@ -89,6 +119,7 @@ pub struct xpc_global_data {
     pub xpc_bootstrap_pipe: xpc_pipe_t,
 }
 
+#[cfg(target_os = "macos")]
 pub fn rs_xpc_strerror(err: i32) -> String {
     unsafe {
         CStr::from_ptr(xpc_strerror(err))
@@ -97,11 +128,19 @@ pub fn rs_xpc_strerror(err: i32) -> String {
     }
 }
 
+/// There is no xpc_strerror off macOS, so launchd's own codes (>= 107)
+/// only get a generic description
+#[cfg(not(target_os = "macos"))]
+pub fn rs_xpc_strerror(err: i32) -> String {
+    rs_strerror(err)
+}
+
 pub fn rs_strerror(err: i32) -> String {
     unsafe { CStr::from_ptr(strerror(err)).to_string_lossy().to_string() }
 }
 
 /// Attempt to yield existing bootstrap_port if not MACH_PORT_NULL
+#[cfg(target_os = "macos")]
 pub unsafe fn get_bootstrap_port() -> mach_port_t {
     if bootstrap_port == MACH_PORT_NULL as mach_port_t {
         log::debug!("Bootstrap port is null! Querying for port");
@@ -113,6 +152,7 @@ pub unsafe fn get_bootstrap_port() -> mach_port_t {
 }
 
 /// Look up bootstrap port for mach_task_self
+#[cfg(target_os = "macos")]
 pub unsafe fn lookup_bootstrap_port() -> mach_port_t {
     let mut num_ports: mach_msg_type_number_t = 0;
     let mut found_ports: *mut mach_port_t = null_mut();
@@ -144,6 +184,7 @@ pub unsafe fn lookup_bootstrap_port() -> mach_port_t {
 }
 
 /// Get xpc global data bootstrap pipe or find bootstrap port + create new pipe
+#[cfg(target_os = "macos")]
 pub unsafe fn get_xpc_bootstrap_pipe() -> xpc_pipe_t {
     match read_xpc_global_data() {
         Some(xpcgd) => {
@@ -162,11 +203,13 @@ pub unsafe fn get_xpc_bootstrap_pipe() -> xpc_pipe_t {
     }
 }
 
+#[cfg(target_os = "macos")]
 pub unsafe fn read_xpc_global_data() -> Option<&'static xpc_global_data> {
     let gd: *mut xpc_global_data = _os_alloc_once_table[1].ptr as *mut _;
     gd.as_ref()
 }
 
+#[cfg(target_os = "macos")]
 pub unsafe fn rs_sysctlbyname(name: &str) -> Result<String, String> {
     let name = CString::new(name).unwrap();
     let mut ret_buf: [c_char; 256] = [0; 256];
@@ -1,10 +1,17 @@
 /// xpc_object_t
+#[cfg(target_os = "macos")]
 pub mod xpc_object;
 
 /// xpc_object_t -> xpc_type_t
+#[cfg(target_os = "macos")]
 pub mod xpc_type;
 
+#[cfg(target_os = "macos")]
 pub mod try_xpc_into_rust;
+#[cfg(target_os = "macos")]
 pub mod unix_fifo;
+pub mod xpc_description;
 pub mod xpc_error;
+#[cfg(target_os = "macos")]
 pub mod xpc_shmem;
+pub mod xpc_value;
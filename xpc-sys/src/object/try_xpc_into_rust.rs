@@ -3,18 +3,25 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 use std::ffi::CStr;
 use std::os::raw::c_char;
+use std::os::unix::prelude::{FromRawFd, OwnedFd};
 use std::rc::Rc;
+use std::slice::from_raw_parts;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use crate::object::xpc_object::{MachPortType, XPCHashMap, XPCObject};
+use crate::object::xpc_shmem::XPCShmem;
 use crate::object::xpc_type;
+use crate::object::xpc_value::{XPCFd, XPCShmemRegion, XPCUuid, XPCValue};
 use crate::{
-    object, rs_strerror, xpc_array_apply, xpc_bool_get_value, xpc_dictionary_apply,
-    xpc_double_get_value, xpc_int64_get_value, xpc_mach_send_get_right, xpc_object_t,
-    xpc_string_get_string_ptr, xpc_type_get_name, xpc_uint64_get_value,
+    _xpc_error_key_description, object, rs_strerror, xpc_array_apply, xpc_bool_get_value,
+    xpc_data_get_bytes_ptr, xpc_data_get_length, xpc_date_get_value, xpc_dictionary_apply,
+    xpc_dictionary_get_string, xpc_double_get_value, xpc_fd_dup, xpc_int64_get_value,
+    xpc_mach_send_get_right, xpc_object_t, xpc_string_get_string_ptr, xpc_type_get_name,
+    xpc_uint64_get_value, xpc_uuid_get_bytes,
 };
 
 use crate::object::xpc_error::XPCError;
-use crate::object::xpc_error::XPCError::{DictionaryError, ValueError};
+use crate::object::xpc_error::XPCError::{DictionaryError, PipeRoutineError, ValueError};
 use crate::object::xpc_type::check_xpc_type;
 use libc::__error;
 use mach2::port::mach_port_t;
@@ -62,6 +69,79 @@ impl TryXPCIntoRust<bool> for XPCObject {
     }
 }
 
+impl TryXPCIntoRust<Vec<u8>> for XPCObject {
+    fn to_rust(&self) -> Result<Vec<u8>, XPCError> {
+        check_xpc_type(self, &xpc_type::Data)?;
+
+        let bytes = unsafe {
+            let ptr = xpc_data_get_bytes_ptr(self.as_ptr()) as *const u8;
+            let len = xpc_data_get_length(self.as_ptr());
+
+            if ptr.is_null() {
+                vec![]
+            } else {
+                from_raw_parts(ptr, len).to_vec()
+            }
+        };
+
+        Ok(bytes)
+    }
+}
+
+impl TryXPCIntoRust<SystemTime> for XPCObject {
+    fn to_rust(&self) -> Result<SystemTime, XPCError> {
+        check_xpc_type(self, &xpc_type::Date)?;
+
+        // Nanoseconds since the epoch
+        let nanos = unsafe { xpc_date_get_value(self.as_ptr()) };
+        let offset = Duration::from_nanos(nanos.unsigned_abs());
+
+        Ok(if nanos >= 0 {
+            UNIX_EPOCH + offset
+        } else {
+            UNIX_EPOCH - offset
+        })
+    }
+}
+
+impl TryXPCIntoRust<XPCUuid> for XPCObject {
+    fn to_rust(&self) -> Result<XPCUuid, XPCError> {
+        check_xpc_type(self, &xpc_type::Uuid)?;
+
+        let mut uuid = [0u8; 16];
+        unsafe {
+            let ptr = xpc_uuid_get_bytes(self.as_ptr());
+            uuid.copy_from_slice(from_raw_parts(ptr, 16));
+        }
+
+        Ok(XPCUuid(uuid))
+    }
+}
+
+impl TryXPCIntoRust<()> for XPCObject {
+    fn to_rust(&self) -> Result<(), XPCError> {
+        check_xpc_type(self, &xpc_type::Null)
+    }
+}
+
+impl TryXPCIntoRust<XPCError> for XPCObject {
+    /// XPC error objects (e.g. XPC_ERROR_CONNECTION_INVALID) as a
+    /// PipeRoutineError with their description
+    fn to_rust(&self) -> Result<XPCError, XPCError> {
+        check_xpc_type(self, &xpc_type::Error)?;
+
+        let description =
+            unsafe { xpc_dictionary_get_string(self.as_ptr(), _xpc_error_key_description) };
+
+        if description.is_null() {
+            return Err(ValueError("XPC error has no description".to_string()));
+        }
+
+        let description = unsafe { CStr::from_ptr(description) };
+        Ok(PipeRoutineError(description.to_string_lossy().to_string()))
+    }
+}
+
 impl TryXPCIntoRust<(MachPortType, mach_port_t)> for XPCObject {
     fn to_rust(&self) -> Result<(MachPortType, mach_port_t), XPCError> {
         let types = [
@@ -158,6 +238,79 @@ impl TryXPCIntoRust<XPCHashMap> for XPCObject {
     }
 }
 
+impl TryXPCIntoRust<XPCValue> for XPCObject {
+    /// Deep copy the object into an owned XPCValue tree
+    fn to_rust(&self) -> Result<XPCValue, XPCError> {
+        let t = self.xpc_type();
+
+        let value = if t == *xpc_type::Int64 {
+            XPCValue::Int64(self.to_rust()?)
+        } else if t == *xpc_type::UInt64 {
+            XPCValue::UInt64(self.to_rust()?)
+        } else if t == *xpc_type::Double {
+            XPCValue::Double(self.to_rust()?)
+        } else if t == *xpc_type::Bool {
+            XPCValue::Bool(self.to_rust()?)
+        } else if t == *xpc_type::String {
+            XPCValue::String(self.to_rust()?)
+        } else if t == *xpc_type::Data {
+            XPCValue::Data(self.to_rust()?)
+        } else if t == *xpc_type::Date {
+            XPCValue::Date(self.to_rust()?)
+        } else if t == *xpc_type::Uuid {
+            let XPCUuid(uuid) = self.to_rust()?;
+            XPCValue::Uuid(uuid)
+        } else if t == *xpc_type::Array {
+            let array: Vec<Arc<XPCObject>> = self.to_rust()?;
+            XPCValue::Array(
+                array
+                    .iter()
+                    .map(|o| o.to_rust())
+                    .collect::<Result<Vec<XPCValue>, XPCError>>()?,
+            )
+        } else if t == *xpc_type::Dictionary {
+            let dict: XPCHashMap = self.to_rust()?;
+            XPCValue::Dictionary(
+                dict.iter()
+                    .map(|(k, o)| o.to_rust().map(|v| (k.clone(), v)))
+                    .collect::<Result<HashMap<String, XPCValue>, XPCError>>()?,
+            )
+        } else if t == *xpc_type::Fd {
+            let fd = unsafe { xpc_fd_dup(self.as_ptr()) };
+            if fd < 0 {
+                return Err(ValueError("xpc_fd_dup failed".to_string()));
+            }
+
+            XPCValue::Fd(XPCFd::from(unsafe { OwnedFd::from_raw_fd(fd) }))
+        } else if t == *xpc_type::MachSend || t == *xpc_type::MachRecv {
+            let (mpt, port): (MachPortType, mach_port_t) = self.to_rust()?;
+            match mpt {
+                MachPortType::Send => XPCValue::MachSend(port),
+                MachPortType::Recv => XPCValue::MachRecv(port),
+            }
+        } else if t == *xpc_type::Shmem {
+            let shmem = XPCShmem::from_xpc_object(self.clone());
+
+            if shmem.region.is_null() {
+                return Err(ValueError("Unable to map shmem".to_string()));
+            }
+
+            XPCValue::Shmem(XPCShmemRegion::mapped(shmem))
+        } else if t == *xpc_type::Endpoint {
+            XPCValue::Endpoint(self.to_string())
+        } else if t == *xpc_type::Error {
+            let error: XPCError = self.to_rust()?;
+            XPCValue::Error(error.to_string())
+        } else if t == *xpc_type::Null {
+            XPCValue::Null
+        } else {
+            return Err(ValueError(format!("Cannot get {} as XPCValue", t)));
+        };
+
+        Ok(value)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::object::try_xpc_into_rust::TryXPCIntoRust;
@@ -165,12 +318,19 @@ mod tests {
     use crate::object::xpc_error::XPCError::ValueError;
     use crate::object::xpc_object::XPCObject;
     use crate::object::xpc_object::{MachPortType, XPCHashMap};
-    use crate::{get_bootstrap_port, xpc_dictionary_create, xpc_dictionary_set_int64};
+    use crate::object::xpc_value::{XPCUuid, XPCValue};
+    use crate::{
+        _xpc_dictionary_s, _xpc_error_connection_invalid, get_bootstrap_port,
+        xpc_dictionary_create, xpc_dictionary_set_int64, xpc_object_t,
+    };
     use libc::mach_port_t;
 
+    use std::collections::HashMap;
+    use std::convert::TryFrom;
     use std::ffi::CString;
     use std::ptr::{null, null_mut};
     use std::sync::Arc;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
     #[test]
     fn xpc_to_rs_with_wrong_type() {
@@ -233,6 +393,64 @@ mod tests {
         // assert_eq!(42, port);
     }
 
+    #[test]
+    fn data_to_rust() {
+        let xpc_data = XPCObject::from(&[0xde_u8, 0xad, 0xbe, 0xef][..]);
+        let rs_data: Vec<u8> = xpc_data.to_rust().unwrap();
+        assert_eq!(rs_data, vec![0xde, 0xad, 0xbe, 0xef]);
+
+        let as_string: Result<String, XPCError> = xpc_data.to_rust();
+        assert_eq!(
+            as_string.err().unwrap(),
+            ValueError("Cannot get data as string".to_string())
+        );
+    }
+
+    #[test]
+    fn date_to_rust() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        let rs_time: SystemTime = XPCObject::try_from(time).unwrap().to_rust().unwrap();
+        assert_eq!(rs_time, time);
+
+        let too_late = UNIX_EPOCH + Duration::from_secs(u64::MAX / 2);
+        assert!(XPCObject::try_from(too_late).is_err());
+    }
+
+    #[test]
+    fn uuid_to_rust() {
+        let uuid = XPCUuid([7; 16]);
+        let rs_uuid: XPCUuid = XPCObject::from(uuid).to_rust().unwrap();
+        assert_eq!(rs_uuid, uuid);
+    }
+
+    #[test]
+    fn null_to_rust() {
+        let null: Result<(), XPCError> = XPCObject::from(()).to_rust();
+        assert!(null.is_ok());
+
+        let not_null: Result<(), XPCError> = XPCObject::from(1_u64).to_rust();
+        assert!(not_null.is_err());
+    }
+
+    #[test]
+    fn error_to_rust() {
+        let connection_invalid = unsafe {
+            XPCObject::from_raw_retain(
+                &_xpc_error_connection_invalid as *const _xpc_dictionary_s as xpc_object_t,
+            )
+        };
+
+        let err: XPCError = connection_invalid.to_rust().unwrap();
+        assert_eq!(
+            err,
+            XPCError::PipeRoutineError("Connection invalid".to_string())
+        );
+
+        // Also inside replies
+        let value: XPCValue = connection_invalid.to_rust().unwrap();
+        assert_eq!(value, XPCValue::Error("Connection invalid".to_string()));
+    }
+
     #[test]
     fn array_to_rust() {
         let xpc_array = XPCObject::from(vec!["eins", "zwei", "polizei"]);
@@ -267,4 +485,52 @@ mod tests {
             panic!("Unable to get value from map");
         }
     }
+
+    #[test]
+    fn xpc_value_round_trip() {
+        let mut service: HashMap<String, XPCValue> = HashMap::new();
+        service.insert("PID".to_string(), XPCValue::Int64(165));
+        service.insert("Label".to_string(), XPCValue::from("com.apple.usbmuxd"));
+        service.insert(
+            "ProgramArguments".to_string(),
+            XPCValue::from(vec!["/usr/libexec/usbmuxd", "-launchd"]),
+        );
+
+        let mut reply: HashMap<String, XPCValue> = HashMap::new();
+        reply.insert("service".to_string(), XPCValue::from(service));
+        reply.insert("handle".to_string(), XPCValue::UInt64(0));
+        reply.insert("legacy".to_string(), XPCValue::Bool(true));
+        reply.insert("ratio".to_string(), XPCValue::Double(0.36));
+        reply.insert(
+            "data".to_string(),
+            XPCValue::Data(vec![0xde, 0xad, 0xbe, 0xef]),
+        );
+        reply.insert(
+            "date".to_string(),
+            XPCValue::Date(UNIX_EPOCH + Duration::from_secs(1_600_000_000)),
+        );
+        reply.insert("uuid".to_string(), XPCValue::Uuid([7; 16]));
+        reply.insert("nothing".to_string(), XPCValue::Null);
+
+        let value = XPCValue::from(reply);
+        let object = XPCObject::try_from(value.clone()).unwrap();
+        let back: XPCValue = object.to_rust().unwrap();
+
+        assert_eq!(value, back);
+    }
+
+    #[test]
+    fn xpc_value_shmem_contents() {
+        let object = XPCObject::try_from(XPCValue::Shmem(vec![1, 2, 3].into())).unwrap();
+
+        // Mapped in whole pages
+        let mut back = match object.to_rust().unwrap() {
+            XPCValue::Shmem(region) => region,
+            other => panic!("Not shmem: {:?}", other),
+        };
+        assert!(back.len() >= 3);
+
+        back.truncate(3);
+        assert_eq!(back.to_vec(), vec![1, 2, 3]);
+    }
 }
@@ -0,0 +1,409 @@
+use std::collections::HashMap;
+
+use crate::object::xpc_error::XPCError;
+use crate::object::xpc_error::XPCError::ValueError;
+use crate::object::xpc_value::{XPCFd, XPCValue, XPCValueMap};
+
+/// Parse one xpc_copy_description() dump, e.g.
+///
+/// ```text
+/// <dictionary: 0x100704180> { count = 2, transaction: 0, voucher = 0x0, contents =
+///     "routine" => <uint64: 0x823dd3881d495785>: 828
+///     "name" => <string: 0x100404390> { length = 15, contents = "com.apple.lskdd" }
+/// }
+/// ```
+///
+/// Dumps copied out of lldb often lose their closing braces, so
+/// dictionaries and arrays also end at the end of input or at a line
+/// that is not one of their entries. Values that only mean something
+/// in the original process are replaced: fds have no descriptor and shmem
+/// becomes a zeroed buffer of the described size.
+pub fn parse_description(text: &str) -> Result<XPCValue, XPCError> {
+    let mut parser = DescriptionParser::new(text);
+    let value = parser.value()?;
+
+    parser.skip_whitespace();
+    if !parser.rest().is_empty() {
+        return Err(parser.error("trailing input"));
+    }
+
+    Ok(value)
+}
+
+/// Parse the entries of a dictionary dump without its `<dictionary: ...>`
+/// header, up to the end of input or an unmatched `}`
+pub fn parse_dictionary_contents(text: &str) -> Result<XPCValueMap, XPCError> {
+    DescriptionParser::new(text).dictionary_entries()
+}
+
+/// Find and parse every dictionary dump in a block of text, such as a
+/// pasted lldb session
+pub fn find_descriptions(text: &str) -> Result<Vec<XPCValue>, XPCError> {
+    let mut parser = DescriptionParser::new(text);
+    let mut found = vec![];
+
+    while let Some(offset) = parser.rest().find("<dictionary:") {
+        parser.pos += offset;
+        found.push(parser.value()?);
+    }
+
+    Ok(found)
+}
+
+struct DescriptionParser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> DescriptionParser<'a> {
+    fn new(text: &'a str) -> Self {
+        Self { text, pos: 0 }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn error(&self, msg: &str) -> XPCError {
+        self.error_at(self.pos, msg)
+    }
+
+    fn error_at(&self, pos: usize, msg: &str) -> XPCError {
+        let consumed = &self.text[..pos];
+        let line = consumed.matches('\n').count() + 1;
+        let column = consumed.len() - consumed.rfind('\n').map_or(0, |i| i + 1) + 1;
+
+        ValueError(format!(
+            "Cannot parse XPC description at {}:{}: {}",
+            line, column, msg
+        ))
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), XPCError> {
+        self.skip_whitespace();
+
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected {:?}", token)))
+        }
+    }
+
+    /// Consume up to (not including) `token`
+    fn take_until(&mut self, token: &str) -> Result<&'a str, XPCError> {
+        let rest = self.rest();
+        let end = rest
+            .find(token)
+            .ok_or_else(|| self.error(&format!("expected {:?}", token)))?;
+
+        self.pos += end;
+        Ok(&rest[..end])
+    }
+
+    /// Consume a `{ ... }` block on a single level, returning what is inside
+    fn braced(&mut self) -> Result<&'a str, XPCError> {
+        self.expect("{")?;
+        let inner = self.take_until("}")?;
+        self.pos += 1;
+        Ok(inner.trim())
+    }
+
+    /// Look up `key = value` in a `{ a = 1, b = 2 }` block
+    fn field<'b>(&self, block: &'b str, key: &str) -> Result<&'b str, XPCError> {
+        block
+            .split(',')
+            .filter_map(|kv| kv.split_once('='))
+            .find(|(k, _)| k.trim() == key)
+            .map(|(_, v)| v.trim())
+            .ok_or_else(|| self.error(&format!("missing {}", key)))
+    }
+
+    /// The value after `: ` up to the end of the line
+    fn scalar(&mut self) -> Result<&'a str, XPCError> {
+        self.expect(":")?;
+        let rest = self.rest();
+        let end = rest.find('\n').unwrap_or(rest.len());
+        self.pos += end;
+        Ok(rest[..end].trim())
+    }
+
+    /// Parse `s`, a slice of our input, reporting errors at its start
+    fn number<T: std::str::FromStr>(&self, s: &str) -> Result<T, XPCError> {
+        let pos = s.as_ptr() as usize - self.text.as_ptr() as usize;
+
+        s.parse()
+            .map_err(|_| self.error_at(pos, &format!("{:?} is not a number", s)))
+    }
+
+    fn value(&mut self) -> Result<XPCValue, XPCError> {
+        self.expect("<")?;
+        let header = self.take_until(">")?;
+        self.pos += 1;
+
+        let type_name = header.split(':').next().unwrap_or("").trim();
+
+        match type_name {
+            "dictionary" => {
+                self.skip_header()?;
+                self.dictionary_entries().map(XPCValue::Dictionary)
+            }
+            "array" => {
+                self.skip_header()?;
+                self.array_entries().map(XPCValue::Array)
+            }
+            "uint64" => {
+                let s = self.scalar()?;
+                self.number(s).map(XPCValue::UInt64)
+            }
+            "int64" => {
+                let s = self.scalar()?;
+                self.number(s).map(XPCValue::Int64)
+            }
+            "double" => {
+                let s = self.scalar()?;
+                self.number(s).map(XPCValue::Double)
+            }
+            "bool" => match self.scalar()? {
+                "true" => Ok(XPCValue::Bool(true)),
+                "false" => Ok(XPCValue::Bool(false)),
+                other => Err(self.error(&format!("{:?} is not a bool", other))),
+            },
+            "string" => self.string().map(XPCValue::String),
+            "null" => Ok(XPCValue::Null),
+            "fd" => {
+                // Not our descriptor, keep the type only
+                self.braced()?;
+                Ok(XPCValue::Fd(XPCFd::default()))
+            }
+            "mach send right" | "mach receive right" => {
+                let block = self.braced()?;
+                let name: u32 = self.number(self.field(block, "name")?)?;
+
+                if type_name == "mach send right" {
+                    Ok(XPCValue::MachSend(name))
+                } else {
+                    Ok(XPCValue::MachRecv(name))
+                }
+            }
+            "shmem" => {
+                // <shmem: 0x1005045e0>: 20971520 bytes (5121 pages)
+                let s = self.scalar()?;
+                let size = s.split_whitespace().next().unwrap_or("");
+                Ok(XPCValue::shmem(self.number(size)?))
+            }
+            other => Err(self.error(&format!("unsupported type {:?}", other))),
+        }
+    }
+
+    /// Skip `{ count = 5, transaction: 0, voucher = 0x0, contents =`
+    fn skip_header(&mut self) -> Result<(), XPCError> {
+        self.expect("{")?;
+        self.take_until("contents =")?;
+        self.pos += "contents =".len();
+        Ok(())
+    }
+
+    /// `{ length = 15, contents = "com.apple.lskdd" }`, reading exactly
+    /// `length` bytes so quotes inside the string are fine
+    fn string(&mut self) -> Result<String, XPCError> {
+        self.expect("{")?;
+        let header = self.take_until("contents =")?;
+        let length: usize = self.number(self.field(header, "length")?)?;
+        self.pos += "contents =".len();
+        self.expect("\"")?;
+
+        let contents = self
+            .rest()
+            .get(..length)
+            .filter(|c| self.rest()[c.len()..].starts_with("\" }"))
+            .ok_or_else(|| self.error("string length does not match contents"))?;
+
+        self.pos += length;
+        self.expect("\"")?;
+        self.expect("}")?;
+
+        Ok(contents.to_string())
+    }
+
+    fn dictionary_entries(&mut self) -> Result<XPCValueMap, XPCError> {
+        let mut dict: XPCValueMap = HashMap::new();
+
+        loop {
+            self.skip_whitespace();
+
+            if self.rest().starts_with('}') {
+                self.pos += 1;
+                break;
+            }
+
+            // Not an entry: the closing brace was lost
+            if !self.rest().starts_with('"') {
+                break;
+            }
+
+            self.pos += 1;
+            let key = self.take_until("\"")?;
+            self.pos += 1;
+            self.expect("=>")?;
+
+            let value = self.value()?;
+            dict.insert(key.to_string(), value);
+        }
+
+        Ok(dict)
+    }
+
+    fn array_entries(&mut self) -> Result<Vec<XPCValue>, XPCError> {
+        let mut array = vec![];
+
+        loop {
+            self.skip_whitespace();
+
+            if self.rest().starts_with('}') {
+                self.pos += 1;
+                break;
+            }
+
+            let index_len = self.rest().find(|c: char| !c.is_ascii_digit()).unwrap_or(0);
+            if index_len == 0 || !self.rest()[index_len..].starts_with(':') {
+                break;
+            }
+
+            self.pos += index_len + 1;
+            array.push(self.value()?);
+        }
+
+        Ok(array)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::object::xpc_value::{XPCFd, XPCValue};
+
+    use super::{find_descriptions, parse_description, parse_dictionary_contents};
+
+    const DOC: &str = include_str!("../../../doc/launchctl_messages.md");
+
+    #[test]
+    fn parse_every_doc_message() {
+        let messages = find_descriptions(DOC).unwrap();
+
+        assert_eq!(messages.len(), DOC.matches("<dictionary:").count());
+
+        // Everything but the Sockets dictionary in the headerless list reply
+        let routines: Vec<u64> = messages
+            .iter()
+            .filter_map(|m| m.get("routine").and_then(|r| r.as_u64()))
+            .collect();
+
+        assert_eq!(routines.len(), messages.len() - 1);
+        assert!(routines.contains(&803));
+        assert!(routines.contains(&822));
+    }
+
+    #[test]
+    fn doc_value_types() {
+        let messages = find_descriptions(DOC).unwrap();
+        let by_routine = |routine: u64| {
+            messages
+                .iter()
+                .find(|m| m.get("routine") == Some(&XPCValue::UInt64(routine)))
+                .unwrap()
+        };
+
+        let dumpstate = by_routine(834);
+        assert_eq!(
+            dumpstate.get("shmem").map(|s| match s {
+                XPCValue::Shmem(s) => s.len(),
+                _ => 0,
+            }),
+            Some(20971520)
+        );
+
+        let print = by_routine(708);
+        assert_eq!(print.get("fd"), Some(&XPCValue::Fd(XPCFd::default())));
+
+        let load = by_routine(800);
+        assert_eq!(load.get("domain-port"), Some(&XPCValue::MachSend(1799)));
+        assert_eq!(load.get("legacy"), Some(&XPCValue::Bool(true)));
+        assert_eq!(
+            load.get("paths"),
+            Some(&XPCValue::from(vec![
+                "/Users/mach/Library/LaunchAgents/homebrew.mxcl.elasticsearch.plist"
+            ]))
+        );
+
+        let port_query = by_routine(822);
+        assert_eq!(port_query.get("process"), Some(&XPCValue::Int64(7578)));
+    }
+
+    #[test]
+    fn headerless_list_reply() {
+        let start = DOC.find("Response:").unwrap();
+        let block = &DOC[start..];
+        let block = &block[block.find("```\n").unwrap() + 4..];
+        let block = &block[..block.find("```").unwrap()];
+
+        let service = parse_dictionary_contents(block).unwrap();
+
+        assert_eq!(service.get("PID"), Some(&XPCValue::Int64(165)));
+        assert_eq!(
+            service.get("Label"),
+            Some(&XPCValue::from("com.apple.usbmuxd"))
+        );
+        assert_eq!(
+            service
+                .get("Sockets")
+                .and_then(|s| s.get("Listeners"))
+                .and_then(|l| l.as_array())
+                .map(|l| l.len()),
+            Some(1)
+        );
+        assert_eq!(
+            service
+                .get("ProgramArguments")
+                .and_then(|a| a.as_array())
+                .map(|a| a.len()),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn closed_and_nested() {
+        let value = parse_description(
+            r#"<dictionary: 0x1> { count = 2, transaction: 0, voucher = 0x0, contents =
+	"quoted" => <string: 0x2> { length = 9, contents = "say "hi"!" }
+	"empty" => <array: 0x3> { count = 0, capacity = 0, contents =
+	}
+	"nothing" => <null: 0x4>
+	"ratio" => <double: 0x5>: 0.5
+}"#,
+        )
+        .unwrap();
+
+        assert_eq!(value.get("quoted"), Some(&XPCValue::from("say \"hi\"!")));
+        assert_eq!(value.get("empty"), Some(&XPCValue::Array(vec![])));
+        assert_eq!(value.get("nothing"), Some(&XPCValue::Null));
+        assert_eq!(value.get("ratio"), Some(&XPCValue::Double(0.5)));
+    }
+
+    #[test]
+    fn error_position() {
+        let err = parse_description(
+            "<dictionary: 0x1> { count = 1, transaction: 0, voucher = 0x0, contents =\n\t\"a\" => <uint64: 0x2>: nope",
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Cannot parse XPC description at 2:24: \"nope\" is not a number"
+        );
+    }
+}
@@ -1,13 +1,21 @@
-use crate::object::xpc_error::XPCError::{DictionaryError, IOError, PipeRoutineError, ValueError};
+use crate::object::xpc_error::XPCError::{
+    Cancelled, DictionaryError, IOError, PipeRoutineError, TimeoutError, ValueError,
+};
 use std::error::Error;
 use std::fmt::{Display, Formatter};
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum XPCError {
     DictionaryError(String),
     ValueError(String),
     PipeRoutineError(String),
     IOError(String),
+    /// Gave up waiting on a routine
+    TimeoutError(String),
+    /// Stopped waiting on a routine when asked to
+    Cancelled,
     NotFound,
 }
 
@@ -18,6 +26,8 @@ impl Display for XPCError {
             PipeRoutineError(e) => e,
             ValueError(e) => e,
             IOError(e) => e,
+            TimeoutError(e) => e,
+            Cancelled => "Cancelled",
             _ => "",
         };
 
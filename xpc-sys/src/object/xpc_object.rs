@@ -4,17 +4,25 @@ use std::collections::HashMap;
 use crate::object::xpc_type::XPCType;
 use crate::{
     xpc_array_append_value, xpc_array_create, xpc_bool_create, xpc_copy, xpc_copy_description,
-    xpc_dictionary_create, xpc_dictionary_set_value, xpc_double_create, xpc_fd_create,
-    xpc_int64_create, xpc_mach_recv_create, xpc_mach_send_create, xpc_object_t, xpc_release,
-    xpc_retain, xpc_string_create, xpc_uint64_create,
+    xpc_data_create, xpc_date_create, xpc_dictionary_create, xpc_dictionary_set_value,
+    xpc_double_create, xpc_fd_create, xpc_int64_create, xpc_mach_recv_create, xpc_mach_send_create,
+    xpc_null_create, xpc_object_t, xpc_release, xpc_retain, xpc_string_create, xpc_uint64_create,
+    xpc_uuid_create, MAP_SHARED,
 };
 use libc::mach_port_t;
+use std::convert::TryFrom;
 use std::ffi::{CStr, CString};
 use std::os::unix::prelude::RawFd;
 use std::ptr::{null, null_mut};
+use std::time::{SystemTime, UNIX_EPOCH};
 
+pub use crate::enums::MachPortType;
+use crate::object::xpc_error::XPCError;
+use crate::object::xpc_error::XPCError::ValueError;
+use crate::object::xpc_shmem::XPCShmem;
 use crate::object::xpc_type;
 use crate::object::xpc_type::check_xpc_type;
+use crate::object::xpc_value::{XPCUuid, XPCValue};
 use std::fmt;
 use std::sync::Arc;
 
@@ -157,13 +165,6 @@ impl From<f64> for XPCObject {
     }
 }
 
-/// Enum used for selecting between _xpc_type_mach_send and _xpc_type_mach_recv
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
-pub enum MachPortType {
-    Send,
-    Recv,
-}
-
 impl From<(MachPortType, mach_port_t)> for XPCObject {
     /// Create XPCObject via xpc_mach_send_create or xpc_mach_recv_create
     fn from((mpt, value): (MachPortType, mach_port_t)) -> Self {
@@ -213,6 +214,50 @@ impl From<String> for XPCObject {
     }
 }
 
+impl From<&[u8]> for XPCObject {
+    /// Create XPCObject via xpc_data_create
+    fn from(bytes: &[u8]) -> Self {
+        unsafe { XPCObject::new(xpc_data_create(bytes.as_ptr() as *const _, bytes.len())) }
+    }
+}
+
+impl From<Vec<u8>> for XPCObject {
+    /// Create XPCObject via xpc_data_create
+    fn from(bytes: Vec<u8>) -> Self {
+        bytes.as_slice().into()
+    }
+}
+
+impl TryFrom<SystemTime> for XPCObject {
+    type Error = XPCError;
+
+    /// Create XPCObject via xpc_date_create, which takes nanoseconds
+    /// since the epoch as an i64 (about 292 years either way)
+    fn try_from(time: SystemTime) -> Result<Self, XPCError> {
+        let nanos = match time.duration_since(UNIX_EPOCH) {
+            Ok(after) => i64::try_from(after.as_nanos()).ok(),
+            Err(before) => i64::try_from(before.duration().as_nanos()).ok().map(|n| -n),
+        }
+        .ok_or_else(|| ValueError(format!("{:?} is out of range for a date", time)))?;
+
+        Ok(unsafe { XPCObject::new(xpc_date_create(nanos)) })
+    }
+}
+
+impl From<XPCUuid> for XPCObject {
+    /// Create XPCObject via xpc_uuid_create
+    fn from(XPCUuid(uuid): XPCUuid) -> Self {
+        unsafe { XPCObject::new(xpc_uuid_create(uuid.as_ptr())) }
+    }
+}
+
+impl From<()> for XPCObject {
+    /// Create XPCObject via xpc_null_create
+    fn from(_: ()) -> Self {
+        unsafe { XPCObject::new(xpc_null_create()) }
+    }
+}
+
 impl<R: AsRef<XPCObject>> From<R> for XPCObject {
     /// Use xpc_copy() to copy out of refs.
     /// https://developer.apple.com/documentation/xpc/1505584-xpc_copy?language=objc
@@ -244,6 +289,66 @@ where
     }
 }
 
+impl TryFrom<XPCValue> for XPCObject {
+    type Error = XPCError;
+
+    /// Build a new XPC object tree from an owned XPCValue
+    fn try_from(value: XPCValue) -> Result<Self, XPCError> {
+        let value_type = value.xpc_type_name();
+        let object: XPCObject = match value {
+            XPCValue::Int64(i) => i.into(),
+            XPCValue::UInt64(u) => u.into(),
+            XPCValue::Double(d) => d.into(),
+            XPCValue::Bool(b) => b.into(),
+            XPCValue::String(s) => s.into(),
+            XPCValue::Data(bytes) => bytes.into(),
+            XPCValue::Date(time) => XPCObject::try_from(time)?,
+            XPCValue::Uuid(uuid) => XPCUuid(uuid).into(),
+            XPCValue::Array(values) => values
+                .into_iter()
+                .map(XPCObject::try_from)
+                .collect::<Result<Vec<XPCObject>, XPCError>>()?
+                .into(),
+            XPCValue::Dictionary(map) => map
+                .into_iter()
+                .map(|(k, v)| XPCObject::try_from(v).map(|o| (k, Arc::new(o))))
+                .collect::<Result<XPCHashMap, XPCError>>()?
+                .into(),
+            // xpc_fd_create dups, the value keeps its own descriptor
+            XPCValue::Fd(fd) => match fd.as_raw_fd() {
+                Some(fd) => unsafe { XPCObject::from_raw_fd(fd) },
+                None => return Err(ValueError("No descriptor to send".to_string())),
+            },
+            XPCValue::MachSend(port) => (MachPortType::Send, port).into(),
+            XPCValue::MachRecv(port) => (MachPortType::Recv, port).into(),
+            XPCValue::Shmem(region) => {
+                // xpc_shmem_create makes a memory entry for the region, so it
+                // stays valid after XPCShmem deallocates our mapping
+                let shmem = XPCShmem::allocate_task_self(region.len().max(1) as u64, MAP_SHARED)?;
+
+                // New pages are zeroed, only copy what was written
+                let contents = region.contents();
+                unsafe {
+                    shmem
+                        .region
+                        .copy_from(contents.as_ptr() as *const _, contents.len());
+                }
+
+                XPCObject::from(&shmem)
+            }
+            XPCValue::Endpoint(_) | XPCValue::Error(_) => {
+                return Err(ValueError(format!(
+                    "Cannot make {} from its description",
+                    value_type
+                )))
+            }
+            XPCValue::Null => ().into(),
+        };
+
+        Ok(object)
+    }
+}
+
 impl Drop for XPCObject {
     /// Release XPC object when dropped
     /// https://developer.apple.com/documentation/xpc/1505851-xpc_release
@@ -285,8 +390,8 @@ mod tests {
 
     use crate::{get_bootstrap_port, xpc_dictionary_get_string};
 
-    use super::XPCObject;
     use super::MachPortType;
+    use super::XPCObject;
 
     // Mostly for docs, int, uint, bool segfault here
     #[test]
@@ -21,6 +21,7 @@ pub struct XPCShmem {
 }
 
 unsafe impl Send for XPCShmem {}
+unsafe impl Sync for XPCShmem {}
 
 impl XPCShmem {
     pub fn from_xpc_object(value: XPCObject) -> XPCShmem {
@@ -152,7 +153,6 @@ mod tests {
     };
     use block::ConcreteBlock;
     use libc::MAP_SHARED;
-    use std::collections::HashMap;
 
     use crate::object::try_xpc_into_rust::TryXPCIntoRust;
     use std::ffi::c_void;
@@ -221,7 +221,7 @@ mod tests {
         });
 
         // Send XPC dictionary with a shmem field
-        let dict: XPCObject = HashMap::new().entry("shmem", &shmem.xpc_object).into();
+        let dict: XPCObject = XPCHashMap::new().entry("shmem", &shmem.xpc_object).into();
 
         unsafe {
             xpc_connection_send_message(endpoint_peer, xpc_retain(dict.as_ptr()));
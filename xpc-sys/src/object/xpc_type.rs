@@ -1,7 +1,8 @@
 use crate::{
-    _xpc_type_array, _xpc_type_bool, _xpc_type_dictionary, _xpc_type_double, _xpc_type_fd,
-    _xpc_type_int64, _xpc_type_mach_recv, _xpc_type_mach_send, _xpc_type_null, _xpc_type_s,
-    _xpc_type_shmem, _xpc_type_string, _xpc_type_uint64, xpc_get_type, xpc_object_t,
+    _xpc_type_array, _xpc_type_bool, _xpc_type_data, _xpc_type_date, _xpc_type_dictionary,
+    _xpc_type_double, _xpc_type_endpoint, _xpc_type_error, _xpc_type_fd, _xpc_type_int64,
+    _xpc_type_mach_recv, _xpc_type_mach_send, _xpc_type_null, _xpc_type_s, _xpc_type_shmem,
+    _xpc_type_string, _xpc_type_uint64, _xpc_type_uuid, xpc_get_type, xpc_object_t,
     xpc_type_get_name, xpc_type_t,
 };
 
@@ -76,6 +77,7 @@ impl fmt::Display for XPCType {
    pub static _xpc_type_array: _xpc_type_s;
    pub static _xpc_type_dictionary: _xpc_type_s;
    pub static _xpc_type_error: _xpc_type_s;
+   pub static _xpc_type_endpoint: _xpc_type_s;
 */
 
 lazy_static! {
@@ -94,6 +96,12 @@ lazy_static! {
     pub static ref Fd: XPCType = unsafe { (&_xpc_type_fd as *const _xpc_type_s).into() };
     pub static ref Shmem: XPCType = unsafe { (&_xpc_type_shmem as *const _xpc_type_s).into() };
     pub static ref Null: XPCType = unsafe { (&_xpc_type_null as *const _xpc_type_s).into() };
+    pub static ref Data: XPCType = unsafe { (&_xpc_type_data as *const _xpc_type_s).into() };
+    pub static ref Date: XPCType = unsafe { (&_xpc_type_date as *const _xpc_type_s).into() };
+    pub static ref Uuid: XPCType = unsafe { (&_xpc_type_uuid as *const _xpc_type_s).into() };
+    pub static ref Error: XPCType = unsafe { (&_xpc_type_error as *const _xpc_type_s).into() };
+    pub static ref Endpoint: XPCType =
+        unsafe { (&_xpc_type_endpoint as *const _xpc_type_s).into() };
 }
 
 /// Runtime type check for XPC object.
@@ -0,0 +1,612 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::os::unix::prelude::{AsRawFd, OwnedFd, RawFd};
+#[cfg(target_os = "macos")]
+use std::slice::from_raw_parts;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::enums::MachPortType;
+use crate::mach_port_t;
+#[cfg(target_os = "macos")]
+use crate::object::xpc_shmem::XPCShmem;
+
+/// Owned XPC dictionary contents
+pub type XPCValueMap = HashMap<String, XPCValue>;
+
+/// An owned, pure Rust copy of an XPC object tree. Unlike XPCObject
+/// nothing here points into libxpc, so values can be built, compared
+/// and inspected without a live xpc_object_t.
+///
+/// The serde derives are a tagged form (`{"Int64": 1}`) for keeping
+/// fixtures on disk. To map structs onto dictionaries use xpc_serde.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum XPCValue {
+    Int64(i64),
+    UInt64(u64),
+    Double(f64),
+    Bool(bool),
+    String(String),
+    Data(Vec<u8>),
+    Date(SystemTime),
+    Uuid([u8; 16]),
+    Array(Vec<XPCValue>),
+    Dictionary(XPCValueMap),
+    /// Descriptor owned by the value (from xpc_fd_dup)
+    Fd(XPCFd),
+    /// mach_port_t send right
+    MachSend(u32),
+    /// mach_port_t receive right
+    MachRecv(u32),
+    /// Shmem region, mapped rather than copied
+    Shmem(XPCShmemRegion),
+    /// Description of an endpoint, which cannot be copied out
+    Endpoint(String),
+    /// Description of an error object, e.g. XPC_ERROR_CONNECTION_INVALID
+    Error(String),
+    Null,
+}
+
+impl XPCValue {
+    /// Zeroed shmem region for a routine to write into, allocated only
+    /// when sent
+    pub fn shmem(size: usize) -> Self {
+        XPCValue::Shmem(XPCShmemRegion::new(size))
+    }
+
+    /// Name of the XPC type, as xpc_type_get_name() would print it
+    pub fn xpc_type_name(&self) -> &'static str {
+        match self {
+            XPCValue::Int64(_) => "int64",
+            XPCValue::UInt64(_) => "uint64",
+            XPCValue::Double(_) => "double",
+            XPCValue::Bool(_) => "bool",
+            XPCValue::String(_) => "string",
+            XPCValue::Data(_) => "data",
+            XPCValue::Date(_) => "date",
+            XPCValue::Uuid(_) => "uuid",
+            XPCValue::Array(_) => "array",
+            XPCValue::Dictionary(_) => "dictionary",
+            XPCValue::Fd(_) => "fd",
+            XPCValue::MachSend(_) => "mach_send",
+            XPCValue::MachRecv(_) => "mach_recv",
+            XPCValue::Shmem(_) => "shmem",
+            XPCValue::Endpoint(_) => "endpoint",
+            XPCValue::Error(_) => "error",
+            XPCValue::Null => "null",
+        }
+    }
+
+    /// Look up a key if this is a dictionary
+    pub fn get(&self, key: &str) -> Option<&XPCValue> {
+        self.as_dictionary().and_then(|d| d.get(key))
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            XPCValue::Int64(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            XPCValue::UInt64(u) => Some(*u),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            XPCValue::Double(d) => Some(*d),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            XPCValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            XPCValue::String(s) => Some(s.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Bytes of data, or the contents of shmem (see XPCShmemRegion)
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            XPCValue::Data(b) => Some(b.as_slice()),
+            XPCValue::Shmem(s) => Some(s.contents()),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<XPCValue>> {
+        match self {
+            XPCValue::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    pub fn as_dictionary(&self) -> Option<&XPCValueMap> {
+        match self {
+            XPCValue::Dictionary(d) => Some(d),
+            _ => None,
+        }
+    }
+
+    pub fn into_dictionary(self) -> Option<XPCValueMap> {
+        match self {
+            XPCValue::Dictionary(d) => Some(d),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        *self == XPCValue::Null
+    }
+}
+
+impl From<i64> for XPCValue {
+    fn from(value: i64) -> Self {
+        XPCValue::Int64(value)
+    }
+}
+
+impl From<i32> for XPCValue {
+    fn from(value: i32) -> Self {
+        XPCValue::Int64(value as i64)
+    }
+}
+
+impl From<u64> for XPCValue {
+    fn from(value: u64) -> Self {
+        XPCValue::UInt64(value)
+    }
+}
+
+impl From<u32> for XPCValue {
+    fn from(value: u32) -> Self {
+        XPCValue::UInt64(value as u64)
+    }
+}
+
+impl From<f64> for XPCValue {
+    fn from(value: f64) -> Self {
+        XPCValue::Double(value)
+    }
+}
+
+impl From<bool> for XPCValue {
+    fn from(value: bool) -> Self {
+        XPCValue::Bool(value)
+    }
+}
+
+impl From<&str> for XPCValue {
+    fn from(value: &str) -> Self {
+        XPCValue::String(value.to_string())
+    }
+}
+
+impl From<String> for XPCValue {
+    fn from(value: String) -> Self {
+        XPCValue::String(value)
+    }
+}
+
+impl From<SystemTime> for XPCValue {
+    fn from(value: SystemTime) -> Self {
+        XPCValue::Date(value)
+    }
+}
+
+impl From<Vec<u8>> for XPCValue {
+    fn from(value: Vec<u8>) -> Self {
+        XPCValue::Data(value)
+    }
+}
+
+impl From<&[u8]> for XPCValue {
+    fn from(value: &[u8]) -> Self {
+        XPCValue::Data(value.to_vec())
+    }
+}
+
+impl From<XPCUuid> for XPCValue {
+    fn from(XPCUuid(uuid): XPCUuid) -> Self {
+        XPCValue::Uuid(uuid)
+    }
+}
+
+impl From<()> for XPCValue {
+    fn from(_: ()) -> Self {
+        XPCValue::Null
+    }
+}
+
+impl From<(MachPortType, mach_port_t)> for XPCValue {
+    fn from((mpt, port): (MachPortType, mach_port_t)) -> Self {
+        match mpt {
+            MachPortType::Send => XPCValue::MachSend(port),
+            MachPortType::Recv => XPCValue::MachRecv(port),
+        }
+    }
+}
+
+impl<V: Into<XPCValue>> From<Vec<V>> for XPCValue {
+    fn from(value: Vec<V>) -> Self {
+        XPCValue::Array(value.into_iter().map(|v| v.into()).collect())
+    }
+}
+
+impl<S: Into<String>> From<HashMap<S, XPCValue>> for XPCValue {
+    fn from(value: HashMap<S, XPCValue>) -> Self {
+        XPCValue::Dictionary(value.into_iter().map(|(k, v)| (k.into(), v)).collect())
+    }
+}
+
+/// A descriptor from xpc_fd_dup, closed when the last clone is dropped.
+/// Fixtures only keep the number, so read back there is no descriptor.
+#[derive(Debug, Clone, Default)]
+pub struct XPCFd(Option<Arc<OwnedFd>>);
+
+impl XPCFd {
+    pub fn as_raw_fd(&self) -> Option<RawFd> {
+        self.0.as_ref().map(|fd| fd.as_raw_fd())
+    }
+}
+
+impl From<OwnedFd> for XPCFd {
+    fn from(fd: OwnedFd) -> Self {
+        XPCFd(Some(Arc::new(fd)))
+    }
+}
+
+impl PartialEq for XPCFd {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_raw_fd() == other.as_raw_fd()
+    }
+}
+
+impl Serialize for XPCFd {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i32(self.as_raw_fd().unwrap_or(-1))
+    }
+}
+
+impl<'de> Deserialize<'de> for XPCFd {
+    /// Never take ownership of a number from a file
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        RawFd::deserialize(deserializer).map(|_| XPCFd::default())
+    }
+}
+
+/// Bytes of an _xpc_type_uuid
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct XPCUuid(pub [u8; 16]);
+
+impl fmt::Display for XPCUuid {
+    /// Hyphenated and uppercase, like uuidgen
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, b) in self.0.iter().enumerate() {
+            if matches!(i, 4 | 6 | 8 | 10) {
+                write!(f, "-")?;
+            }
+
+            write!(f, "{:02X}", b)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Contents of an _xpc_type_shmem.
+///
+/// Regions made here only hold the bytes written into them, the rest is
+/// zeros. Regions read from an XPC object are mapped with xpc_shmem_map,
+/// whole pages long until truncated to the length they were made with.
+/// Clones of a mapped region share the mapping.
+#[derive(Clone)]
+pub struct XPCShmemRegion {
+    len: usize,
+    bytes: ShmemBytes,
+}
+
+#[derive(Clone)]
+enum ShmemBytes {
+    /// Up to the last byte written
+    Owned(Vec<u8>),
+    #[cfg(target_os = "macos")]
+    Mapped(Arc<XPCShmem>),
+}
+
+impl XPCShmemRegion {
+    /// Zeroed region, nothing is allocated until it is written or sent
+    pub fn new(len: usize) -> Self {
+        Self {
+            len,
+            bytes: ShmemBytes::Owned(vec![]),
+        }
+    }
+
+    /// Region of a mapped shmem object, as long as the mapping
+    #[cfg(target_os = "macos")]
+    pub(crate) fn mapped(shmem: XPCShmem) -> Self {
+        Self {
+            len: shmem.size as usize,
+            bytes: ShmemBytes::Mapped(Arc::new(shmem)),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Shorten the region, e.g. to the length it was made with
+    pub fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+
+        match &mut self.bytes {
+            ShmemBytes::Owned(bytes) => bytes.truncate(len),
+            #[cfg(target_os = "macos")]
+            ShmemBytes::Mapped(_) => {}
+        }
+    }
+
+    /// Bytes that are not known to be zero: up to the last one written,
+    /// or all of a mapped region
+    pub fn contents(&self) -> &[u8] {
+        match &self.bytes {
+            ShmemBytes::Owned(bytes) => bytes,
+            #[cfg(target_os = "macos")]
+            ShmemBytes::Mapped(shmem) => unsafe {
+                from_raw_parts(shmem.region as *const u8, self.len)
+            },
+        }
+    }
+
+    /// Copy out the first `len` bytes, or all of them if it is shorter
+    pub fn read(&self, len: usize) -> Vec<u8> {
+        let len = len.min(self.len);
+        let contents = self.contents();
+
+        let mut bytes = contents[..len.min(contents.len())].to_vec();
+        bytes.resize(len, 0);
+        bytes
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.read(self.len)
+    }
+
+    /// Write `bytes` at the start of the region, as many as fit
+    pub fn write(&mut self, bytes: &[u8]) {
+        let n = bytes.len().min(self.len);
+
+        match &mut self.bytes {
+            ShmemBytes::Owned(owned) => {
+                if owned.len() < n {
+                    owned.resize(n, 0);
+                }
+
+                owned[..n].copy_from_slice(&bytes[..n]);
+            }
+            #[cfg(target_os = "macos")]
+            ShmemBytes::Mapped(shmem) => unsafe {
+                shmem.region.copy_from(bytes.as_ptr() as *const _, n);
+            },
+        }
+    }
+
+    /// contents() without the zeros at the end
+    fn written(&self) -> &[u8] {
+        let contents = self.contents();
+        let written = contents.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+        &contents[..written]
+    }
+}
+
+impl From<Vec<u8>> for XPCShmemRegion {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self {
+            len: bytes.len(),
+            bytes: ShmemBytes::Owned(bytes),
+        }
+    }
+}
+
+impl PartialEq for XPCShmemRegion {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.written() == other.written()
+    }
+}
+
+impl fmt::Debug for XPCShmemRegion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("XPCShmemRegion")
+            .field("len", &self.len)
+            .field("written", &self.written().len())
+            .finish()
+    }
+}
+
+/// Shmem regions are mostly empty, so fixtures only keep the size and the
+/// bytes up to the last one written
+#[derive(Serialize, Deserialize)]
+struct ShmemContents {
+    size: usize,
+    contents: Vec<u8>,
+}
+
+impl Serialize for XPCShmemRegion {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ShmemContents {
+            size: self.len,
+            contents: self.written().to_vec(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for XPCShmemRegion {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let ShmemContents { size, contents } = ShmemContents::deserialize(deserializer)?;
+
+        Ok(Self {
+            len: size.max(contents.len()),
+            bytes: ShmemBytes::Owned(contents),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::os::unix::prelude::{AsRawFd, OwnedFd};
+
+    use super::{XPCFd, XPCShmemRegion, XPCUuid, XPCValue, XPCValueMap};
+
+    #[test]
+    fn uuid_display() {
+        let uuid = XPCUuid([
+            0x5f, 0x2c, 0x8e, 0x10, 0x0b, 0x3a, 0x4c, 0x71, 0x9d, 0x02, 0xe6, 0x47, 0x1a, 0xbc,
+            0x00, 0xff,
+        ]);
+
+        assert_eq!(uuid.to_string(), "5F2C8E10-0B3A-4C71-9D02-E6471ABC00FF");
+        assert_eq!(XPCValue::from(uuid), XPCValue::Uuid(uuid.0));
+    }
+
+    #[test]
+    fn bytes_are_data() {
+        assert_eq!(XPCValue::from(vec![1u8, 2]), XPCValue::Data(vec![1, 2]));
+        assert_eq!(XPCValue::from(&b"ok"[..]), XPCValue::Data(b"ok".to_vec()));
+        assert_eq!(XPCValue::from(()), XPCValue::Null);
+    }
+
+    #[test]
+    fn xpc_type_names() {
+        assert_eq!(XPCValue::from(1_i64).xpc_type_name(), "int64");
+        assert_eq!(XPCValue::from(1_u64).xpc_type_name(), "uint64");
+        assert_eq!(XPCValue::from("foo").xpc_type_name(), "string");
+        assert_eq!(XPCValue::Null.xpc_type_name(), "null");
+        assert_eq!(XPCValue::Error(String::new()).xpc_type_name(), "error");
+        assert_eq!(
+            XPCValue::from(HashMap::<String, XPCValue>::new()).xpc_type_name(),
+            "dictionary"
+        );
+    }
+
+    #[test]
+    fn accessors_check_variant() {
+        let value = XPCValue::from(42_i64);
+
+        assert_eq!(value.as_i64(), Some(42));
+        assert_eq!(value.as_u64(), None);
+        assert_eq!(value.as_str(), None);
+    }
+
+    #[test]
+    fn nested_get() {
+        let mut service: XPCValueMap = HashMap::new();
+        service.insert("PID".to_string(), 165_i64.into());
+        service.insert("Label".to_string(), "com.apple.usbmuxd".into());
+
+        let mut reply: XPCValueMap = HashMap::new();
+        reply.insert("service".to_string(), service.into());
+        let reply: XPCValue = reply.into();
+
+        assert_eq!(
+            reply
+                .get("service")
+                .and_then(|s| s.get("PID"))
+                .and_then(|p| p.as_i64()),
+            Some(165)
+        );
+        assert_eq!(
+            reply
+                .get("service")
+                .and_then(|s| s.get("Label"))
+                .and_then(|l| l.as_str()),
+            Some("com.apple.usbmuxd")
+        );
+        assert!(reply.get("nope").is_none());
+        assert!(XPCValue::from(1_u64).get("service").is_none());
+    }
+
+    #[test]
+    fn array_from_vec() {
+        let array = XPCValue::from(vec!["eins", "zwei", "polizei"]);
+
+        assert_eq!(
+            array
+                .as_array()
+                .map(|a| a.iter().filter_map(|v| v.as_str()).collect()),
+            Some(vec!["eins", "zwei", "polizei"])
+        );
+    }
+
+    #[test]
+    fn fd_is_owned() {
+        let (read, write) = std::os::unix::net::UnixStream::pair().unwrap();
+        let raw = read.as_raw_fd();
+        let value = XPCValue::Fd(XPCFd::from(OwnedFd::from(read)));
+        let clone = value.clone();
+
+        // Open until the last clone is dropped
+        drop(value);
+        assert_ne!(unsafe { libc::fcntl(raw, libc::F_GETFD) }, -1);
+        drop(clone);
+        assert_eq!(unsafe { libc::fcntl(raw, libc::F_GETFD) }, -1);
+
+        // Fixtures keep the number but not the descriptor
+        let json = serde_json::to_string(&XPCValue::Fd(XPCFd::from(OwnedFd::from(write)))).unwrap();
+        let back: XPCValue = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, XPCValue::Fd(XPCFd::default()));
+    }
+
+    #[test]
+    fn shmem_fixture_is_trimmed() {
+        let mut shmem = vec![0; 64];
+        shmem[..5].copy_from_slice(b"hello");
+
+        let json = serde_json::to_string(&XPCValue::Shmem(shmem.clone().into())).unwrap();
+        assert_eq!(
+            json,
+            r#"{"Shmem":{"size":64,"contents":[104,101,108,108,111]}}"#
+        );
+
+        let back: XPCValue = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, XPCValue::Shmem(shmem.into()));
+    }
+
+    #[test]
+    fn shmem_is_not_allocated_up_front() {
+        let mut region = XPCShmemRegion::new(0x1400000);
+        assert!(region.contents().is_empty());
+
+        region.write(b"ok");
+        assert_eq!(region.contents(), b"ok");
+        assert_eq!(region.read(4), b"ok\0\0");
+        assert_eq!(region.len(), 0x1400000);
+
+        region.truncate(1);
+        assert_eq!(region.to_vec(), b"o");
+        assert_eq!(
+            XPCValue::Shmem(region),
+            XPCValue::Shmem(b"o".to_vec().into())
+        );
+    }
+}
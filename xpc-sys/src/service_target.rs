@@ -0,0 +1,244 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::enums::DomainType;
+use crate::object::xpc_error::XPCError;
+use crate::object::xpc_error::XPCError::ValueError;
+use crate::rs_geteuid;
+
+/// A launchctl domain-target, optionally with a service name:
+/// `system/com.apple.lskdd`, `gui/501`, `pid/1613/com.apple.security.pboxd`.
+/// See doc/launchctl_messages.md for how each maps to "type" and "handle".
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct ServiceTarget {
+    pub domain: DomainType,
+    /// UID for user and gui, ASID for login and session, PID for pid,
+    /// 0 for system
+    pub handle: u64,
+    /// Service label, None for the whole domain
+    pub name: Option<String>,
+}
+
+impl ServiceTarget {
+    pub fn system() -> Self {
+        Self::new(DomainType::System, 0)
+    }
+
+    pub fn user(uid: u64) -> Self {
+        Self::new(DomainType::User, uid)
+    }
+
+    pub fn gui(uid: u64) -> Self {
+        Self::new(DomainType::GUI, uid)
+    }
+
+    pub fn login(asid: u64) -> Self {
+        Self::new(DomainType::UserLogin, asid)
+    }
+
+    pub fn session(asid: u64) -> Self {
+        Self::new(DomainType::Session, asid)
+    }
+
+    pub fn pid(pid: u64) -> Self {
+        Self::new(DomainType::PID, pid)
+    }
+
+    pub fn new(domain: DomainType, handle: u64) -> Self {
+        Self {
+            domain,
+            handle,
+            name: None,
+        }
+    }
+
+    /// Target a service in this domain
+    pub fn with_name<S: Into<String>>(self, name: S) -> Self {
+        Self {
+            name: Some(name.into()),
+            ..self
+        }
+    }
+
+    /// The domain alone, without the service name
+    pub fn domain_target(&self) -> Self {
+        Self::new(self.domain.clone(), self.handle)
+    }
+
+    /// launchctl's name for the domain, if it has one
+    fn prefix(&self) -> Option<&'static str> {
+        let prefix = match self.domain {
+            DomainType::System => "system",
+            DomainType::User => "user",
+            DomainType::UserLogin => "login",
+            DomainType::Session => "session",
+            DomainType::PID => "pid",
+            DomainType::GUI => "gui",
+            _ => return None,
+        };
+
+        Some(prefix)
+    }
+}
+
+impl From<DomainType> for ServiceTarget {
+    /// The domain as launchk has always addressed it: user is our own GUI
+    /// domain, anything else has handle 0
+    fn from(domain: DomainType) -> Self {
+        match domain {
+            DomainType::User | DomainType::GUI => Self::gui(rs_geteuid() as u64),
+            domain => Self::new(domain, 0),
+        }
+    }
+}
+
+impl FromStr for ServiceTarget {
+    type Err = XPCError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(3, '/');
+        let prefix = parts.next().unwrap_or("");
+
+        let domain = match prefix {
+            "system" => DomainType::System,
+            "user" => DomainType::User,
+            "login" => DomainType::UserLogin,
+            "session" => DomainType::Session,
+            "pid" => DomainType::PID,
+            "gui" => DomainType::GUI,
+            _ => {
+                return Err(ValueError(format!(
+                    "Unknown domain {} in service target {}",
+                    prefix, s
+                )))
+            }
+        };
+
+        // system has no handle, so the name comes right after it
+        let handle = if domain == DomainType::System {
+            0
+        } else {
+            let handle = parts.next().unwrap_or("");
+
+            handle.parse::<u64>().map_err(|_| {
+                ValueError(format!(
+                    "{} needs a numeric handle in service target {}",
+                    prefix, s
+                ))
+            })?
+        };
+
+        let name = parts
+            .collect::<Vec<&str>>()
+            .join("/")
+            .trim_end_matches('/')
+            .to_string();
+
+        let target = Self::new(domain, handle);
+
+        if name.is_empty() {
+            Ok(target)
+        } else {
+            Ok(target.with_name(name))
+        }
+    }
+}
+
+impl fmt::Display for ServiceTarget {
+    /// launchctl syntax. Domains it has no syntax for (e.g. the
+    /// requestor's domain) are written as "RequestorDomain/0".
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.prefix(), &self.domain) {
+            (Some(prefix), DomainType::System) => write!(f, "{}", prefix)?,
+            (Some(prefix), _) => write!(f, "{}/{}", prefix, self.handle)?,
+            (None, domain) => write!(f, "{}/{}", domain, self.handle)?,
+        }
+
+        if let Some(name) = &self.name {
+            write!(f, "/{}", name)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::enums::DomainType;
+
+    use super::ServiceTarget;
+
+    #[test]
+    fn parse_doc_targets() {
+        let cases = [
+            (
+                "system/com.apple.lskdd",
+                DomainType::System,
+                0,
+                Some("com.apple.lskdd"),
+            ),
+            ("user/501", DomainType::User, 501, None),
+            (
+                "login/100006/com.apple.assistantd",
+                DomainType::UserLogin,
+                100006,
+                Some("com.apple.assistantd"),
+            ),
+            ("session/100006", DomainType::Session, 100006, None),
+            (
+                "pid/1613/com.apple.security.pboxd",
+                DomainType::PID,
+                1613,
+                Some("com.apple.security.pboxd"),
+            ),
+            (
+                "gui/501/com.apple.usernoted",
+                DomainType::GUI,
+                501,
+                Some("com.apple.usernoted"),
+            ),
+        ];
+
+        for (s, domain, handle, name) in cases.iter() {
+            let target = ServiceTarget::from_str(s).unwrap();
+
+            assert_eq!(target.domain, *domain);
+            assert_eq!(target.handle, *handle);
+            assert_eq!(target.name.as_deref(), *name);
+            assert_eq!(target.to_string(), *s);
+        }
+    }
+
+    #[test]
+    fn domain_only() {
+        assert_eq!("system".parse(), Ok(ServiceTarget::system()));
+        assert_eq!("system/".parse(), Ok(ServiceTarget::system()));
+        assert_eq!("gui/501/".parse(), Ok(ServiceTarget::gui(501)));
+        assert_eq!(
+            ServiceTarget::pid(1613)
+                .with_name("com.apple.security.pboxd")
+                .domain_target(),
+            ServiceTarget::pid(1613)
+        );
+    }
+
+    #[test]
+    fn invalid_targets() {
+        assert!("".parse::<ServiceTarget>().is_err());
+        assert!("gui".parse::<ServiceTarget>().is_err());
+        assert!("gui/me".parse::<ServiceTarget>().is_err());
+        assert!("domain/501".parse::<ServiceTarget>().is_err());
+    }
+
+    #[test]
+    fn type_numbers() {
+        let types: Vec<u64> = ["system", "user/0", "login/0", "session/0", "pid/0", "gui/0"]
+            .iter()
+            .map(|s| s.parse::<ServiceTarget>().unwrap().domain as u64)
+            .collect();
+
+        assert_eq!(types, vec![1, 2, 3, 4, 5, 8]);
+    }
+}
@@ -0,0 +1,474 @@
+use std::collections::hash_map;
+use std::convert::TryFrom;
+use std::slice;
+use std::time::UNIX_EPOCH;
+
+use serde::de::value::{BorrowedStrDeserializer, MapDeserializer, SeqDeserializer};
+use serde::de::{
+    self, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess, VariantAccess,
+    Visitor,
+};
+use serde::forward_to_deserialize_any;
+
+use crate::object::xpc_value::XPCValue;
+use crate::xpc_serde::error::{Error, ErrorKind, PathSegment};
+
+/// Deserializes borrowed XPC values into Rust types.
+///
+/// XPC types must match what the Rust type asks for (an int64 will not
+/// fill a u64), and narrowing integers are range checked. With `strict`,
+/// dictionary keys not declared by the struct are errors instead of
+/// being ignored.
+#[derive(Clone, Copy)]
+pub struct Deserializer<'de> {
+    value: &'de XPCValue,
+    strict: bool,
+}
+
+impl<'de> Deserializer<'de> {
+    pub fn new(value: &'de XPCValue) -> Self {
+        Self {
+            value,
+            strict: false,
+        }
+    }
+
+    pub fn strict(value: &'de XPCValue) -> Self {
+        Self {
+            value,
+            strict: true,
+        }
+    }
+
+    fn child(&self, value: &'de XPCValue) -> Self {
+        Self {
+            value,
+            strict: self.strict,
+        }
+    }
+
+    fn wrong_type(&self, expected: &str) -> Error {
+        Error::wrong_type(expected, self.value.xpc_type_name())
+    }
+
+    fn signed<T: TryFrom<i64>>(&self) -> Result<T, Error> {
+        let i = self
+            .value
+            .as_i64()
+            .ok_or_else(|| self.wrong_type("int64"))?;
+        T::try_from(i).map_err(|_| Error::new(ErrorKind::OutOfRange(i.to_string())))
+    }
+
+    fn unsigned<T: TryFrom<u64>>(&self) -> Result<T, Error> {
+        let u = self
+            .value
+            .as_u64()
+            .ok_or_else(|| self.wrong_type("uint64"))?;
+        T::try_from(u).map_err(|_| Error::new(ErrorKind::OutOfRange(u.to_string())))
+    }
+
+    fn bytes(&self) -> Result<&'de [u8], Error> {
+        match self.value {
+            XPCValue::Data(b) => Ok(b.as_slice()),
+            XPCValue::Shmem(s) => Ok(s.contents()),
+            XPCValue::Uuid(u) => Ok(u),
+            _ => Err(self.wrong_type("data")),
+        }
+    }
+
+    /// Dates are handed to serde the same way it serializes SystemTime
+    fn visit_date<V: Visitor<'de>>(&self, visitor: V) -> Result<V::Value, Error> {
+        let since_epoch = match self.value {
+            XPCValue::Date(d) => d
+                .duration_since(UNIX_EPOCH)
+                .map_err(|e| Error::new(ErrorKind::OutOfRange(e.to_string())))?,
+            _ => return Err(self.wrong_type("date")),
+        };
+
+        let fields = vec![
+            ("secs_since_epoch", since_epoch.as_secs()),
+            ("nanos_since_epoch", since_epoch.subsec_nanos() as u64),
+        ];
+
+        visitor.visit_map(MapDeserializer::new(fields.into_iter()))
+    }
+
+    fn visit_dictionary<V: Visitor<'de>>(
+        &self,
+        fields: Option<&'static [&'static str]>,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.value {
+            XPCValue::Dictionary(dict) => visitor.visit_map(DictionaryAccess {
+                iter: dict.iter(),
+                current: None,
+                fields: if self.strict { fields } else { None },
+                strict: self.strict,
+            }),
+            _ => Err(self.wrong_type("dictionary")),
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value {
+            XPCValue::Int64(i) => visitor.visit_i64(*i),
+            XPCValue::UInt64(u) => visitor.visit_u64(*u),
+            XPCValue::Double(d) => visitor.visit_f64(*d),
+            XPCValue::Bool(b) => visitor.visit_bool(*b),
+            XPCValue::String(s) => visitor.visit_borrowed_str(s),
+            XPCValue::Data(b) => visitor.visit_borrowed_bytes(b),
+            XPCValue::Shmem(s) => visitor.visit_borrowed_bytes(s.contents()),
+            XPCValue::Uuid(u) => visitor.visit_borrowed_bytes(u),
+            XPCValue::Date(_) => self.visit_date(visitor),
+            XPCValue::Array(_) => self.deserialize_seq(visitor),
+            XPCValue::Dictionary(_) => self.visit_dictionary(None, visitor),
+            XPCValue::Fd(fd) => visitor.visit_i32(fd.as_raw_fd().unwrap_or(-1)),
+            XPCValue::MachSend(p) | XPCValue::MachRecv(p) => visitor.visit_u32(*p),
+            XPCValue::Endpoint(s) | XPCValue::Error(s) => visitor.visit_borrowed_str(s),
+            XPCValue::Null => visitor.visit_unit(),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let b = self
+            .value
+            .as_bool()
+            .ok_or_else(|| self.wrong_type("bool"))?;
+        visitor.visit_bool(b)
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_i8(self.signed()?)
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_i16(self.signed()?)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_i32(self.signed()?)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_i64(self.signed()?)
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_u8(self.unsigned()?)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_u16(self.unsigned()?)
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_u32(self.unsigned()?)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_u64(self.unsigned()?)
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let d = self
+            .value
+            .as_f64()
+            .ok_or_else(|| self.wrong_type("double"))?;
+        visitor.visit_f32(d as f32)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let d = self
+            .value
+            .as_f64()
+            .ok_or_else(|| self.wrong_type("double"))?;
+        visitor.visit_f64(d)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value {
+            XPCValue::String(s) => visitor.visit_borrowed_str(s),
+            _ => Err(self.wrong_type("string")),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_borrowed_bytes(self.bytes()?)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value {
+            XPCValue::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value {
+            XPCValue::Null => visitor.visit_unit(),
+            _ => Err(self.wrong_type("null")),
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value {
+            XPCValue::Array(values) => visitor.visit_seq(ArrayAccess {
+                iter: values.iter(),
+                index: 0,
+                strict: self.strict,
+            }),
+            // Vec<u8> asks for a sequence
+            XPCValue::Data(_) | XPCValue::Shmem(_) | XPCValue::Uuid(_) => {
+                let bytes = self.bytes()?.iter().copied();
+                visitor.visit_seq(SeqDeserializer::new(bytes))
+            }
+            _ => Err(self.wrong_type("array")),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.visit_dictionary(None, visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.value {
+            XPCValue::Date(_) if name == "SystemTime" => self.visit_date(visitor),
+            _ => self.visit_dictionary(Some(fields), visitor),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.value {
+            XPCValue::String(s) => visitor.visit_enum(s.as_str().into_deserializer()),
+            XPCValue::Dictionary(dict) if dict.len() == 1 => {
+                let (variant, value) = dict.iter().next().unwrap();
+                visitor.visit_enum(VariantDictionary {
+                    variant,
+                    value: self.child(value),
+                })
+            }
+            _ => Err(self.wrong_type("string or single key dictionary")),
+        }
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! { i128 u128 }
+}
+
+struct ArrayAccess<'de> {
+    iter: slice::Iter<'de, XPCValue>,
+    index: usize,
+    strict: bool,
+}
+
+impl<'de> SeqAccess<'de> for ArrayAccess<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        let value = match self.iter.next() {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+
+        let index = self.index;
+        self.index += 1;
+
+        let deserializer = Deserializer {
+            value,
+            strict: self.strict,
+        };
+
+        seed.deserialize(deserializer)
+            .map(Some)
+            .map_err(|e| e.prefixed(PathSegment::Index(index)))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct DictionaryAccess<'de> {
+    iter: hash_map::Iter<'de, String, XPCValue>,
+    current: Option<(&'de String, &'de XPCValue)>,
+    /// Declared struct fields, only set in strict mode
+    fields: Option<&'static [&'static str]>,
+    strict: bool,
+}
+
+impl<'de> MapAccess<'de> for DictionaryAccess<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        let (key, value) = match self.iter.next() {
+            Some(kv) => kv,
+            None => return Ok(None),
+        };
+
+        if let Some(fields) = self.fields {
+            if !fields.contains(&key.as_str()) {
+                return Err(Error::at_key(key.as_str(), ErrorKind::UnknownKey));
+            }
+        }
+
+        self.current = Some((key, value));
+        seed.deserialize(BorrowedStrDeserializer::new(key))
+            .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let (key, value) = self
+            .current
+            .take()
+            .ok_or_else(|| Error::new(ErrorKind::Custom("Value without key".to_string())))?;
+
+        let deserializer = Deserializer {
+            value,
+            strict: self.strict,
+        };
+
+        seed.deserialize(deserializer)
+            .map_err(|e| e.prefixed(PathSegment::Key(key.clone())))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+/// Externally tagged enum: { "Variant": value }
+struct VariantDictionary<'de> {
+    variant: &'de String,
+    value: Deserializer<'de>,
+}
+
+impl<'de> EnumAccess<'de> for VariantDictionary<'de> {
+    type Error = Error;
+    type Variant = VariantValue<'de>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, VariantValue<'de>), Error> {
+        let variant = seed.deserialize(BorrowedStrDeserializer::<Error>::new(self.variant))?;
+
+        Ok((
+            variant,
+            VariantValue {
+                variant: self.variant,
+                value: self.value,
+            },
+        ))
+    }
+}
+
+struct VariantValue<'de> {
+    variant: &'de String,
+    value: Deserializer<'de>,
+}
+
+impl<'de> VariantValue<'de> {
+    fn prefixed(&self, e: Error) -> Error {
+        e.prefixed(PathSegment::Key(self.variant.clone()))
+    }
+}
+
+impl<'de> VariantAccess<'de> for VariantValue<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        let result: Result<(), Error> = de::Deserialize::deserialize(self.value);
+        result.map_err(|e| self.prefixed(e))
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        let result = seed.deserialize(self.value);
+        result.map_err(|e| self.prefixed(e))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        let result = de::Deserializer::deserialize_seq(self.value, visitor);
+        result.map_err(|e| self.prefixed(e))
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let result = self.value.visit_dictionary(Some(fields), visitor);
+        result.map_err(|e| self.prefixed(e))
+    }
+}
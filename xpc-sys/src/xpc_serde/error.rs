@@ -0,0 +1,141 @@
+use std::error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use crate::object::xpc_error::XPCError;
+
+/// One step into an XPC value tree
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ErrorKind {
+    /// A struct field has no matching key in the dictionary
+    MissingKey,
+    /// The dictionary has a key that the struct does not declare
+    UnknownKey,
+    /// Value is a different XPC type than requested
+    WrongType {
+        expected: String,
+        found: String,
+    },
+    /// Value is the right type but out of range for the Rust type
+    OutOfRange(String),
+    /// The data model cannot be represented in XPC
+    Unsupported(String),
+    Custom(String),
+}
+
+/// serde error that knows where in the tree it happened
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Error {
+    pub path: Vec<PathSegment>,
+    pub kind: ErrorKind,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind) -> Self {
+        Self { path: vec![], kind }
+    }
+
+    pub fn wrong_type<E: Into<String>, F: Into<String>>(expected: E, found: F) -> Self {
+        Self::new(ErrorKind::WrongType {
+            expected: expected.into(),
+            found: found.into(),
+        })
+    }
+
+    /// Error for a dictionary key, with the key as the last path segment
+    pub fn at_key<S: Into<String>>(key: S, kind: ErrorKind) -> Self {
+        Self {
+            path: vec![PathSegment::Key(key.into())],
+            kind,
+        }
+    }
+
+    /// Push a parent segment onto the front of the path
+    pub fn prefixed(mut self, segment: PathSegment) -> Self {
+        self.path.insert(0, segment);
+        self
+    }
+
+    /// Dotted key path, e.g. services.com.apple.foo.PID or ProgramArguments[1]
+    pub fn path_string(&self) -> String {
+        let mut path = String::new();
+
+        for segment in &self.path {
+            match segment {
+                PathSegment::Key(k) => {
+                    if !path.is_empty() {
+                        path.push('.');
+                    }
+                    path.push_str(k);
+                }
+                PathSegment::Index(i) => path.push_str(&format!("[{}]", i)),
+            }
+        }
+
+        path
+    }
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::MissingKey => write!(f, "Missing key"),
+            ErrorKind::UnknownKey => write!(f, "Unknown key"),
+            // Same wording as check_xpc_type
+            ErrorKind::WrongType { expected, found } => {
+                write!(f, "Cannot get {} as {}", found, expected)
+            }
+            ErrorKind::OutOfRange(e) => write!(f, "Out of range: {}", e),
+            ErrorKind::Unsupported(e) => write!(f, "Unsupported: {}", e),
+            ErrorKind::Custom(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.kind)
+        } else {
+            write!(f, "{}: {}", self.path_string(), self.kind)
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl serde::ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::new(ErrorKind::Custom(msg.to_string()))
+    }
+}
+
+impl serde::de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::new(ErrorKind::Custom(msg.to_string()))
+    }
+
+    fn invalid_type(unexp: serde::de::Unexpected, exp: &dyn serde::de::Expected) -> Self {
+        Error::wrong_type(exp.to_string(), unexp.to_string())
+    }
+
+    fn unknown_field(field: &str, _expected: &'static [&'static str]) -> Self {
+        Error::at_key(field, ErrorKind::UnknownKey)
+    }
+
+    fn missing_field(field: &'static str) -> Self {
+        Error::at_key(field, ErrorKind::MissingKey)
+    }
+}
+
+impl From<Error> for XPCError {
+    fn from(e: Error) -> Self {
+        XPCError::ValueError(e.to_string())
+    }
+}
@@ -0,0 +1,200 @@
+//! serde data format for XPC values, so requests and replies can be
+//! typed structs instead of hand built dictionaries.
+
+#[cfg(target_os = "macos")]
+use std::convert::TryFrom;
+
+#[cfg(target_os = "macos")]
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+#[cfg(target_os = "macos")]
+use serde::Serialize;
+
+#[cfg(target_os = "macos")]
+use crate::object::try_xpc_into_rust::TryXPCIntoRust;
+#[cfg(target_os = "macos")]
+use crate::object::xpc_error::XPCError;
+#[cfg(target_os = "macos")]
+use crate::object::xpc_object::XPCObject;
+use crate::object::xpc_value::XPCValue;
+
+pub mod de;
+pub mod error;
+pub mod ser;
+
+pub use de::Deserializer;
+pub use error::{Error, ErrorKind, PathSegment};
+pub use ser::{to_xpc_value, Serializer};
+
+/// Deserialize from an XPCValue, ignoring keys the type does not declare
+pub fn from_xpc_value<'de, T: Deserialize<'de>>(value: &'de XPCValue) -> Result<T, Error> {
+    T::deserialize(Deserializer::new(value))
+}
+
+/// Deserialize from an XPCValue, failing on keys the type does not declare
+pub fn from_xpc_value_strict<'de, T: Deserialize<'de>>(value: &'de XPCValue) -> Result<T, Error> {
+    T::deserialize(Deserializer::strict(value))
+}
+
+/// Serialize straight to an XPC object, e.g. for pipe_routine()
+#[cfg(target_os = "macos")]
+pub fn to_xpc_object<T: Serialize + ?Sized>(value: &T) -> Result<XPCObject, Error> {
+    let value = to_xpc_value(value)?;
+    XPCObject::try_from(value).map_err(|e| Error::new(ErrorKind::Custom(e.to_string())))
+}
+
+/// Deserialize a reply XPC object
+#[cfg(target_os = "macos")]
+pub fn from_xpc_object<T: DeserializeOwned>(object: &XPCObject) -> Result<T, XPCError> {
+    let value: XPCValue = object.to_rust()?;
+    Ok(from_xpc_value(&value)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use serde::{Deserialize, Serialize};
+
+    use crate::object::xpc_value::{XPCValue, XPCValueMap};
+
+    use super::{from_xpc_value, from_xpc_value_strict, to_xpc_value, ErrorKind};
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Service {
+        #[serde(rename = "PID")]
+        pid: i64,
+        #[serde(rename = "LimitLoadToSessionType")]
+        session_type: String,
+        #[serde(rename = "ProgramArguments")]
+        program_arguments: Vec<String>,
+        #[serde(rename = "LastExitStatus")]
+        last_exit_status: Option<i64>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Reply {
+        service: Service,
+    }
+
+    fn reply() -> Reply {
+        Reply {
+            service: Service {
+                pid: 165,
+                session_type: "System".to_string(),
+                program_arguments: vec!["/usr/libexec/usbmuxd".to_string(), "-launchd".to_string()],
+                last_exit_status: None,
+            },
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let value = to_xpc_value(&reply()).unwrap();
+
+        assert_eq!(
+            value.get("service").and_then(|s| s.get("PID")),
+            Some(&XPCValue::Int64(165))
+        );
+        // None fields are left out, not sent as null
+        assert!(value
+            .get("service")
+            .and_then(|s| s.get("LastExitStatus"))
+            .is_none());
+
+        let back: Reply = from_xpc_value(&value).unwrap();
+        assert_eq!(back, reply());
+    }
+
+    #[test]
+    fn missing_key_has_path() {
+        let mut value = to_xpc_value(&reply()).unwrap();
+        if let XPCValue::Dictionary(d) = &mut value {
+            if let Some(XPCValue::Dictionary(s)) = d.get_mut("service") {
+                s.remove("PID");
+            }
+        }
+
+        let err = from_xpc_value::<Reply>(&value).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::MissingKey);
+        assert_eq!(err.to_string(), "service.PID: Missing key");
+    }
+
+    #[test]
+    fn wrong_type_has_path() {
+        let mut value = to_xpc_value(&reply()).unwrap();
+        if let XPCValue::Dictionary(d) = &mut value {
+            if let Some(XPCValue::Dictionary(s)) = d.get_mut("service") {
+                s.insert(
+                    "ProgramArguments".to_string(),
+                    XPCValue::from(vec![XPCValue::from("ok"), XPCValue::from(1_u64)]),
+                );
+            }
+        }
+
+        let err = from_xpc_value::<Reply>(&value).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "service.ProgramArguments[1]: Cannot get uint64 as string"
+        );
+    }
+
+    #[test]
+    fn unknown_key_only_when_strict() {
+        let mut value = to_xpc_value(&reply()).unwrap();
+        if let XPCValue::Dictionary(d) = &mut value {
+            if let Some(XPCValue::Dictionary(s)) = d.get_mut("service") {
+                s.insert("Extra".to_string(), XPCValue::Bool(true));
+            }
+        }
+
+        assert!(from_xpc_value::<Reply>(&value).is_ok());
+
+        let err = from_xpc_value_strict::<Reply>(&value).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::UnknownKey);
+        assert_eq!(err.path_string(), "service.Extra");
+    }
+
+    #[test]
+    fn out_of_range() {
+        let value = XPCValue::UInt64(u64::MAX);
+        let err = from_xpc_value::<u32>(&value).unwrap_err();
+
+        assert_eq!(err.kind, ErrorKind::OutOfRange(u64::MAX.to_string()));
+    }
+
+    #[test]
+    fn system_time_is_date() {
+        let time = UNIX_EPOCH + Duration::new(1_600_000_000, 42);
+        let value = to_xpc_value(&time).unwrap();
+
+        assert_eq!(value, XPCValue::Date(time));
+        assert_eq!(from_xpc_value::<SystemTime>(&value).unwrap(), time);
+    }
+
+    #[test]
+    fn enums() {
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        enum Target {
+            System,
+            Pid(i64),
+        }
+
+        let system = to_xpc_value(&Target::System).unwrap();
+        assert_eq!(system, XPCValue::from("System"));
+        assert_eq!(from_xpc_value::<Target>(&system).unwrap(), Target::System);
+
+        let pid = to_xpc_value(&Target::Pid(1)).unwrap();
+        let mut expected: XPCValueMap = HashMap::new();
+        expected.insert("Pid".to_string(), XPCValue::Int64(1));
+        assert_eq!(pid, XPCValue::Dictionary(expected));
+        assert_eq!(from_xpc_value::<Target>(&pid).unwrap(), Target::Pid(1));
+    }
+
+    #[test]
+    fn bytes_from_data() {
+        let value = XPCValue::Data(vec![1, 2, 3]);
+        assert_eq!(from_xpc_value::<Vec<u8>>(&value).unwrap(), vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,414 @@
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::time::{Duration, UNIX_EPOCH};
+
+use serde::ser;
+use serde::Serialize;
+
+use crate::object::xpc_value::{XPCValue, XPCValueMap};
+use crate::xpc_serde::error::{Error, ErrorKind, PathSegment};
+
+/// Serialize anything implementing Serialize into an XPCValue
+pub fn to_xpc_value<T: Serialize + ?Sized>(value: &T) -> Result<XPCValue, Error> {
+    value.serialize(Serializer)
+}
+
+/// Serializes the serde data model into owned XPC values:
+///
+/// - Integers become int64 or uint64 by signedness, floats become double
+/// - Structs and maps become dictionaries, sequences and tuples arrays
+/// - `None` and `()` become null, and `None` struct fields are left out
+///   entirely (like DictBuilder::entry_if_present)
+/// - `SystemTime` becomes a date
+pub struct Serializer;
+
+fn int64<T: TryInto<i64> + ToString + Copy>(v: T) -> Result<XPCValue, Error> {
+    v.try_into()
+        .map(XPCValue::Int64)
+        .map_err(|_| Error::new(ErrorKind::OutOfRange(v.to_string())))
+}
+
+fn uint64<T: TryInto<u64> + ToString + Copy>(v: T) -> Result<XPCValue, Error> {
+    v.try_into()
+        .map(XPCValue::UInt64)
+        .map_err(|_| Error::new(ErrorKind::OutOfRange(v.to_string())))
+}
+
+fn single_entry(key: &str, value: XPCValue) -> XPCValue {
+    let mut dict: XPCValueMap = HashMap::new();
+    dict.insert(key.to_string(), value);
+    XPCValue::Dictionary(dict)
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = XPCValue;
+    type Error = Error;
+
+    type SerializeSeq = SerializeArray;
+    type SerializeTuple = SerializeArray;
+    type SerializeTupleStruct = SerializeArray;
+    type SerializeTupleVariant = SerializeArray;
+    type SerializeMap = SerializeDictionary;
+    type SerializeStruct = SerializeDictionary;
+    type SerializeStructVariant = SerializeDictionary;
+
+    fn serialize_bool(self, v: bool) -> Result<XPCValue, Error> {
+        Ok(XPCValue::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<XPCValue, Error> {
+        int64(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<XPCValue, Error> {
+        int64(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<XPCValue, Error> {
+        int64(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<XPCValue, Error> {
+        int64(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<XPCValue, Error> {
+        int64(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<XPCValue, Error> {
+        uint64(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<XPCValue, Error> {
+        uint64(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<XPCValue, Error> {
+        uint64(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<XPCValue, Error> {
+        uint64(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<XPCValue, Error> {
+        uint64(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<XPCValue, Error> {
+        Ok(XPCValue::Double(v as f64))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<XPCValue, Error> {
+        Ok(XPCValue::Double(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<XPCValue, Error> {
+        Ok(XPCValue::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<XPCValue, Error> {
+        Ok(XPCValue::String(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<XPCValue, Error> {
+        Ok(XPCValue::Data(v.to_vec()))
+    }
+
+    fn serialize_none(self) -> Result<XPCValue, Error> {
+        Ok(XPCValue::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<XPCValue, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<XPCValue, Error> {
+        Ok(XPCValue::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<XPCValue, Error> {
+        Ok(XPCValue::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<XPCValue, Error> {
+        Ok(XPCValue::String(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<XPCValue, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<XPCValue, Error> {
+        let inner = value
+            .serialize(Serializer)
+            .map_err(|e| e.prefixed(PathSegment::Key(variant.to_string())))?;
+
+        Ok(single_entry(variant, inner))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeArray, Error> {
+        Ok(SerializeArray {
+            variant: None,
+            values: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeArray, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeArray, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeArray, Error> {
+        Ok(SerializeArray {
+            variant: Some(variant),
+            values: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeDictionary, Error> {
+        Ok(SerializeDictionary::new(None, None))
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        _len: usize,
+    ) -> Result<SerializeDictionary, Error> {
+        Ok(SerializeDictionary::new(Some(name), None))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<SerializeDictionary, Error> {
+        Ok(SerializeDictionary::new(Some(name), Some(variant)))
+    }
+}
+
+pub struct SerializeArray {
+    variant: Option<&'static str>,
+    values: Vec<XPCValue>,
+}
+
+impl SerializeArray {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let index = self.values.len();
+        let value = value
+            .serialize(Serializer)
+            .map_err(|e| e.prefixed(PathSegment::Index(index)))?;
+
+        self.values.push(value);
+        Ok(())
+    }
+
+    fn finish(self) -> XPCValue {
+        match self.variant {
+            Some(variant) => single_entry(variant, XPCValue::Array(self.values)),
+            None => XPCValue::Array(self.values),
+        }
+    }
+}
+
+impl ser::SerializeSeq for SerializeArray {
+    type Ok = XPCValue;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<XPCValue, Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTuple for SerializeArray {
+    type Ok = XPCValue;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<XPCValue, Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeArray {
+    type Ok = XPCValue;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<XPCValue, Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeArray {
+    type Ok = XPCValue;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<XPCValue, Error> {
+        Ok(self.finish())
+    }
+}
+
+pub struct SerializeDictionary {
+    name: Option<&'static str>,
+    variant: Option<&'static str>,
+    dict: XPCValueMap,
+    next_key: Option<String>,
+}
+
+impl SerializeDictionary {
+    fn new(name: Option<&'static str>, variant: Option<&'static str>) -> Self {
+        Self {
+            name,
+            variant,
+            dict: HashMap::new(),
+            next_key: None,
+        }
+    }
+
+    fn insert<T: Serialize + ?Sized>(&mut self, key: String, value: &T) -> Result<(), Error> {
+        let value = value
+            .serialize(Serializer)
+            .map_err(|e| e.prefixed(PathSegment::Key(key.clone())))?;
+
+        self.dict.insert(key, value);
+        Ok(())
+    }
+
+    /// serde models SystemTime as a struct of seconds and nanoseconds
+    fn as_date(&self) -> Option<XPCValue> {
+        let secs = self.dict.get("secs_since_epoch")?.as_u64()?;
+        let nanos = self.dict.get("nanos_since_epoch")?.as_u64()?;
+
+        Some(XPCValue::Date(
+            UNIX_EPOCH + Duration::new(secs, nanos as u32),
+        ))
+    }
+
+    fn finish(mut self) -> XPCValue {
+        if self.name.is_some() {
+            // Leave out None fields rather than sending null
+            self.dict.retain(|_, v| !v.is_null());
+        }
+
+        if self.name == Some("SystemTime") {
+            if let Some(date) = self.as_date() {
+                return date;
+            }
+        }
+
+        match self.variant {
+            Some(variant) => single_entry(variant, XPCValue::Dictionary(self.dict)),
+            None => XPCValue::Dictionary(self.dict),
+        }
+    }
+}
+
+impl ser::SerializeMap for SerializeDictionary {
+    type Ok = XPCValue;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        let key = match key.serialize(Serializer)? {
+            XPCValue::String(s) => s,
+            XPCValue::Int64(i) => i.to_string(),
+            XPCValue::UInt64(u) => u.to_string(),
+            other => return Err(Error::wrong_type("string", other.xpc_type_name())),
+        };
+
+        self.next_key = Some(key);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .next_key
+            .take()
+            .ok_or_else(|| Error::new(ErrorKind::Custom("Value without key".to_string())))?;
+
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<XPCValue, Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeStruct for SerializeDictionary {
+    type Ok = XPCValue;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<XPCValue, Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeDictionary {
+    type Ok = XPCValue;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<XPCValue, Error> {
+        Ok(self.finish())
+    }
+}